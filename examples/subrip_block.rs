@@ -1,13 +1,13 @@
-use chrono::{NaiveTime, TimeDelta};
+use chrono::TimeDelta;
 use mksubrip::prelude::*;
 use mksubrip::ui::{Drawable, SubripBlock};
-use mksubrip::Subrip;
+use mksubrip::{Subrip, Timestamp};
 
 fn main() {
     let opt = eframe::NativeOptions::default();
     let subrip = Shared::new(Subrip::new(
         "hello world.",
-        NaiveTime::from_hms_opt(0, 0, 10).unwrap(),
+        Timestamp::from_hms_milli(0, 0, 10, 0),
         TimeDelta::seconds(180),
     ));
     let _ = eframe::run_simple_native("subrip block", opt, move |ctx, _eframe| {
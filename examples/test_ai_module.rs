@@ -1,16 +1,18 @@
 use mksubrip::ai::*;
-use mksubrip::prelude::*;
 
 use std::path;
+use std::sync::Arc;
 
 fn main() {
-    let translator = AiTranslator {
-        model_type: AIModelType::OpenaiWhisper,
-        scale: AIModelScale::Base,
-    };
+    let translator = AiTranslator::default()
+        .model(AIModelType::OpenaiWhisper)
+        .scale(AIModelScale::Base);
 
     let text = translator
-        .request(path::Path::new("data/test.mp3"))
+        .transcribe(
+            path::Path::new("data/test.mp3"),
+            &Arc::new(TranscriptionProgress::default()),
+        )
         .unwrap_or_else(|err| {
             println!("{}", err);
             unreachable!()
@@ -19,7 +21,7 @@ fn main() {
 
     assert_eq!(&text[0..7], "{\"data\"");
 
-    let result = utils::json_str_to_subrips(text.as_str()).unwrap_or_else(|err| {
+    let result = mksubrip::utils::json_str_to_subrips(text.as_str()).unwrap_or_else(|err| {
         println!("{}", err);
         unreachable!()
     });
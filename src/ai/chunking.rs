@@ -0,0 +1,388 @@
+//! Long media are transcribed in chunks of their audio, which overlap a little and are cut
+//! where it's quiet, so no word is split. The transcriptions are stitched back together.
+
+use crate::prelude::*;
+use crate::subrip::WordTiming;
+use crate::Timestamp;
+
+use serde_json::json;
+use std::ops::Range;
+
+/// Whisper models take 16 kHz mono audio, and it keeps the uploads small.
+pub const SAMPLE_RATE: u32 = 16000;
+/// The audio transcribed by both chunks around a seam.
+const OVERLAP_SECS: u32 = 5;
+/// How far before the planned end of a chunk the quietest moment is looked for.
+const SILENCE_SEARCH_SECS: u32 = 30;
+/// The loudness is compared over frames of this duration.
+const FRAME_MS: u32 = 100;
+
+/// A transcribed cue, unlike a [`crate::Subrip`] it can be sent between threads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub begin_time: Timestamp,
+    pub end_time: Timestamp,
+    pub text: String,
+    /// Relative to `begin_time` like [`crate::Subrip::words`]
+    pub words: Vec<WordTiming>,
+    /// Label of a diarized transcription, empty without one. The labels of different
+    /// chunks needn't agree, so [`stitch`] prefixes them with the chunk, e.g. `2#SPEAKER_00`.
+    pub speaker: String,
+}
+
+impl Cue {
+    fn center(&self) -> Timestamp {
+        Timestamp::from_millis((self.begin_time.as_millis() + self.end_time.as_millis()) / 2)
+    }
+}
+
+pub fn samples_to_timestamp(samples: usize, sample_rate: u32) -> Timestamp {
+    Timestamp::from_millis(samples as i64 * 1000 / sample_rate as i64)
+}
+
+/// Decode the audio of `path` to mono [`SAMPLE_RATE`] samples, the video is skipped.
+/// `on_samples` receives them as they're decoded, decoding is cancelled once it returns
+/// `false`.
+pub fn extract_audio(
+    path: &std::path::Path,
+    mut on_samples: impl FnMut(&[f32]) -> bool,
+) -> Result<()> {
+    let mut sample_count = 0;
+    let mut cancelled = false;
+    crate::core::audio_decoder::decode_mono(path, SAMPLE_RATE, |samples| {
+        sample_count += samples.len();
+        cancelled = !on_samples(samples);
+
+        !cancelled
+    })?;
+    if cancelled {
+        return Err(anyhow!("Cancelled"));
+    }
+    debug!(
+        "Extracted {:.1}s of audio from {}",
+        sample_count as f32 / SAMPLE_RATE as f32,
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// The sample at the middle of the quietest frame in `range`.
+fn quietest_sample(samples: &[f32], range: Range<usize>, frame_len: usize) -> usize {
+    let frame_len = frame_len.max(1);
+
+    range
+        .clone()
+        .step_by(frame_len)
+        .min_by(|a, b| {
+            let energy = |begin: usize| -> f32 {
+                samples[begin..(begin + frame_len).min(range.end)]
+                    .iter()
+                    .map(|i| i * i)
+                    .sum()
+            };
+            energy(*a).total_cmp(&energy(*b))
+        })
+        .map(|begin| (begin + frame_len / 2).min(range.end))
+        .unwrap_or(range.end)
+}
+
+/// A chunk of the audio planned by [`ChunkPlanner`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// The samples of the whole audio it covers
+    pub span: Range<usize>,
+    pub samples: Vec<f32>,
+}
+
+/// Splits the samples into chunks of about `chunk_secs` while they're decoded, each cut at the
+/// quietest moment near its end and beginning [`OVERLAP_SECS`] before the end of the previous
+/// one. Only the samples of the chunk being planned are kept.
+pub struct ChunkPlanner {
+    chunk_len: usize,
+    overlap: usize,
+    search: usize,
+    frame_len: usize,
+    /// The samples from `begin` on
+    buffer: Vec<f32>,
+    begin: usize,
+}
+
+impl ChunkPlanner {
+    pub fn new(sample_rate: u32, chunk_secs: u32) -> Self {
+        let sample_rate = sample_rate as usize;
+        let chunk_len = chunk_secs as usize * sample_rate;
+
+        Self {
+            chunk_len,
+            overlap: OVERLAP_SECS as usize * sample_rate,
+            // Keep every chunk longer than the overlap, so the next one begins further on.
+            search: (SILENCE_SEARCH_SECS as usize * sample_rate).min(chunk_len / 4),
+            frame_len: FRAME_MS as usize * sample_rate / 1000,
+            buffer: vec![],
+            begin: 0,
+        }
+    }
+
+    /// Add the samples decoded next, return the chunks they complete.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Chunk> {
+        self.buffer.extend_from_slice(samples);
+
+        let mut chunks = vec![];
+        while self.chunk_len > self.overlap + self.search && self.buffer.len() > self.chunk_len {
+            let end = self.chunk_len;
+            let cut = quietest_sample(&self.buffer, end - self.search..end, self.frame_len);
+            chunks.push(Chunk {
+                span: self.begin..self.begin + cut,
+                samples: self.buffer[..cut].to_vec(),
+            });
+            self.buffer.drain(..cut - self.overlap);
+            self.begin += cut - self.overlap;
+        }
+
+        chunks
+    }
+
+    /// The last chunk, once all samples are pushed.
+    pub fn finish(self) -> Chunk {
+        Chunk {
+            span: self.begin..self.begin + self.buffer.len(),
+            samples: self.buffer,
+        }
+    }
+}
+
+/// The samples as a 16-bit PCM WAV file.
+pub fn wav_bytes(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    bytes
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|i| i.is_alphanumeric())
+        .flat_map(|i| i.to_lowercase())
+        .collect()
+}
+
+/// Join the cues of consecutive chunks, each given with the span of the media it covers and
+/// its cues relative to the beginning of that.
+///
+/// Around a seam, the cues of the earlier chunk are kept up to the middle of the overlap
+/// and those of the later chunk after it. A cue transcribed by both is kept once.
+///
+/// The speaker labels of several chunks are told apart by the number of the chunk. Someone
+/// speaking in two chunks becomes two speakers, but two people are never mixed up.
+pub fn stitch(chunks: Vec<(Range<Timestamp>, Vec<Cue>)>) -> Vec<Cue> {
+    let is_chunked = chunks.len() > 1;
+    let seams = chunks
+        .windows(2)
+        .map(|pair| {
+            Timestamp::from_millis((pair[1].0.start.as_millis() + pair[0].0.end.as_millis()) / 2)
+        })
+        .collect::<Vec<_>>();
+    let mut stitched: Vec<Cue> = vec![];
+
+    for (k, (span, cues)) in chunks.into_iter().enumerate() {
+        for mut cue in cues {
+            cue.begin_time = span.start + (cue.begin_time - Timestamp::ZERO);
+            cue.end_time = span.start + (cue.end_time - Timestamp::ZERO);
+            let center = cue.center();
+            let is_after_seam = k == 0 || center >= seams[k - 1];
+            let is_before_seam = seams.get(k).is_none_or(|seam| center < *seam);
+            if !is_after_seam || !is_before_seam {
+                continue;
+            }
+            if is_chunked && !cue.speaker.is_empty() {
+                cue.speaker = format!("{}#{}", k + 1, cue.speaker);
+            }
+
+            match stitched.last_mut() {
+                Some(last)
+                    if cue.begin_time < last.end_time
+                        && normalize(&last.text) == normalize(&cue.text) =>
+                {
+                    last.end_time = last.end_time.max(cue.end_time);
+                }
+                _ => stitched.push(cue),
+            }
+        }
+    }
+
+    stitched
+}
+
+/// The cues as `{"data": [{"index", "start", "end", "text", "words", "speaker"}]}` with the
+/// times in seconds, see [`crate::utils::json_str_to_subrips`].
+pub fn cues_to_json(cues: &[Cue]) -> String {
+    let data = cues
+        .iter()
+        .filter(|cue| !cue.text.trim().is_empty())
+        .enumerate()
+        .map(|(i, cue)| {
+            let seconds = |time: Timestamp| time.as_millis() as f64 / 1000.0;
+            let words = cue
+                .words
+                .iter()
+                .map(|word| {
+                    json!({
+                        "text": word.text,
+                        "start": seconds(cue.begin_time + (word.begin - Timestamp::ZERO)),
+                        "end": seconds(cue.begin_time + (word.end - Timestamp::ZERO)),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut value = json!({
+                "index": i + 1,
+                "start": seconds(cue.begin_time),
+                "end": seconds(cue.end_time),
+                "text": cue.text.trim(),
+                "words": words,
+            });
+            if !cue.speaker.is_empty() {
+                value["speaker"] = cue.speaker.clone().into();
+            }
+
+            value
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "data": data }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(begin_ms: i64, end_ms: i64, text: &str) -> Cue {
+        Cue {
+            begin_time: Timestamp::from_millis(begin_ms),
+            end_time: Timestamp::from_millis(end_ms),
+            text: text.to_string(),
+            words: vec![],
+            speaker: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_chunk_planner() {
+        // 10 samples a second, loud but for a pause at 170s and another at 330s.
+        let sample_rate = 10;
+        let samples = (0..5000)
+            .map(|i| {
+                if (1700..1705).contains(&i) || (3300..3305).contains(&i) {
+                    0.0
+                } else {
+                    0.5
+                }
+            })
+            .collect::<Vec<f32>>();
+
+        // Pushed as they'd be decoded, a few at a time.
+        let mut planner = ChunkPlanner::new(sample_rate, 180);
+        let mut chunks = samples
+            .chunks(7)
+            .flat_map(|i| planner.push(i))
+            .collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 2);
+        // Only the samples of the last chunk are left.
+        assert_eq!(planner.buffer.len(), 5000 - (3300 - 50));
+        chunks.push(planner.finish());
+
+        let spans = chunks.iter().map(|i| i.span.clone()).collect::<Vec<_>>();
+        assert_eq!(spans, vec![0..1700, 1700 - 50..3300, 3300 - 50..5000]);
+        assert!(chunks.iter().all(|i| i.samples == samples[i.span.clone()]));
+
+        let mut planner = ChunkPlanner::new(sample_rate, 180);
+        assert!(planner.push(&samples[..100]).is_empty());
+        assert_eq!(planner.finish().span, 0..100);
+    }
+
+    #[test]
+    fn test_wav_bytes() {
+        let bytes = wav_bytes(&[0.0, 1.0, -1.0], SAMPLE_RATE);
+
+        assert_eq!(bytes.len(), 44 + 6);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[8..12], b"WAVE");
+        assert_eq!(i16::from_le_bytes([bytes[46], bytes[47]]), i16::MAX);
+    }
+
+    #[test]
+    fn test_stitch() {
+        let ms = Timestamp::from_millis;
+        let stitched = stitch(vec![
+            (
+                ms(0)..ms(60_000),
+                vec![
+                    cue(1000, 3000, "Hello"),
+                    cue(55_500, 59_000, "Across the seam."),
+                ],
+            ),
+            (
+                ms(55_000)..ms(120_000),
+                // The same cue heard a little differently, and one cut short by the chunk.
+                vec![cue(1000, 4000, "across the seam"), cue(5000, 7000, "Bye")],
+            ),
+        ]);
+
+        assert_eq!(
+            stitched,
+            vec![
+                cue(1000, 3000, "Hello"),
+                cue(55_500, 59_000, "Across the seam."),
+                cue(60_000, 62_000, "Bye"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stitch_speakers() {
+        let ms = Timestamp::from_millis;
+        let speaking = |begin_ms, speaker: &str| Cue {
+            speaker: speaker.to_string(),
+            ..cue(begin_ms, begin_ms + 1000, "Hi")
+        };
+        let speakers = |chunks| {
+            stitch(chunks)
+                .into_iter()
+                .map(|cue| cue.speaker)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            speakers(vec![(ms(0)..ms(60_000), vec![speaking(0, "SPEAKER_00")])]),
+            vec!["SPEAKER_00"]
+        );
+        assert_eq!(
+            speakers(vec![
+                (ms(0)..ms(60_000), vec![speaking(0, "SPEAKER_00")]),
+                (
+                    ms(55_000)..ms(120_000),
+                    vec![speaking(10_000, "SPEAKER_00"), speaking(20_000, "")]
+                ),
+            ]),
+            vec!["1#SPEAKER_00", "2#SPEAKER_00", ""]
+        );
+    }
+}
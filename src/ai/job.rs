@@ -0,0 +1,180 @@
+use crate::ai::AiTranslator;
+use crate::prelude::*;
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Where a running [`TranscriptionJob`] is at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TranscriptionStatus {
+    /// The audio is being decoded from the media, `done` of the `total` chunks planned so far
+    /// are transcribed
+    ExtractingAudio { done: usize, total: usize },
+    /// The fraction of the audio chunks uploaded so far
+    Uploading(f32),
+    /// Everything is uploaded, `done` of `total` chunks are transcribed
+    Transcribing { done: usize, total: usize },
+}
+
+/// Updated by [`AiTranslator::transcribe`] from any of its threads, read by the UI.
+#[derive(Debug, Default)]
+pub struct TranscriptionProgress {
+    cancelled: AtomicBool,
+    /// Set once all the audio is extracted and split into chunks
+    extracted: AtomicBool,
+    chunk_count: AtomicUsize,
+    transcribed_chunks: AtomicUsize,
+    uploaded_bytes: AtomicU64,
+    total_bytes: AtomicU64,
+}
+
+impl TranscriptionProgress {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Another chunk is planned, `bytes` of it are to be uploaded.
+    pub fn add_chunk(&self, bytes: u64) {
+        self.total_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.chunk_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// All chunks are planned.
+    pub fn finish_extracting(&self) {
+        self.extracted.store(true, Ordering::Relaxed);
+    }
+
+    pub fn add_uploaded(&self, bytes: u64) {
+        self.uploaded_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn finish_chunk(&self) {
+        self.transcribed_chunks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn status(&self) -> TranscriptionStatus {
+        let done = self.transcribed_chunks.load(Ordering::Relaxed);
+        let total = self.chunk_count.load(Ordering::Relaxed);
+        if !self.extracted.load(Ordering::Relaxed) {
+            return TranscriptionStatus::ExtractingAudio { done, total };
+        }
+
+        let uploaded_bytes = self.uploaded_bytes.load(Ordering::Relaxed);
+        let total_bytes = self.total_bytes.load(Ordering::Relaxed);
+        if uploaded_bytes < total_bytes {
+            return TranscriptionStatus::Uploading(uploaded_bytes as f32 / total_bytes as f32);
+        }
+
+        TranscriptionStatus::Transcribing { done, total }
+    }
+}
+
+/// Transcribes a media on a background thread, the UI polls it every frame.
+///
+/// A cancelled job stops extracting the audio and uploading at once. Requests which are
+/// already waiting for the server can't be interrupted, the result is dropped when they're
+/// done.
+pub struct TranscriptionJob {
+    receiver: mpsc::Receiver<Result<String>>,
+    progress: Arc<TranscriptionProgress>,
+    started_at: Instant,
+}
+
+impl TranscriptionJob {
+    pub fn spawn(translator: AiTranslator, media_path: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let progress = Arc::new(TranscriptionProgress::default());
+
+        let worker_progress = progress.clone();
+        thread::spawn(move || {
+            let _ = sender.send(translator.transcribe(&media_path, &worker_progress));
+        });
+
+        Self {
+            receiver,
+            progress,
+            started_at: Instant::now(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+
+    pub fn status(&self) -> TranscriptionStatus {
+        self.progress.status()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// The transcription once the job is done, see [`AiTranslator::transcribe`].
+    pub fn poll(&mut self) -> Option<Result<String>> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Block until the job is done, for tests.
+    #[cfg(test)]
+    fn wait(&mut self) -> Result<String> {
+        loop {
+            if let Some(result) = self.poll() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcription_job_failure() {
+        let mut job = TranscriptionJob::spawn(
+            AiTranslator::default(),
+            PathBuf::from("/nonexistent/media.mp4"),
+        );
+
+        assert!(job.wait().is_err());
+        assert_eq!(
+            job.status(),
+            TranscriptionStatus::ExtractingAudio { done: 0, total: 0 }
+        );
+    }
+
+    #[test]
+    fn test_transcription_progress() {
+        let progress = TranscriptionProgress::default();
+        assert_eq!(
+            progress.status(),
+            TranscriptionStatus::ExtractingAudio { done: 0, total: 0 }
+        );
+
+        progress.add_chunk(60);
+        progress.add_uploaded(25);
+        progress.add_chunk(40);
+        assert_eq!(
+            progress.status(),
+            TranscriptionStatus::ExtractingAudio { done: 0, total: 2 }
+        );
+
+        progress.finish_extracting();
+        assert_eq!(progress.status(), TranscriptionStatus::Uploading(0.25));
+
+        progress.add_uploaded(75);
+        progress.finish_chunk();
+        assert_eq!(
+            progress.status(),
+            TranscriptionStatus::Transcribing { done: 1, total: 2 }
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Speech recognition on this machine with a whisper.cpp model, nothing leaves it.
+//!
+//! The media is recognized in chunks like it's uploaded to a server, so the cues are stitched
+//! alike. Without the `local_whisper` feature transcribing fails.
+
+use crate::ai::chunking::Cue;
+use crate::prelude::*;
+#[cfg(feature = "local_whisper")]
+use crate::subrip::WordTiming;
+
+use std::path::Path;
+
+pub fn check_model(model_path: &Path) -> Result<()> {
+    if !model_path.is_file() {
+        return Err(anyhow!(
+            "The model {} doesn't exist, pick one in the AI settings",
+            model_path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// whisper.cpp reports times in centiseconds.
+#[cfg(feature = "local_whisper")]
+fn centiseconds(cs: i64) -> crate::Timestamp {
+    crate::Timestamp::from_millis(cs * 10)
+}
+
+/// A whisper.cpp model, loaded once for all the chunks of a transcription.
+#[cfg(feature = "local_whisper")]
+pub struct Recognizer {
+    context: whisper_rs::WhisperContext,
+    /// Detected if it's empty
+    language: String,
+}
+
+#[cfg(feature = "local_whisper")]
+impl Recognizer {
+    pub fn new(model_path: &Path, language: &str) -> Result<Self> {
+        use whisper_rs::{WhisperContext, WhisperContextParameters};
+
+        check_model(model_path)?;
+        let model_path = model_path
+            .to_str()
+            .ok_or(anyhow!("The path of the model isn't UTF-8"))?;
+        let context =
+            WhisperContext::new_with_params(model_path, WhisperContextParameters::default())?;
+
+        Ok(Self {
+            context,
+            language: language.trim().to_string(),
+        })
+    }
+
+    /// Recognize the speech in [`super::chunking::SAMPLE_RATE`] mono `samples`.
+    pub fn recognize(&self, samples: &[f32]) -> Result<Vec<Cue>> {
+        use whisper_rs::{FullParams, SamplingStrategy};
+
+        let mut state = self.context.create_state()?;
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(match self.language.as_str() {
+            "" => "auto",
+            language => language,
+        }));
+        params.set_n_threads(
+            std::thread::available_parallelism()
+                .map(|i| i.get() as i32)
+                .unwrap_or(4),
+        );
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+        params.set_token_timestamps(true);
+        state.full(params, samples)?;
+
+        (0..state.full_n_segments()?)
+            .map(|i| {
+                let begin_cs = state.full_get_segment_t0(i)?;
+                Ok(Cue {
+                    begin_time: centiseconds(begin_cs),
+                    end_time: centiseconds(state.full_get_segment_t1(i)?),
+                    text: state.full_get_segment_text(i)?,
+                    words: segment_words(&state, i, begin_cs)?,
+                    speaker: String::new(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Join the timed tokens of a segment into words, relative to `begin_cs`. A token beginning
+/// with a space begins a word, special tokens like `[_BEG_]` are skipped.
+#[cfg(feature = "local_whisper")]
+fn segment_words(
+    state: &whisper_rs::WhisperState,
+    segment: i32,
+    begin_cs: i64,
+) -> Result<Vec<WordTiming>> {
+    // The bytes of a character may be spread over several tokens.
+    let mut words: Vec<(Vec<u8>, i64, i64)> = vec![];
+    for i in 0..state.full_n_tokens(segment)? {
+        let bytes = state.full_get_token_bytes(segment, i)?;
+        if bytes.starts_with(b"[_") || bytes.starts_with(b"<|") {
+            continue;
+        }
+
+        let data = state.full_get_token_data(segment, i)?;
+        match words.last_mut() {
+            Some((word, _, end_cs)) if !bytes.starts_with(b" ") => {
+                word.extend_from_slice(&bytes);
+                *end_cs = data.t1;
+            }
+            _ => words.push((bytes, data.t0, data.t1)),
+        }
+    }
+
+    Ok(words
+        .into_iter()
+        .map(|(bytes, t0, t1)| WordTiming {
+            text: String::from_utf8_lossy(&bytes).trim().to_string(),
+            begin: centiseconds(t0 - begin_cs),
+            end: centiseconds(t1 - begin_cs),
+        })
+        .filter(|word| !word.text.is_empty())
+        .collect())
+}
+
+/// Never made without the `local_whisper` feature.
+#[cfg(not(feature = "local_whisper"))]
+pub enum Recognizer {}
+
+#[cfg(not(feature = "local_whisper"))]
+impl Recognizer {
+    pub fn new(model_path: &Path, _language: &str) -> Result<Self> {
+        check_model(model_path)?;
+
+        Err(anyhow!(
+            "Local transcription needs mksubrip built with the `local_whisper` feature"
+        ))
+    }
+
+    pub fn recognize(&self, _samples: &[f32]) -> Result<Vec<Cue>> {
+        match *self {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recognizer_without_model() {
+        assert!(Recognizer::new(Path::new("/nonexistent/ggml-base.bin"), "").is_err());
+    }
+}
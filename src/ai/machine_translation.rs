@@ -0,0 +1,502 @@
+use crate::ai::{TranslationBackend, TranslationSettings};
+use crate::prelude::*;
+
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+/// A batch of a few dozen subrips is translated well within this.
+const TRANSLATION_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Subrips translated in one request, with their neighbours to translate them in context.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TranslationBatch {
+    pub context_before: Vec<String>,
+    pub texts: Vec<String>,
+    pub context_after: Vec<String>,
+}
+
+/// Split `texts` into batches of `batch_size`, each with `context_size` neighbours on both sides.
+pub fn batches(texts: &[String], batch_size: usize, context_size: usize) -> Vec<TranslationBatch> {
+    let batch_size = batch_size.max(1);
+
+    (0..texts.len())
+        .step_by(batch_size)
+        .map(|begin| {
+            let end = (begin + batch_size).min(texts.len());
+
+            TranslationBatch {
+                context_before: texts[begin.saturating_sub(context_size)..begin].to_vec(),
+                texts: texts[begin..end].to_vec(),
+                context_after: texts[end..(end + context_size).min(texts.len())].to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Keep the line breaks of `text` if every line fits in `max_line_length` characters,
+/// otherwise wrap its words again.
+pub fn wrap_lines(text: &str, max_line_length: usize) -> String {
+    let lines = text
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    if lines
+        .iter()
+        .all(|line| line.chars().count() <= max_line_length)
+    {
+        return lines.join("\n");
+    }
+
+    let mut wrapped: Vec<String> = vec![];
+    for word in lines.iter().flat_map(|line| line.split_whitespace()) {
+        match wrapped.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= max_line_length => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => wrapped.push(word.to_string()),
+        }
+    }
+
+    wrapped.join("\n")
+}
+
+/// Translates the text of subrips, e.g. through a translation server.
+pub trait TextTranslator: Send {
+    /// One translation per text of the batch, in the same order.
+    fn translate(&self, batch: &TranslationBatch) -> Result<Vec<String>>;
+}
+
+/// The translator the settings ask for, from `source_language` (empty to detect it) into
+/// `target_language`.
+pub fn translator(
+    settings: &TranslationSettings,
+    source_language: &str,
+    target_language: &str,
+) -> Box<dyn TextTranslator> {
+    match settings.backend {
+        TranslationBackend::LibreTranslate => Box::new(LibreTranslator {
+            base_url: settings.base_url.clone(),
+            api_key: settings.api_key(),
+            source_language: source_language.to_string(),
+            target_language: target_language.to_string(),
+        }),
+        TranslationBackend::OpenaiChat => Box::new(ChatTranslator {
+            base_url: settings.base_url.clone(),
+            api_key: settings.api_key(),
+            model: settings.model.clone(),
+            source_language: source_language.to_string(),
+            target_language: target_language.to_string(),
+        }),
+    }
+}
+
+fn endpoint(base_url: &str, path: &str) -> String {
+    format!("{}{}", base_url.trim().trim_end_matches('/'), path)
+}
+
+/// LibreTranslate has no notion of context, the neighbours of a batch aren't sent.
+pub struct LibreTranslator {
+    pub base_url: String,
+    pub api_key: String,
+    pub source_language: String,
+    pub target_language: String,
+}
+
+#[derive(Deserialize)]
+struct LibreResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: Vec<String>,
+}
+
+impl TextTranslator for LibreTranslator {
+    fn translate(&self, batch: &TranslationBatch) -> Result<Vec<String>> {
+        let source = match self.source_language.trim() {
+            "" => "auto",
+            language => language,
+        };
+        let mut body = json!({
+            "q": batch.texts,
+            "source": source,
+            "target": self.target_language.trim(),
+            "format": "text",
+        });
+        if !self.api_key.trim().is_empty() {
+            body["api_key"] = json!(self.api_key.trim());
+        }
+
+        let response = request::post_with_json(
+            endpoint(&self.base_url, "/translate"),
+            None,
+            &body,
+            TRANSLATION_TIMEOUT,
+        )?
+        .error_for_status()?;
+        let response: LibreResponse = serde_json::from_str(&response.text()?)?;
+
+        Ok(response.translated_text)
+    }
+}
+
+/// Asks a chat model of an OpenAI-compatible server, which sees the neighbours of a batch.
+pub struct ChatTranslator {
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub source_language: String,
+    pub target_language: String,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: String,
+}
+
+impl ChatTranslator {
+    fn prompt(&self) -> String {
+        let source = match self.source_language.trim() {
+            "" => "their language",
+            language => language,
+        };
+
+        format!(
+            "You translate subtitles from {} to {}. The user sends a JSON object whose `cues` \
+             are to be translated, `context_before` and `context_after` are the cues around \
+             them and are not to be translated. Reply with a JSON array of strings only, one \
+             translation per cue in the same order. Keep the line breaks of each cue.",
+            source,
+            self.target_language.trim()
+        )
+    }
+}
+
+/// The translations in a reply of a chat model, which may wrap them in a code block.
+fn parse_chat_reply(reply: &str) -> Result<Vec<String>> {
+    let reply = reply.trim();
+    let reply = reply
+        .strip_prefix("```json")
+        .or_else(|| reply.strip_prefix("```"))
+        .and_then(|i| i.strip_suffix("```"))
+        .unwrap_or(reply);
+
+    serde_json::from_str(reply).map_err(|err| anyhow!("The reply isn't a list of cues: {}", err))
+}
+
+impl TextTranslator for ChatTranslator {
+    fn translate(&self, batch: &TranslationBatch) -> Result<Vec<String>> {
+        let cues = json!({
+            "context_before": batch.context_before,
+            "cues": batch.texts,
+            "context_after": batch.context_after,
+        });
+        let body = json!({
+            "model": self.model.trim(),
+            "temperature": 0,
+            "messages": [
+                {"role": "system", "content": self.prompt()},
+                {"role": "user", "content": cues.to_string()},
+            ],
+        });
+        let authorization = format!("Bearer {}", self.api_key.trim());
+        let header =
+            (!self.api_key.trim().is_empty()).then_some(("Authorization", authorization.as_str()));
+
+        let response = request::post_with_json(
+            endpoint(&self.base_url, "/v1/chat/completions"),
+            header,
+            &body,
+            TRANSLATION_TIMEOUT,
+        )?
+        .error_for_status()?;
+        let response: ChatResponse = serde_json::from_str(&response.text()?)?;
+        let reply = response
+            .choices
+            .first()
+            .ok_or(anyhow!("The reply has no choices"))?;
+
+        parse_chat_reply(&reply.message.content)
+    }
+}
+
+enum MachineTranslationEvent {
+    /// Batches translated so far
+    Translated(usize),
+    Done(Result<Vec<String>>),
+}
+
+/// Translates texts batch by batch on a background thread, the UI polls it every frame.
+/// A cancelled job stops after the batch being translated.
+pub struct MachineTranslationJob {
+    receiver: mpsc::Receiver<MachineTranslationEvent>,
+    cancel: Arc<AtomicBool>,
+    batch_count: usize,
+    translated_batches: usize,
+}
+
+impl MachineTranslationJob {
+    pub fn spawn(
+        translator: Box<dyn TextTranslator>,
+        texts: Vec<String>,
+        settings: &TranslationSettings,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let batches = batches(&texts, settings.batch_size, settings.context_size);
+        let batch_count = batches.len();
+        let max_line_length = settings.max_line_length;
+
+        let worker_cancel = cancel.clone();
+        thread::spawn(move || {
+            let mut translations = vec![];
+            for (k, batch) in batches.iter().enumerate() {
+                if worker_cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let translated = match translator.translate(batch) {
+                    Ok(translated) if translated.len() == batch.texts.len() => translated,
+                    Ok(translated) => {
+                        let _ = sender.send(MachineTranslationEvent::Done(Err(anyhow!(
+                            "{} cues were sent, {} translations came back",
+                            batch.texts.len(),
+                            translated.len()
+                        ))));
+                        return;
+                    }
+                    Err(err) => {
+                        let _ = sender.send(MachineTranslationEvent::Done(Err(err)));
+                        return;
+                    }
+                };
+                translations.extend(translated.iter().map(|i| wrap_lines(i, max_line_length)));
+                let _ = sender.send(MachineTranslationEvent::Translated(k + 1));
+            }
+            let _ = sender.send(MachineTranslationEvent::Done(Ok(translations)));
+        });
+
+        Self {
+            receiver,
+            cancel,
+            batch_count,
+            translated_batches: 0,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// The fraction of the batches translated so far.
+    pub fn progress(&self) -> f32 {
+        if self.batch_count == 0 {
+            return 1.0;
+        }
+
+        self.translated_batches as f32 / self.batch_count as f32
+    }
+
+    /// Update the progress, return one translation per text once the job is done.
+    pub fn poll(&mut self) -> Option<Result<Vec<String>>> {
+        for event in self.receiver.try_iter() {
+            match event {
+                MachineTranslationEvent::Translated(count) => self.translated_batches = count,
+                MachineTranslationEvent::Done(result) => return Some(result),
+            }
+        }
+
+        None
+    }
+
+    /// Block until the job is done, for tests.
+    #[cfg(test)]
+    fn wait(&mut self) -> Result<Vec<String>> {
+        loop {
+            if let Some(result) = self.poll() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::test_server::serve_once;
+
+    /// Shouts the texts back, or drops the last one to act like a broken server.
+    struct StubTranslator {
+        drop_last: bool,
+    }
+
+    impl TextTranslator for StubTranslator {
+        fn translate(&self, batch: &TranslationBatch) -> Result<Vec<String>> {
+            let mut translated = batch
+                .texts
+                .iter()
+                .map(|i| i.to_uppercase())
+                .collect::<Vec<_>>();
+            if self.drop_last {
+                translated.pop();
+            }
+
+            Ok(translated)
+        }
+    }
+
+    fn texts(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("cue {i}")).collect()
+    }
+
+    #[test]
+    fn test_batches() {
+        let batches = batches(&texts(5), 2, 1);
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[0].context_before, Vec::<String>::new());
+        assert_eq!(batches[0].texts, vec!["cue 0", "cue 1"]);
+        assert_eq!(batches[0].context_after, vec!["cue 2"]);
+        assert_eq!(batches[2].context_before, vec!["cue 3"]);
+        assert_eq!(batches[2].texts, vec!["cue 4"]);
+        assert!(batches[2].context_after.is_empty());
+    }
+
+    #[test]
+    fn test_wrap_lines() {
+        assert_eq!(wrap_lines("Hello,\n World!", 10), "Hello,\nWorld!");
+        assert_eq!(
+            wrap_lines("The quick brown fox\njumps over the lazy dog", 15),
+            "The quick brown\nfox jumps over\nthe lazy dog"
+        );
+        assert_eq!(wrap_lines("Unbreakable", 5), "Unbreakable");
+    }
+
+    #[test]
+    fn test_parse_chat_reply() {
+        assert_eq!(
+            parse_chat_reply("```json\n[\"Hallo\", \"Welt\"]\n```").unwrap(),
+            vec!["Hallo", "Welt"]
+        );
+        assert!(parse_chat_reply("Sure! Here you go.").is_err());
+    }
+
+    #[test]
+    fn test_machine_translation_job() {
+        let settings = TranslationSettings {
+            batch_size: 2,
+            ..Default::default()
+        };
+
+        let mut job = MachineTranslationJob::spawn(
+            Box::new(StubTranslator { drop_last: false }),
+            texts(3),
+            &settings,
+        );
+        assert_eq!(job.wait().unwrap(), vec!["CUE 0", "CUE 1", "CUE 2"]);
+        assert_eq!(job.progress(), 1.0);
+
+        let mut job = MachineTranslationJob::spawn(
+            Box::new(StubTranslator { drop_last: true }),
+            texts(3),
+            &settings,
+        );
+        assert!(job.wait().is_err());
+    }
+
+    fn batch() -> TranslationBatch {
+        TranslationBatch {
+            context_before: vec!["Hi.".to_string()],
+            texts: vec!["Hello,\nworld".to_string(), "Bye".to_string()],
+            context_after: vec![],
+        }
+    }
+
+    #[test]
+    fn test_libre_translator() {
+        let (base_url, handle) = serve_once(
+            "200 OK",
+            r#"{"translatedText": ["Hallo,\nWelt", "Tschüss"]}"#,
+        );
+        let translator = LibreTranslator {
+            base_url: format!("{}/", base_url),
+            api_key: "secret".to_string(),
+            source_language: String::new(),
+            target_language: "de".to_string(),
+        };
+
+        assert_eq!(
+            translator.translate(&batch()).unwrap(),
+            vec!["Hallo,\nWelt", "Tschüss"]
+        );
+        let request = handle.join().unwrap().remove(0);
+        assert!(request.starts_with("POST /translate "));
+        assert!(request.contains(r#""q":["Hello,\nworld","Bye"]"#));
+        assert!(request.contains(r#""source":"auto""#));
+        assert!(request.contains(r#""target":"de""#));
+        assert!(request.contains(r#""api_key":"secret""#));
+        // LibreTranslate doesn't get the context.
+        assert!(!request.contains("Hi."));
+
+        let (base_url, handle) = serve_once("403 Forbidden", r#"{"error": "Invalid API key"}"#);
+        let translator = LibreTranslator {
+            base_url,
+            ..translator
+        };
+        assert!(translator.translate(&batch()).is_err());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_chat_translator() {
+        let (base_url, handle) = serve_once(
+            "200 OK",
+            r#"{"choices": [{"message": {"role": "assistant",
+                "content": "```json\n[\"Hallo,\\nWelt\", \"Tschüss\"]\n```"}}]}"#,
+        );
+        let translator = ChatTranslator {
+            base_url,
+            api_key: "secret".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            source_language: "en".to_string(),
+            target_language: "de".to_string(),
+        };
+
+        assert_eq!(
+            translator.translate(&batch()).unwrap(),
+            vec!["Hallo,\nWelt", "Tschüss"]
+        );
+        let request = handle.join().unwrap().remove(0);
+        assert!(request.starts_with("POST /v1/chat/completions "));
+        assert!(request
+            .to_lowercase()
+            .contains("authorization: bearer secret"));
+        assert!(request.contains(r#""model":"gpt-4o-mini""#));
+        // The context goes along with the cues.
+        assert!(request.contains(r#"\"context_before\":[\"Hi.\"]"#));
+
+        let (base_url, handle) = serve_once(
+            "200 OK",
+            r#"{"choices": [{"message": {"content": "Sorry, I can't."}}]}"#,
+        );
+        let translator = ChatTranslator {
+            base_url,
+            ..translator
+        };
+        assert!(translator.translate(&batch()).is_err());
+        handle.join().unwrap();
+    }
+}
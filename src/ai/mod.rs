@@ -1,85 +1,370 @@
+mod chunking;
+mod job;
+mod local;
+mod machine_translation;
+mod openai;
+mod settings;
+#[cfg(test)]
+mod test_server;
+
+use crate::core::request::ProgressReader;
 use crate::{prelude::*, Subrip};
 
+use chunking::Cue;
 use reqwest::blocking::multipart;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
 use std::path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub use job::{TranscriptionJob, TranscriptionProgress, TranscriptionStatus};
+pub use machine_translation::{
+    translator, wrap_lines, ChatTranslator, LibreTranslator, MachineTranslationJob, TextTranslator,
+    TranslationBatch,
+};
+pub use settings::{
+    AiSettings, TranslationBackend, TranslationSettings, API_KEY_VAR, TRANSLATION_API_KEY_VAR,
+};
 
-const MODEL_URL: [&str; 1] = ["/model/whisper"];
+/// The local model has no URL.
+const MODEL_URL: [&str; 3] = ["/model/whisper", openai::TRANSCRIPTIONS_URL, ""];
+/// Transcribing a long media takes a while, but not forever.
+const TRANSCRIPTION_TIMEOUT: Duration = Duration::from_secs(60 * 60);
 
 #[repr(usize)]
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AIModelType {
     OpenaiWhisper = 0,
+    /// A server speaking the `/v1/audio/transcriptions` API of OpenAI
+    OpenaiCompatible = 1,
+    /// A whisper.cpp model running on this machine, see [`AiSettings::model_path`]
+    LocalWhisper = 2,
 }
 
-#[repr(usize)]
+impl AIModelType {
+    pub const ALL: [AIModelType; 3] = [
+        AIModelType::OpenaiWhisper,
+        AIModelType::OpenaiCompatible,
+        AIModelType::LocalWhisper,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AIModelType::OpenaiWhisper => "OpenAI Whisper",
+            AIModelType::OpenaiCompatible => "OpenAI-compatible API",
+            AIModelType::LocalWhisper => "Local Whisper (offline)",
+        }
+    }
+
+    /// Convert what the server of this model responded into subrips.
+    pub fn parse_response(&self, response: &str) -> Result<Vec<Shared<Subrip>>> {
+        match self {
+            AIModelType::OpenaiWhisper | AIModelType::LocalWhisper => {
+                utils::json_str_to_subrips(response)
+            }
+            AIModelType::OpenaiCompatible => openai::segments_to_subrips(response),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AIModelScale {
-    Tiny = 0,
+    Tiny,
     Base,
     Large,
-    // Specified(String),
+    /// A model the server knows by this name, e.g. `large-v3`
+    Specified(String),
 }
 
+#[derive(Default)]
 pub struct AiTranslator {
-    pub model_type: AIModelType,
-    pub scale: AIModelScale,
+    pub settings: AiSettings,
 }
 
-impl Default for AiTranslator {
-    fn default() -> Self {
-        Self {
-            model_type: AIModelType::OpenaiWhisper,
-            scale: AIModelScale::Base,
-        }
+impl AiTranslator {
+    pub fn new(settings: AiSettings) -> Self {
+        Self { settings }
     }
-}
 
-impl AiTranslator {
     pub fn model(mut self, model: AIModelType) -> Self {
-        self.model_type = model;
+        self.settings.model_type = model;
 
         self
     }
 
     pub fn scale(mut self, scale: AIModelScale) -> Self {
-        self.scale = scale;
+        self.settings.scale = scale;
 
         self
     }
 
-    pub fn translate(&self, file_path: &path::Path) -> Vec<Shared<Subrip>> {
-        match self.request(file_path) {
-            Ok(str) => utils::json_str_to_subrips(&str).unwrap_or_default(),
-            Err(err) => {
-                error!("{}", err.to_string());
-                vec![]
-            }
-        }
+    pub fn translate(&self, file_path: &path::Path) -> Result<Vec<Shared<Subrip>>> {
+        let transcription =
+            self.transcribe(file_path, &Arc::new(TranscriptionProgress::default()))?;
+
+        utils::json_str_to_subrips(&transcription)
     }
 
     pub fn get_scale_str(&self) -> &str {
-        match self.scale {
+        match &self.settings.scale {
             AIModelScale::Tiny => "tiny",
             AIModelScale::Base => "base",
             AIModelScale::Large => "large",
+            AIModelScale::Specified(name) => name.trim(),
         }
     }
 
     pub fn get_url(&self) -> &str {
-        MODEL_URL[self.model_type as usize]
+        MODEL_URL[self.settings.model_type as usize]
+    }
+
+    /// The full URL of the model, with the language hint if there is one.
+    /// An OpenAI-compatible server gets the model and the language in the form instead.
+    pub fn endpoint(&self) -> String {
+        let base_url = self.settings.base_url.trim().trim_end_matches('/');
+        if self.settings.model_type == AIModelType::OpenaiCompatible {
+            return format!("{base_url}{}", self.get_url());
+        }
+
+        let url = format!("{base_url}{}/{}", self.get_url(), self.get_scale_str());
+        let language = self.settings.language.trim();
+        if language.is_empty() {
+            return url;
+        }
+
+        // An invalid base URL is left for the request to report.
+        match reqwest::Url::parse(&url) {
+            Ok(mut url) => {
+                url.query_pairs_mut().append_pair("language", language);
+                url.into()
+            }
+            Err(_) => url,
+        }
+    }
+
+    /// Send `part` to the server of the model, return its response.
+    fn post(&self, part: multipart::Part) -> Result<String> {
+        let multipart = match self.settings.model_type {
+            AIModelType::OpenaiWhisper | AIModelType::LocalWhisper => {
+                multipart::Form::new().part("data", part)
+            }
+            AIModelType::OpenaiCompatible => {
+                openai::form(part, self.get_scale_str(), self.settings.language.trim())
+            }
+        };
+
+        let api_key = self.settings.api_key();
+        match request::post_with_multipart(
+            self.endpoint(),
+            api_key
+                .as_ref()
+                .map(|(header, key)| (*header, key.as_str())),
+            multipart,
+            TRANSCRIPTION_TIMEOUT,
+        ) {
+            Ok(result) => Ok(result.error_for_status()?.text()?),
+            Err(err) => Err(anyhow!(err.to_string())),
+        }
     }
 
-    pub fn request(&self, file_path: &path::Path) -> Result<String> {
+    fn model_path(&self) -> &path::Path {
+        path::Path::new(self.settings.model_path.trim())
+    }
+
+    /// Transcribe one chunk of [`chunking::SAMPLE_RATE`] mono audio, the `k`th of the media,
+    /// with the local model if there is one.
+    fn transcribe_chunk(
+        &self,
+        samples: &[f32],
+        k: usize,
+        recognizer: Option<&local::Recognizer>,
+        progress: &Arc<TranscriptionProgress>,
+    ) -> Result<Vec<Cue>> {
+        if let Some(recognizer) = recognizer {
+            return recognizer.recognize(samples);
+        }
+
+        let wav = chunking::wav_bytes(samples, chunking::SAMPLE_RATE);
+        let size = wav.len() as u64;
+        let reader_progress = progress.clone();
+        let mut reported = 0;
+        let reader = ProgressReader::new(Cursor::new(wav), move |uploaded| {
+            reader_progress.add_uploaded(uploaded - reported);
+            reported = uploaded;
+            !reader_progress.is_cancelled()
+        });
+        let part = multipart::Part::reader_with_length(reader, size)
+            .file_name(format!("chunk{k}.wav"))
+            .mime_str("audio/wav")?;
+
+        let subrips = self.settings.model_type.parse_response(&self.post(part)?)?;
+        let cues = subrips
+            .iter()
+            .map(|subrip| {
+                let subrip = subrip.borrow();
+                Cue {
+                    begin_time: subrip.begin_time,
+                    end_time: subrip.end_time,
+                    text: subrip.content.clone(),
+                    words: subrip.words.clone(),
+                    speaker: subrip.style.actor.clone(),
+                }
+            })
+            .collect();
+
+        Ok(cues)
+    }
+
+    /// Transcribe the audio of the media in chunks, see [`AiSettings::chunk_minutes`].
+    /// Only the audio is uploaded, downmixed to mono. Up to
+    /// [`AiSettings::parallel_requests`] chunks are transcribed at once, while the rest of the
+    /// audio is still decoded.
+    ///
+    /// The stitched transcription is returned as [`utils::json_str_to_subrips`] reads it,
+    /// whichever the model is.
+    pub fn transcribe(
+        &self,
+        file_path: &path::Path,
+        progress: &Arc<TranscriptionProgress>,
+    ) -> Result<String> {
         if !file_path.exists() {
             return Err(anyhow!("{} is invalid", file_path.to_str().unwrap_or("")));
         }
-        let model_url = self.get_url();
-        let url = format!("{model_url}/{}", self.get_scale_str());
-        let multipart = multipart::Form::new().file("data", file_path)?;
+        info!("Transcribe {}", file_path.display());
 
-        match request::post_with_multipart(url, multipart) {
-            Ok(result) => Ok(result.text()?),
-            Err(err) => Err(anyhow!(err.to_string())),
+        self.transcribe_stream(progress, |on_samples| {
+            chunking::extract_audio(file_path, on_samples)
+        })
+    }
+
+    /// Like [`AiTranslator::transcribe`], with the audio already extracted.
+    pub fn transcribe_samples(
+        &self,
+        samples: &[f32],
+        progress: &Arc<TranscriptionProgress>,
+    ) -> Result<String> {
+        self.transcribe_stream(progress, |on_samples| {
+            match samples
+                .chunks(chunking::SAMPLE_RATE as usize)
+                .all(on_samples)
+            {
+                true => Ok(()),
+                false => Err(anyhow!("Cancelled")),
+            }
+        })
+    }
+
+    /// Transcribe the samples `feed` hands to the function it's given, which returns `false`
+    /// once it should stop.
+    fn transcribe_stream(
+        &self,
+        progress: &Arc<TranscriptionProgress>,
+        feed: impl FnOnce(&mut dyn FnMut(&[f32]) -> bool) -> Result<()>,
+    ) -> Result<String> {
+        let recognizer = match self.settings.model_type {
+            AIModelType::LocalWhisper => Some(local::Recognizer::new(
+                self.model_path(),
+                &self.settings.language,
+            )?),
+            _ => None,
+        };
+        // whisper.cpp already keeps every core busy.
+        let parallel_requests = match recognizer {
+            Some(_) => 1,
+            None => self.settings.parallel_requests.max(1),
+        };
+
+        // Decoding waits while this many chunks are queued, so the audio isn't all in memory.
+        let (sender, receiver) = mpsc::sync_channel::<(usize, chunking::Chunk)>(parallel_requests);
+        let receiver = Mutex::new(receiver);
+        let failed = AtomicBool::new(false);
+        let mut spans = vec![];
+        let (fed, mut results) = thread::scope(|scope| {
+            let workers = (0..parallel_requests)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = vec![];
+                        loop {
+                            let next = receiver.lock().unwrap().recv();
+                            let Ok((k, chunk)) = next else {
+                                break;
+                            };
+                            // Keep taking the chunks, so decoding isn't stuck waiting.
+                            if progress.is_cancelled() || failed.load(Ordering::Relaxed) {
+                                continue;
+                            }
+
+                            let result = self.transcribe_chunk(
+                                &chunk.samples,
+                                k,
+                                recognizer.as_ref(),
+                                progress,
+                            );
+                            progress.finish_chunk();
+                            failed.fetch_or(result.is_err(), Ordering::Relaxed);
+                            results.push((k, result));
+                        }
+
+                        results
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut planner = chunking::ChunkPlanner::new(
+                chunking::SAMPLE_RATE,
+                self.settings.chunk_minutes.max(1) * 60,
+            );
+            let mut queue = |chunk: chunking::Chunk| {
+                progress.add_chunk(match recognizer {
+                    Some(_) => 0,
+                    None => 44 + 2 * chunk.samples.len() as u64,
+                });
+                spans.push(chunk.span.clone());
+                // The receiver outlives the scope, sending can't fail.
+                let _ = sender.send((spans.len() - 1, chunk));
+            };
+            let fed = feed(&mut |samples| {
+                planner.push(samples).into_iter().for_each(&mut queue);
+
+                !progress.is_cancelled() && !failed.load(Ordering::Relaxed)
+            });
+            if fed.is_ok() {
+                queue(planner.finish());
+                progress.finish_extracting();
+            }
+            debug!("The audio is split into {} chunks", spans.len());
+            drop(sender);
+
+            let results = workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap_or_default())
+                .collect::<Vec<_>>();
+            (fed, results)
+        });
+        results.sort_by_key(|(k, _)| *k);
+
+        if let Some((k, Err(err))) = results.iter().find(|(_, result)| result.is_err()) {
+            return Err(anyhow!("Transcribing chunk {} failed: {}", k + 1, err));
+        }
+        fed?;
+        if results.len() < spans.len() {
+            return Err(anyhow!("Cancelled"));
         }
+
+        let chunk_cues = spans
+            .into_iter()
+            .zip(results)
+            .map(|(chunk, (_, cues))| {
+                let span = chunking::samples_to_timestamp(chunk.start, chunking::SAMPLE_RATE)
+                    ..chunking::samples_to_timestamp(chunk.end, chunking::SAMPLE_RATE);
+                (span, cues.unwrap_or_default())
+            })
+            .collect();
+
+        Ok(chunking::cues_to_json(&chunking::stitch(chunk_cues)))
     }
 }
 
@@ -87,6 +372,41 @@ impl AiTranslator {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_endpoint() {
+        let translator = AiTranslator::default().scale(AIModelScale::Large);
+        assert_eq!(
+            translator.endpoint(),
+            "http://localhost:5000/model/whisper/large"
+        );
+
+        let translator = AiTranslator::new(AiSettings {
+            base_url: "https://example.com/".to_string(),
+            language: "de".to_string(),
+            ..Default::default()
+        })
+        .scale(AIModelScale::Specified("large-v3".to_string()));
+        assert_eq!(
+            translator.endpoint(),
+            "https://example.com/model/whisper/large-v3?language=de"
+        );
+
+        let translator = AiTranslator::new(AiSettings {
+            language: "zh Hant&x=1".to_string(),
+            ..translator.settings.clone()
+        });
+        assert_eq!(
+            translator.endpoint(),
+            "https://example.com/model/whisper/large-v3?language=zh+Hant%26x%3D1"
+        );
+
+        let translator = translator.model(AIModelType::OpenaiCompatible);
+        assert_eq!(
+            translator.endpoint(),
+            "https://example.com/v1/audio/transcriptions"
+        );
+    }
+
     #[test]
     fn test_json_str_to_subrips() {
         let text = r#"{
@@ -0,0 +1,227 @@
+//! The `/v1/audio/transcriptions` API of OpenAI, which servers like faster-whisper-server and
+//! the whisper.cpp server speak as well.
+
+use crate::prelude::*;
+use crate::subrip::WordTiming;
+use crate::{Subrip, Timestamp};
+
+use reqwest::blocking::multipart;
+use serde::Deserialize;
+
+pub const TRANSCRIPTIONS_URL: &str = "/v1/audio/transcriptions";
+
+/// The only response format which carries the timing of the segments.
+const RESPONSE_FORMAT: &str = "verbose_json";
+
+#[derive(Debug, Deserialize)]
+struct VerboseTranscription {
+    segments: Vec<Segment>,
+    /// OpenAI lists the timed words of all segments here
+    #[serde(default)]
+    words: Vec<Word>,
+}
+
+/// A span of speech, in seconds from the beginning of the media.
+#[derive(Debug, Deserialize)]
+struct Segment {
+    start: f64,
+    end: f64,
+    text: String,
+    /// Some servers nest the timed words in their segments
+    #[serde(default)]
+    words: Vec<Word>,
+    /// Label of a diarized transcription, e.g. `A`
+    #[serde(default)]
+    speaker: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Word {
+    word: String,
+    start: f64,
+    end: f64,
+}
+
+fn seconds(secs: f64) -> Timestamp {
+    Timestamp::from_millis((secs * 1000.0).round() as i64)
+}
+
+/// The form uploading `file` to be transcribed by `model`, `language` is left to the server
+/// if it's empty.
+pub fn form(file: multipart::Part, model: &str, language: &str) -> multipart::Form {
+    let mut form = multipart::Form::new()
+        .part("file", file)
+        .text("model", model.to_string())
+        .text("response_format", RESPONSE_FORMAT)
+        .text("timestamp_granularities[]", "segment")
+        .text("timestamp_granularities[]", "word");
+    if !language.is_empty() {
+        form = form.text("language", language.to_string());
+    }
+
+    form
+}
+
+/// Convert the segments of a `verbose_json` response into subrips, skipping silent ones.
+pub fn segments_to_subrips(json_str: &str) -> Result<Vec<Shared<Subrip>>> {
+    let transcription: VerboseTranscription = serde_json::from_str(json_str)?;
+
+    let subrips = transcription
+        .segments
+        .iter()
+        .filter(|segment| !segment.text.trim().is_empty())
+        .enumerate()
+        .map(|(i, segment)| {
+            let begin_time = seconds(segment.start);
+            let end_time = seconds(segment.end);
+            let mut subrip = Subrip::new(segment.text.trim(), begin_time, end_time - begin_time);
+            subrip.set_index(i as u32 + 1);
+            if let Some(speaker) = segment.speaker.as_ref() {
+                subrip.style.actor = speaker.trim().to_string();
+            }
+
+            // The words of the whole transcription belong to the segment they're centered in.
+            let words = if segment.words.is_empty() {
+                transcription
+                    .words
+                    .iter()
+                    .filter(|word| {
+                        (segment.start..segment.end).contains(&((word.start + word.end) / 2.0))
+                    })
+                    .collect::<Vec<_>>()
+            } else {
+                segment.words.iter().collect()
+            };
+            subrip.words = words
+                .into_iter()
+                .filter(|word| !word.word.trim().is_empty())
+                .map(|word| WordTiming {
+                    text: word.word.trim().to_string(),
+                    begin: Timestamp::ZERO + (seconds(word.start) - begin_time),
+                    end: Timestamp::ZERO + (seconds(word.end) - begin_time),
+                })
+                .collect();
+
+            Shared::new(subrip)
+        })
+        .collect();
+
+    Ok(subrips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::test_server::{serve, serve_once};
+    use crate::ai::{AIModelScale, AIModelType, AiSettings, AiTranslator};
+
+    fn translator(base_url: String) -> AiTranslator {
+        AiTranslator::new(AiSettings {
+            base_url,
+            api_key_header: "Authorization".to_string(),
+            api_key: "Bearer secret".to_string(),
+            language: "en".to_string(),
+            ..Default::default()
+        })
+        .model(AIModelType::OpenaiCompatible)
+        .scale(AIModelScale::Specified("whisper-1".to_string()))
+    }
+
+    #[test]
+    fn test_openai_transcription() {
+        let (base_url, handle) = serve_once(
+            "200 OK",
+            r#"{
+    "task": "transcribe",
+    "language": "english",
+    "duration": 4.2,
+    "text": "Hello, World! Bye.",
+    "segments": [
+        {"id": 0, "seek": 0, "start": 0.5, "end": 2.0, "text": " Hello, World!"},
+        {"id": 1, "seek": 0, "start": 2.0, "end": 2.5, "text": " "},
+        {"id": 2, "seek": 0, "start": 2.5, "end": 4.2, "text": " Bye.", "speaker": "B"}
+    ],
+    "words": [
+        {"word": "Hello", "start": 0.5, "end": 1.1},
+        {"word": "World", "start": 1.3, "end": 2.0},
+        {"word": "Bye", "start": 2.9, "end": 3.4}
+    ]
+}"#,
+        );
+        let samples = vec![0.0; 5 * crate::ai::chunking::SAMPLE_RATE as usize];
+        let progress = std::sync::Arc::new(crate::ai::TranscriptionProgress::default());
+
+        let translator = translator(base_url);
+        let transcription = translator.transcribe_samples(&samples, &progress).unwrap();
+        let request = handle.join().unwrap().remove(0);
+        assert!(request.starts_with("POST /v1/audio/transcriptions "));
+        assert!(request
+            .to_lowercase()
+            .contains("authorization: bearer secret"));
+        for field in ["file", "model", "response_format", "language"] {
+            assert!(request.contains(&format!("name=\"{field}\"")));
+        }
+        assert!(request.contains("verbose_json"));
+        assert!(request.contains("whisper-1"));
+        assert!(request.contains("RIFF"));
+
+        let subrips = utils::json_str_to_subrips(&transcription).unwrap();
+        assert_eq!(subrips.len(), 2);
+        let subrip = subrips[1].borrow();
+        assert_eq!(subrip.get_index(), 2);
+        assert_eq!(subrip.content, "Bye.");
+        assert_eq!(subrip.begin_time, Timestamp::from_millis(2500));
+        assert_eq!(subrip.end_time, Timestamp::from_millis(4200));
+        assert_eq!(subrip.style.actor, "B");
+        assert_eq!(subrip.words.len(), 1);
+        assert_eq!(subrip.words[0].begin, Timestamp::from_millis(400));
+        assert_eq!(subrips[0].borrow().timed_lines().unwrap()[0].len(), 2);
+    }
+
+    #[test]
+    fn test_openai_transcription_error() {
+        let (base_url, handle) = serve_once(
+            "401 Unauthorized",
+            r#"{"error": {"message": "Invalid API key"}}"#,
+        );
+        let samples = vec![0.0; 5 * crate::ai::chunking::SAMPLE_RATE as usize];
+        let progress = std::sync::Arc::new(crate::ai::TranscriptionProgress::default());
+
+        assert!(translator(base_url)
+            .transcribe_samples(&samples, &progress)
+            .is_err());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_chunked_transcription() {
+        let (base_url, handle) = serve(
+            3,
+            "200 OK",
+            r#"{"segments": [{"start": 10.0, "end": 12.0, "text": "Hello"}]}"#,
+        );
+        let mut translator = translator(base_url);
+        translator.settings.chunk_minutes = 1;
+        translator.settings.parallel_requests = 2;
+
+        // 130s of silence, cut into chunks at 0s, about 40s and about 80s.
+        let samples = vec![0.0; 130 * crate::ai::chunking::SAMPLE_RATE as usize];
+        let progress = std::sync::Arc::new(crate::ai::TranscriptionProgress::default());
+        let transcription = translator.transcribe_samples(&samples, &progress).unwrap();
+        let requests = handle.join().unwrap();
+        assert!(requests
+            .iter()
+            .all(|i| i.contains("RIFF") && i.contains("audio/wav")));
+        assert_eq!(
+            progress.status(),
+            crate::ai::TranscriptionStatus::Transcribing { done: 3, total: 3 }
+        );
+
+        let subrips = utils::json_str_to_subrips(&transcription).unwrap();
+        let begin_times = subrips
+            .iter()
+            .map(|i| i.borrow().begin_time.as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(begin_times, vec![10_000, 50_050, 90_100]);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::ai::{AIModelScale, AIModelType};
+use crate::prelude::*;
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Read when [`AiSettings::api_key`] is empty, to keep the key out of the settings file.
+pub const API_KEY_VAR: &str = "MKSUBRIP_API_KEY";
+/// Read when [`TranslationSettings::api_key`] is empty.
+pub const TRANSLATION_API_KEY_VAR: &str = "MKSUBRIP_TRANSLATION_API_KEY";
+
+/// Where and how [`crate::ai::AiTranslator`] reaches the transcription server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiSettings {
+    pub base_url: String,
+    /// The header carrying the API key, e.g. `Authorization`. Nothing is sent if it's empty.
+    pub api_key_header: String,
+    /// Sent as written, so include the scheme if the server wants one, e.g. `Bearer sk-...`
+    /// Saved in plain text, leave it empty to read [`API_KEY_VAR`] instead.
+    pub api_key: String,
+    pub model_type: AIModelType,
+    pub scale: AIModelScale,
+    /// The spoken language, e.g. `en`, empty to let the model detect it
+    pub language: String,
+    /// The whisper.cpp model file, e.g. `ggml-base.bin`, for [`AIModelType::LocalWhisper`]
+    pub model_path: String,
+    /// Long media are transcribed in chunks of about this length
+    pub chunk_minutes: u32,
+    /// Chunks transcribed at once by a server
+    pub parallel_requests: usize,
+    /// Turn the speaker labels of the transcription into speakers, if the server sends any
+    pub diarization: bool,
+    pub translation: TranslationSettings,
+}
+
+/// The kind of server translating the text of subrips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TranslationBackend {
+    /// `POST /translate` of LibreTranslate
+    LibreTranslate,
+    /// `POST /v1/chat/completions` of an OpenAI-compatible server
+    OpenaiChat,
+}
+
+impl TranslationBackend {
+    pub const ALL: [TranslationBackend; 2] = [
+        TranslationBackend::LibreTranslate,
+        TranslationBackend::OpenaiChat,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TranslationBackend::LibreTranslate => "LibreTranslate",
+            TranslationBackend::OpenaiChat => "OpenAI-compatible chat",
+        }
+    }
+}
+
+/// Where and how [`crate::ai::MachineTranslationJob`] translates subrips.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranslationSettings {
+    pub backend: TranslationBackend,
+    pub base_url: String,
+    /// Sent in the body to LibreTranslate, as a bearer token to a chat server.
+    /// Saved in plain text, leave it empty to read [`TRANSLATION_API_KEY_VAR`] instead.
+    pub api_key: String,
+    /// The chat model, unused by LibreTranslate
+    pub model: String,
+    /// Subrips sent per request
+    pub batch_size: usize,
+    /// Subrips around a batch sent along to be translated in context
+    pub context_size: usize,
+    /// Longer translated lines are wrapped
+    pub max_line_length: usize,
+}
+
+impl TranslationSettings {
+    /// The API key, from the environment if none is saved. It may be empty.
+    pub fn api_key(&self) -> String {
+        key_or_var(&self.api_key, TRANSLATION_API_KEY_VAR)
+    }
+}
+
+impl Default for TranslationSettings {
+    fn default() -> Self {
+        Self {
+            backend: TranslationBackend::LibreTranslate,
+            base_url: "http://localhost:5000".to_string(),
+            api_key: String::new(),
+            model: String::new(),
+            batch_size: 20,
+            context_size: 2,
+            max_line_length: 42,
+        }
+    }
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:5000".to_string(),
+            api_key_header: String::new(),
+            api_key: String::new(),
+            model_type: AIModelType::OpenaiWhisper,
+            scale: AIModelScale::Base,
+            language: String::new(),
+            model_path: String::new(),
+            chunk_minutes: 10,
+            parallel_requests: 1,
+            diarization: false,
+            translation: TranslationSettings::default(),
+        }
+    }
+}
+
+impl AiSettings {
+    pub fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or(PathBuf::from("./"))
+            .join("mksubrip")
+            .join("ai.json")
+    }
+
+    /// Read the settings at `path`, the defaults if there are none yet.
+    pub fn load(path: &Path) -> Self {
+        let json_str = match fs::read_to_string(path) {
+            Ok(json_str) => json_str,
+            Err(_) => return Self::default(),
+        };
+
+        serde_json::from_str(&json_str).unwrap_or_else(|err| {
+            warn!("The AI settings {} are invalid: {}", path.display(), err);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// The API key header, if both the name and the value are set. The value is read from
+    /// [`API_KEY_VAR`] if none is saved.
+    pub fn api_key(&self) -> Option<(&str, String)> {
+        let header = self.api_key_header.trim();
+        let key = key_or_var(&self.api_key, API_KEY_VAR);
+
+        (!header.is_empty() && !key.is_empty()).then_some((header, key))
+    }
+}
+
+/// `key` trimmed, or the environment variable `var` if `key` is empty.
+fn key_or_var(key: &str, var: &str) -> String {
+    match key.trim() {
+        "" => std::env::var(var).unwrap_or_default().trim().to_string(),
+        key => key.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ai_settings() {
+        let path = std::env::temp_dir().join("mksubrip_test_ai_settings.json");
+        let _ = fs::remove_file(&path);
+        assert_eq!(AiSettings::load(&path), AiSettings::default());
+
+        let settings = AiSettings {
+            base_url: "https://example.com".to_string(),
+            api_key_header: "X-Api-Key".to_string(),
+            api_key: "secret".to_string(),
+            scale: AIModelScale::Specified("large-v3".to_string()),
+            ..Default::default()
+        };
+        settings.save(&path).unwrap();
+        assert_eq!(AiSettings::load(&path), settings);
+        assert_eq!(
+            settings.api_key(),
+            Some(("X-Api-Key", "secret".to_string()))
+        );
+
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(AiSettings::load(&path), AiSettings::default());
+    }
+
+    #[test]
+    fn test_key_or_var() {
+        std::env::set_var("MKSUBRIP_TEST_KEY_OR_VAR", " from-env ");
+        assert_eq!(key_or_var(" saved ", "MKSUBRIP_TEST_KEY_OR_VAR"), "saved");
+        assert_eq!(key_or_var("", "MKSUBRIP_TEST_KEY_OR_VAR"), "from-env");
+        assert_eq!(key_or_var("", "MKSUBRIP_TEST_KEY_OR_VAR_UNSET"), "");
+    }
+}
@@ -0,0 +1,62 @@
+//! A one-off HTTP server standing in for the AI servers in tests.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// Answer `count` requests one by one with `status` and `body`, the requests are returned
+/// by the handle.
+pub fn serve(
+    count: usize,
+    status: &'static str,
+    body: &'static str,
+) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = thread::spawn(move || {
+        (0..count)
+            .map(|_| answer(&listener, status, body))
+            .collect()
+    });
+
+    (base_url, handle)
+}
+
+pub fn serve_once(
+    status: &'static str,
+    body: &'static str,
+) -> (String, thread::JoinHandle<Vec<String>>) {
+    serve(1, status, body)
+}
+
+/// Answer the next request, return it.
+fn answer(listener: &TcpListener, status: &str, body: &str) -> String {
+    let (stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    let mut request = String::new();
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+            content_length = value.trim().parse().unwrap();
+        }
+        request.push_str(&line);
+        if line == "\r\n" {
+            break;
+        }
+    }
+    let mut request_body = vec![0; content_length];
+    reader.read_exact(&mut request_body).unwrap();
+    request.push_str(&String::from_utf8_lossy(&request_body));
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    reader.get_mut().write_all(response.as_bytes()).unwrap();
+
+    request
+}
@@ -1,19 +1,354 @@
 use crate::{
+    ai::AiSettings,
+    autosave,
+    history::{EditCommand, EditHistory},
     prelude::*,
+    subrip::{SubripId, Translation},
     ui::{self, Drawable},
-    Subrip,
+    Speaker, SpeakerId, Subrip, Timestamp, Track, TrackId,
 };
 
 use std::path;
 
 use eframe::{self, egui};
 
-#[derive(Default)]
 pub struct AppState {
+    /// Subrips of all tracks
     pub subrips: Vec<Shared<Subrip>>,
+    /// There is always at least one track
+    pub tracks: Vec<Track>,
+    /// The track new subrips are added to
+    pub active_track: TrackId,
+    /// Shared with the edits which remove or change a speaker
+    pub speakers: Shared<Vec<Speaker>>,
+    /// The id of the next speaker, those of removed speakers aren't reused
+    pub next_speaker_id: SpeakerId,
+    /// The id of the next subrip, those of removed subrips aren't reused
+    pub next_subrip_id: SubripId,
+    pub history: Shared<EditHistory>,
     pub screen_width: f32,
     pub screen_height: f32,
     pub file_path_opening: Option<path::PathBuf>,
+    /// Where the project was opened from or saved to
+    pub project_path: Option<path::PathBuf>,
+    pub ai_settings: AiSettings,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self {
+            subrips: vec![],
+            tracks: vec![Track::default()],
+            active_track: 0,
+            speakers: Shared::default(),
+            next_speaker_id: 0,
+            // 0 is left for subrips without an id.
+            next_subrip_id: 1,
+            history: Shared::default(),
+            screen_width: 0.0,
+            screen_height: 0.0,
+            file_path_opening: None,
+            project_path: None,
+            ai_settings: AiSettings::default(),
+        }
+    }
+}
+
+impl AppState {
+    pub fn track(&self, id: TrackId) -> Option<&Track> {
+        self.tracks.iter().find(|i| i.id == id)
+    }
+
+    pub fn track_mut(&mut self, id: TrackId) -> Option<&mut Track> {
+        self.tracks.iter_mut().find(|i| i.id == id)
+    }
+
+    /// Position of the track, e.g. its lane on the timeline.
+    pub fn track_index(&self, id: TrackId) -> Option<usize> {
+        self.tracks.iter().position(|i| i.id == id)
+    }
+
+    /// Append a new track and return its id.
+    pub fn add_track(&mut self, name: impl Into<String>) -> TrackId {
+        let id = self
+            .tracks
+            .iter()
+            .map(|i| i.id + 1)
+            .max()
+            .unwrap_or_default();
+        self.tracks.push(Track::new(id, name));
+
+        id
+    }
+
+    /// Remove a track without subrips, its deleted subrips and their edits are dropped for
+    /// good. The last track can't be removed.
+    pub fn remove_track(&mut self, id: TrackId) -> Result<()> {
+        let subrips = self.subrips_of_track(id);
+        if subrips.iter().any(|i| !i.borrow().is_deleted()) {
+            return Err(anyhow!("The track still has subrips"));
+        }
+        if self.tracks.len() <= 1 {
+            return Err(anyhow!("The last track can't be removed"));
+        }
+
+        self.tracks.retain(|i| i.id != id);
+        self.subrips.retain(|i| i.borrow().track != id);
+        self.history.borrow_mut().forget(&subrips);
+        if self.active_track == id {
+            self.active_track = self.tracks[0].id;
+        }
+
+        Ok(())
+    }
+
+    pub fn subrips_of_track(&self, id: TrackId) -> Vec<Shared<Subrip>> {
+        self.subrips
+            .iter()
+            .filter(|i| i.borrow().track == id)
+            .cloned()
+            .collect()
+    }
+
+    /// Append `subrip` to the active track.
+    pub fn add_subrip(&mut self, subrip: &Shared<Subrip>) {
+        self.add_subrip_to_track(subrip, self.active_track);
+    }
+
+    /// Append `subrip` to `track` with a new id.
+    pub fn add_subrip_to_track(&mut self, subrip: &Shared<Subrip>, track: TrackId) {
+        let id = self.take_subrip_id();
+        let mut borrowed_subrip = subrip.borrow_mut();
+        borrowed_subrip.id = id;
+        borrowed_subrip.track = track;
+        drop(borrowed_subrip);
+
+        self.subrips.push(subrip.clone());
+    }
+
+    pub fn subrip(&self, id: SubripId) -> Option<Shared<Subrip>> {
+        self.subrips.iter().find(|i| i.borrow().id == id).cloned()
+    }
+
+    /// The subrips whose timing is locked to `subrip`: its source if it's a translation, and
+    /// the other translations of that source.
+    pub fn linked_subrips(&self, subrip: &Shared<Subrip>) -> Vec<Shared<Subrip>> {
+        let source = {
+            let subrip = subrip.borrow();
+            subrip.translation.as_ref().map_or(subrip.id, |i| i.source)
+        };
+
+        self.subrips
+            .iter()
+            .filter(|i| !Rc::ptr_eq(i, subrip))
+            .filter(|i| {
+                let i = i.borrow();
+                !i.is_deleted()
+                    && (i.id == source
+                        || i.translation.as_ref().is_some_and(|i| i.source == source))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Retime `subrip` from `old` to `new`, along with the subrips linked to it (see
+    /// [`AppState::linked_subrips`]). The command isn't applied yet.
+    pub fn retime_command(
+        &self,
+        subrip: &Shared<Subrip>,
+        old: (Timestamp, Timestamp),
+        new: (Timestamp, Timestamp),
+    ) -> EditCommand {
+        let command = EditCommand::Retime {
+            subrip: subrip.clone(),
+            old,
+            new,
+        };
+        let linked = self.linked_subrips(subrip);
+        if linked.is_empty() {
+            return command;
+        }
+
+        let mut commands = vec![command];
+        commands.extend(linked.into_iter().map(|subrip| {
+            let old = {
+                let subrip = subrip.borrow();
+                (subrip.begin_time, subrip.end_time)
+            };
+
+            EditCommand::Retime { subrip, old, new }
+        }));
+
+        EditCommand::Batch(commands)
+    }
+
+    /// Replace `subrip` by `parts`, which are added already. The translations of `subrip`
+    /// become those of the first part. The command isn't applied yet.
+    pub fn split_command(
+        &self,
+        subrip: &Shared<Subrip>,
+        parts: Vec<Shared<Subrip>>,
+    ) -> EditCommand {
+        let source = subrip.borrow().id;
+        let first = parts.first().map(|i| i.borrow().id);
+        let command = EditCommand::Split {
+            subrip: subrip.clone(),
+            parts,
+        };
+        let Some(first) = first else {
+            return command;
+        };
+
+        let mut commands = vec![command];
+        for translation in self.subrips.iter() {
+            let old = translation.borrow().translation.clone();
+            let Some(link) = old.as_ref().filter(|i| i.source == source) else {
+                continue;
+            };
+            let new = Some(Translation {
+                source: first,
+                source_text: link.source_text.clone(),
+            });
+
+            commands.push(EditCommand::SetTranslation {
+                subrip: translation.clone(),
+                old,
+                new,
+            });
+        }
+
+        match commands.len() {
+            1 => commands.remove(0),
+            _ => EditCommand::Batch(commands),
+        }
+    }
+
+    fn take_subrip_id(&mut self) -> SubripId {
+        let id = self.next_subrip_id;
+        self.next_subrip_id += 1;
+
+        id
+    }
+
+    pub fn speaker(&self, id: SpeakerId) -> Option<Speaker> {
+        self.speakers.borrow().iter().find(|i| i.id == id).cloned()
+    }
+
+    /// Append a new speaker and return its id.
+    pub fn add_speaker(&mut self, name: impl Into<String>) -> SpeakerId {
+        // Projects written before `next_speaker_id` existed only know their speakers.
+        let id = self
+            .speakers
+            .borrow()
+            .iter()
+            .map(|i| i.id + 1)
+            .fold(self.next_speaker_id, SpeakerId::max);
+        self.next_speaker_id = id + 1;
+        self.speakers.borrow_mut().push(Speaker::new(id, name));
+
+        id
+    }
+
+    /// The speaker named `name`, which is added if there isn't one yet.
+    pub fn speaker_by_name(&mut self, name: &str) -> SpeakerId {
+        let id = self
+            .speakers
+            .borrow()
+            .iter()
+            .find(|i| i.name == name)
+            .map(|i| i.id);
+
+        id.unwrap_or_else(|| self.add_speaker(name))
+    }
+
+    /// Remove a speaker, its subrips are left without one. The command isn't applied yet.
+    pub fn remove_speaker_command(&self, id: SpeakerId) -> Option<EditCommand> {
+        let (index, speaker) = self
+            .speakers
+            .borrow()
+            .iter()
+            .enumerate()
+            .find(|(_, i)| i.id == id)
+            .map(|(index, i)| (index, i.clone()))?;
+        let subrips = self
+            .subrips
+            .iter()
+            .filter(|i| i.borrow().speaker == Some(id))
+            .cloned()
+            .collect();
+
+        Some(EditCommand::RemoveSpeaker {
+            speakers: self.speakers.clone(),
+            speaker,
+            index,
+            subrips,
+        })
+    }
+
+    /// Give the subrips of the speaker `from` to the speaker `into` and remove `from`, e.g.
+    /// the same voice labelled apart by two chunks of a transcription. The command isn't
+    /// applied yet.
+    pub fn merge_speakers_command(&self, from: SpeakerId, into: SpeakerId) -> Option<EditCommand> {
+        if from == into || self.speaker(into).is_none() {
+            return None;
+        }
+        let EditCommand::RemoveSpeaker {
+            speakers,
+            speaker,
+            index,
+            subrips,
+        } = self.remove_speaker_command(from)?
+        else {
+            return None;
+        };
+
+        let mut commands = subrips
+            .iter()
+            .map(|subrip| EditCommand::SetSpeaker {
+                subrip: subrip.clone(),
+                old: Some(from),
+                new: Some(into),
+            })
+            .collect::<Vec<_>>();
+        // No subrips are left to the speaker once it's removed.
+        commands.push(EditCommand::RemoveSpeaker {
+            speakers,
+            speaker,
+            index,
+            subrips: vec![],
+        });
+
+        Some(EditCommand::Batch(commands))
+    }
+
+    /// Turn the actors of the subrips without a speaker into speakers, e.g. the `Name` of
+    /// ASS events or the labels of a diarized transcription.
+    pub fn assign_speakers(&mut self, subrips: &[Shared<Subrip>]) {
+        for subrip in subrips.iter() {
+            let mut subrip = subrip.borrow_mut();
+            let actor = subrip.style.actor.trim();
+            if subrip.speaker.is_none() && !actor.is_empty() {
+                let id = self.speaker_by_name(actor);
+                subrip.speaker = Some(id);
+            }
+        }
+    }
+
+    /// Give new ids to the subrips without one, e.g. of projects written before ids existed.
+    pub fn assign_missing_ids(&mut self) {
+        // Projects written before `next_subrip_id` existed only know their subrips.
+        self.next_subrip_id = self
+            .subrips
+            .iter()
+            .map(|i| i.borrow().id + 1)
+            .fold(self.next_subrip_id.max(1), SubripId::max);
+
+        for subrip in self.subrips.clone().iter() {
+            if subrip.borrow().id == 0 {
+                subrip.borrow_mut().id = self.take_subrip_id();
+            }
+        }
+    }
 }
 
 pub struct App {
@@ -22,15 +357,34 @@ pub struct App {
 }
 
 impl App {
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         let app_state = Shared::new(AppState {
             screen_width: 1024.0,
             screen_height: 720.0,
+            ai_settings: AiSettings::load(&AiSettings::path()),
             ..Default::default()
         });
+        let mut mainwindow = ui::MainWindow::new(app_state.clone());
+
+        let recovery_path = autosave::recovery_path();
+        if recovery_path.exists() {
+            let result = rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Warning)
+                .set_title("Restore")
+                .set_description(
+                    "The last session wasn't closed properly. Restore the unsaved subrips?",
+                )
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show();
+
+            if result == rfd::MessageDialogResult::Yes {
+                mainwindow.recover_session(&cc.egui_ctx, &recovery_path);
+            }
+        }
+
         Self {
             state: app_state.clone(),
-            mainwindow: ui::MainWindow::new(app_state.clone()),
+            mainwindow,
         }
     }
 }
@@ -42,4 +396,172 @@ impl eframe::App for App {
             self.mainwindow.draw(ctx, eui);
         });
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.mainwindow.on_exit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks() {
+        let mut app_state = AppState::default();
+        let english = app_state.add_track("English");
+        assert_eq!(english, 1);
+
+        app_state.active_track = english;
+        let subrip = Shared::new(Subrip::new(
+            "hello",
+            Timestamp::ZERO,
+            chrono::Duration::seconds(1),
+        ));
+        app_state.add_subrip(&subrip);
+        assert_eq!(subrip.borrow().track, english);
+        assert_eq!(subrip.borrow().id, 1);
+        assert!(app_state.subrip(1).is_some());
+        assert_eq!(app_state.subrips_of_track(0).len(), 0);
+
+        assert!(app_state.remove_track(english).is_err());
+        subrip.borrow_mut().delete();
+        app_state.history.borrow_mut().push(EditCommand::Delete {
+            subrips: vec![subrip.clone()],
+        });
+        app_state.remove_track(english).unwrap();
+        assert!(app_state.subrips.is_empty());
+        // Undo can't bring the subrip back to the removed track.
+        assert!(!app_state.history.borrow().can_undo());
+        assert_eq!(app_state.active_track, 0);
+        assert!(app_state.remove_track(0).is_err());
+
+        // The id of the removed subrip isn't reused.
+        let subrip = Shared::new(Subrip::default());
+        app_state.add_subrip(&subrip);
+        assert_eq!(subrip.borrow().id, 2);
+    }
+
+    #[test]
+    fn test_linked_retime() {
+        let mut app_state = AppState::default();
+        let german = app_state.add_track("German");
+        let source = Shared::new(Subrip::new(
+            "hello",
+            Timestamp::ZERO,
+            chrono::Duration::seconds(1),
+        ));
+        app_state.add_subrip(&source);
+        let mut translation = Subrip::new("hallo", Timestamp::ZERO, chrono::Duration::seconds(1));
+        translation.translation = Some(Translation {
+            source: source.borrow().id,
+            source_text: "hello".to_string(),
+        });
+        let translation = Shared::new(translation);
+        app_state.add_subrip_to_track(&translation, german);
+
+        // Dragging the translation moves the source as well.
+        let new = (Timestamp::from_millis(500), Timestamp::from_millis(1500));
+        let command = app_state.retime_command(
+            &translation,
+            (Timestamp::ZERO, Timestamp::from_millis(1000)),
+            new,
+        );
+        command.redo();
+        assert_eq!(source.borrow().begin_time, new.0);
+        assert_eq!(translation.borrow().end_time, new.1);
+
+        command.undo();
+        assert_eq!(source.borrow().begin_time, Timestamp::ZERO);
+        assert_eq!(translation.borrow().begin_time, Timestamp::ZERO);
+    }
+
+    #[test]
+    fn test_split_keeps_translations() {
+        let mut app_state = AppState::default();
+        let german = app_state.add_track("German");
+        let source = Shared::new(Subrip::new(
+            "hello world",
+            Timestamp::ZERO,
+            chrono::Duration::seconds(2),
+        ));
+        app_state.add_subrip(&source);
+        let source_id = source.borrow().id;
+        let mut translation =
+            Subrip::new("hallo welt", Timestamp::ZERO, chrono::Duration::seconds(2));
+        translation.translation = Some(Translation {
+            source: source_id,
+            source_text: "hello world".to_string(),
+        });
+        let translation = Shared::new(translation);
+        app_state.add_subrip_to_track(&translation, german);
+
+        let (first, second) = source.borrow().split().unwrap();
+        let parts = vec![Shared::new(first), Shared::new(second)];
+        parts.iter().for_each(|i| app_state.add_subrip(i));
+        let first_id = parts[0].borrow().id;
+
+        let command = app_state.split_command(&source, parts);
+        command.redo();
+        let source_of =
+            |subrip: &Shared<Subrip>| subrip.borrow().translation.as_ref().map(|i| i.source);
+        assert_eq!(source_of(&translation), Some(first_id));
+
+        command.undo();
+        assert_eq!(source_of(&translation), Some(source_id));
+        assert!(!source.borrow().is_deleted());
+    }
+
+    #[test]
+    fn test_speakers() {
+        let mut app_state = AppState::default();
+        let subrips = ["SPEAKER_00", "SPEAKER_01", "SPEAKER_00", ""]
+            .iter()
+            .map(|actor| {
+                let mut subrip = Subrip::new("hi", Timestamp::ZERO, chrono::Duration::seconds(1));
+                subrip.style.actor = actor.to_string();
+                let subrip = Shared::new(subrip);
+                app_state.add_subrip(&subrip);
+
+                subrip
+            })
+            .collect::<Vec<_>>();
+
+        app_state.assign_speakers(&subrips);
+        assert_eq!(app_state.speakers.borrow().len(), 2);
+        assert_eq!(app_state.speaker(1).unwrap().name, "SPEAKER_01");
+        assert_ne!(
+            app_state.speaker(0).unwrap().color,
+            app_state.speaker(1).unwrap().color
+        );
+        assert_eq!(subrips[0].borrow().speaker, Some(0));
+        assert_eq!(subrips[2].borrow().speaker, Some(0));
+        assert_eq!(subrips[3].borrow().speaker, None);
+
+        let command = app_state.remove_speaker_command(0).unwrap();
+        command.redo();
+        assert_eq!(subrips[0].borrow().speaker, None);
+        assert!(app_state.speaker(0).is_none());
+
+        // Undoing brings back the speaker and its subrips.
+        command.undo();
+        assert_eq!(app_state.speakers.borrow()[0].name, "SPEAKER_00");
+        assert_eq!(subrips[2].borrow().speaker, Some(0));
+        command.redo();
+
+        // The id of a removed speaker isn't reused.
+        assert_eq!(app_state.add_speaker("Interviewer"), 2);
+        app_state.remove_speaker_command(2).unwrap().redo();
+        assert_eq!(app_state.add_speaker("Guest"), 3);
+
+        // Merging moves the subrips over and removes the speaker, undoably.
+        assert!(app_state.merge_speakers_command(1, 1).is_none());
+        let command = app_state.merge_speakers_command(1, 3).unwrap();
+        command.redo();
+        assert_eq!(subrips[1].borrow().speaker, Some(3));
+        assert!(app_state.speaker(1).is_none());
+        command.undo();
+        assert_eq!(subrips[1].borrow().speaker, Some(1));
+        assert_eq!(app_state.speakers.borrow()[0].name, "SPEAKER_01");
+    }
 }
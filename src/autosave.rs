@@ -0,0 +1,155 @@
+use crate::app::AppState;
+use crate::prelude::*;
+use crate::project::{Project, PROJECT_EXTENSION};
+use crate::ui::Timeline;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The snapshot of the last session, it's left behind only if the app didn't exit cleanly.
+pub fn recovery_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or(PathBuf::from("./"))
+        .join("mksubrip")
+        .join("recovery")
+        .join(format!("session.{}", PROJECT_EXTENSION))
+}
+
+/// Write `content` to a temporary file first, so a crash never leaves a truncated snapshot.
+fn write_atomically(path: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Writes snapshots on a background thread, so the UI never waits for the disk.
+struct RecoveryWriter {
+    path: PathBuf,
+    sender: Option<mpsc::Sender<String>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl RecoveryWriter {
+    fn spawn(path: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel::<String>();
+        let thread_path = path.clone();
+        let handle = thread::spawn(move || {
+            for content in receiver {
+                if let Err(err) = write_atomically(&thread_path, &content) {
+                    error!("Autosave to {} failed: {}", thread_path.display(), err);
+                }
+            }
+        });
+
+        Self {
+            path,
+            sender: Some(sender),
+            handle: Some(handle),
+        }
+    }
+
+    fn write(&self, content: String) {
+        if let Some(sender) = self.sender.as_ref() {
+            let _ = sender.send(content);
+        }
+    }
+
+    /// Wait for the pending snapshots, then remove the recovery file.
+    fn discard(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+
+        if let Err(err) = fs::remove_file(&self.path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                error!("Removing {} failed: {}", self.path.display(), err);
+            }
+        }
+    }
+}
+
+/// Periodically snapshot the session to [`recovery_path`].
+pub struct Autosave {
+    app_state: Shared<AppState>,
+    timeline: Shared<Timeline>,
+
+    writer: RecoveryWriter,
+    last_saved_at: Instant,
+    last_snapshot: String,
+}
+
+impl Autosave {
+    pub fn new(app_state: Shared<AppState>, timeline: Shared<Timeline>) -> Self {
+        Self {
+            app_state,
+            timeline,
+            writer: RecoveryWriter::spawn(recovery_path()),
+            last_saved_at: Instant::now(),
+            last_snapshot: String::new(),
+        }
+    }
+
+    /// Called every frame, take a snapshot once the interval has passed and the session changed.
+    pub fn update(&mut self) {
+        if self.last_saved_at.elapsed() < AUTOSAVE_INTERVAL {
+            return;
+        }
+        self.last_saved_at = Instant::now();
+
+        let view = self.timeline.borrow().view();
+        let project = Project::new(&self.app_state.borrow(), view);
+        if project.subrips.is_empty() {
+            return;
+        }
+
+        match project.to_json() {
+            Ok(snapshot) if snapshot != self.last_snapshot => {
+                debug!("Autosave to {}", recovery_path().display());
+                self.writer.write(snapshot.clone());
+                self.last_snapshot = snapshot;
+            }
+            Ok(_) => {}
+            Err(err) => error!("Autosave failed: {}", err),
+        }
+    }
+
+    /// Called on a clean exit, there is nothing to recover then.
+    pub fn discard(&mut self) {
+        self.writer.discard();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_writer() {
+        let path = std::env::temp_dir()
+            .join("mksubrip_test_recovery")
+            .join("session.mksubrip");
+        let mut writer = RecoveryWriter::spawn(path.clone());
+
+        writer.write("first".to_string());
+        writer.write("second".to_string());
+        // Dropping the sender waits for both snapshots.
+        writer.sender.take();
+        writer.handle.take().unwrap().join().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+
+        writer.discard();
+        assert!(!path.exists());
+    }
+}
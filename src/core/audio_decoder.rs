@@ -0,0 +1,76 @@
+use crate::core::media_player::{
+    get_decoder_from_stream_index, get_stream_indices_of_type, packed,
+};
+
+use anyhow::Result;
+use ffmpeg_the_third as ffmpeg;
+
+use ffmpeg::format::{input, sample, Sample};
+use ffmpeg::frame::Audio;
+use ffmpeg::media::Type;
+use ffmpeg::{software, ChannelLayout};
+use std::path::Path;
+
+/// Decode the first audio stream of `path` into mono `f32` samples at `sample_rate`,
+/// e.g. for drawing waveforms or speech recognition.
+///
+/// `on_samples` receives the samples chunk by chunk, return `false` from it to stop decoding.
+pub fn decode_mono(
+    path: &Path,
+    sample_rate: u32,
+    mut on_samples: impl FnMut(&[f32]) -> bool,
+) -> Result<()> {
+    let mut input_context = input(&path)?;
+    let stream_index = *get_stream_indices_of_type(&input_context, Type::Audio)
+        .front()
+        .ok_or(ffmpeg::Error::StreamNotFound)?;
+    let mut decoder = get_decoder_from_stream_index(&input_context, stream_index)?.audio()?;
+
+    // Some codecs leave the layout unset, guess it from the number of channels.
+    let channel_layout = if decoder.channel_layout().is_empty() {
+        ChannelLayout::default(decoder.channels() as i32)
+    } else {
+        decoder.channel_layout()
+    };
+    let mut resampler = software::resampling::Context::get(
+        decoder.format(),
+        channel_layout,
+        decoder.rate(),
+        Sample::F32(sample::Type::Packed),
+        ChannelLayout::MONO,
+        sample_rate,
+    )?;
+
+    let mut decoded_frame = Audio::empty();
+    let mut resampled_frame = Audio::empty();
+    // Return `false` once `on_samples` asks to stop.
+    let mut receive_frames = |decoder: &mut ffmpeg::decoder::Audio| -> Result<bool> {
+        while decoder.receive_frame(&mut decoded_frame).is_ok() {
+            if decoded_frame.channel_layout().is_empty() {
+                decoded_frame.set_channel_layout(channel_layout);
+            }
+            resampler.run(&decoded_frame, &mut resampled_frame)?;
+            if resampled_frame.samples() > 0 && !on_samples(packed(&resampled_frame)) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    };
+
+    for (stream, packet) in input_context.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+
+        decoder.send_packet(&packet)?;
+        if !receive_frames(&mut decoder)? {
+            return Ok(());
+        }
+    }
+
+    decoder.send_eof()?;
+    receive_frames(&mut decoder)?;
+
+    Ok(())
+}
@@ -1012,7 +1012,7 @@ impl Player {
     }
 }
 
-fn get_stream_indices_of_type(
+pub(crate) fn get_stream_indices_of_type(
     input_context: &Input,
     stream_type: ffmpeg::media::Type,
 ) -> VecDeque<usize> {
@@ -1022,7 +1022,7 @@ fn get_stream_indices_of_type(
         .collect::<VecDeque<_>>()
 }
 
-fn get_decoder_from_stream_index(
+pub(crate) fn get_decoder_from_stream_index(
     input_context: &Input,
     stream_index: usize,
 ) -> Result<ffmpeg::decoder::Decoder> {
@@ -1354,7 +1354,7 @@ impl AudioCallback for AudioDeviceCallback {
 #[inline]
 // Thanks https://github.com/zmwangx/rust-ffmpeg/issues/72 <3
 // Interpret the audio frame's data as packed (alternating channels, 12121212, as opposed to planar 11112222)
-fn packed<T: ffmpeg::frame::audio::Sample>(frame: &ffmpeg::frame::Audio) -> &[T] {
+pub(crate) fn packed<T: ffmpeg::frame::audio::Sample>(frame: &ffmpeg::frame::Audio) -> &[T] {
     if !frame.is_packed() {
         panic!("data is not packed");
     }
@@ -1385,7 +1385,7 @@ fn is_ffmpeg_incomplete_error(error: &anyhow::Error) -> bool {
     )
 }
 
-fn video_frame_to_image(frame: Video) -> ColorImage {
+pub(crate) fn video_frame_to_image(frame: Video) -> ColorImage {
     let size = [frame.width() as usize, frame.height() as usize];
     let data = frame.data(0);
     let stride = frame.stride(0);
@@ -1,8 +1,11 @@
+pub mod audio_decoder;
 pub mod media_player;
 pub mod request;
 pub mod shared;
 pub mod signal;
 pub mod slot;
+pub mod thumbnailer;
+pub mod video_decoder;
 
 pub use shared::Shared;
 pub use signal::Signal;
@@ -4,8 +4,11 @@ use reqwest::blocking;
 use reqwest::IntoUrl;
 
 use std::collections::HashMap;
+use std::io::{self, Read};
+use std::time::Duration;
 
-const BASE_URL: &str = "http://localhost:5000";
+/// Give up when the server doesn't accept the connection within this duration.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub fn get<U: IntoUrl>(url: U) -> reqwest::Result<blocking::Response> {
     let client = blocking::Client::new();
@@ -13,21 +16,57 @@ pub fn get<U: IntoUrl>(url: U) -> reqwest::Result<blocking::Response> {
     client.get(url).send()
 }
 
+/// `timeout` covers the whole request, from connecting to reading the response.
+/// `header` is sent along if there is one, e.g. an API key.
 pub fn post_with_multipart<U: Into<String>>(
     url: U,
+    header: Option<(&str, &str)>,
     multipart: blocking::multipart::Form,
+    timeout: Duration,
 ) -> reqwest::Result<blocking::Response> {
-    let full_url = format!("{BASE_URL}{}", url.into().as_str());
+    let full_url = url.into();
     debug!("Full URL = {}", full_url);
-    let client = blocking::Client::new();
-    let request_builder = client
-        .post(full_url)
-        .multipart(multipart)
-        .timeout(std::time::Duration::new(0xffffff, 0));
+    let client = blocking::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()?;
+    let mut request_builder = client.post(full_url).multipart(multipart).timeout(timeout);
+    if let Some((name, value)) = header {
+        request_builder = request_builder.header(name, value);
+    }
 
     request_builder.send()
 }
 
+/// Reports how many bytes have been read, e.g. to show the progress of an upload.
+/// Reading fails once `on_read` returns `false`, which aborts the upload.
+pub struct ProgressReader<R, F> {
+    inner: R,
+    read_bytes: u64,
+    on_read: F,
+}
+
+impl<R: Read, F: FnMut(u64) -> bool> ProgressReader<R, F> {
+    pub fn new(inner: R, on_read: F) -> Self {
+        Self {
+            inner,
+            read_bytes: 0,
+            on_read,
+        }
+    }
+}
+
+impl<R: Read, F: FnMut(u64) -> bool> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read_bytes += n as u64;
+        if !(self.on_read)(self.read_bytes) {
+            return Err(io::Error::other("Cancelled"));
+        }
+
+        Ok(n)
+    }
+}
+
 pub fn post_with_form<U: Into<String>, K, V>(
     _url: U,
     _map: HashMap<K, V>,
@@ -35,9 +74,45 @@ pub fn post_with_form<U: Into<String>, K, V>(
     todo!()
 }
 
-pub fn post_with_json<U: Into<String>, K, V>(
-    _url: U,
-    _map: HashMap<K, V>,
+/// Like [`post_with_multipart`], with `json` as the body.
+pub fn post_with_json<U: Into<String>>(
+    url: U,
+    header: Option<(&str, &str)>,
+    json: &serde_json::Value,
+    timeout: Duration,
 ) -> reqwest::Result<blocking::Response> {
-    todo!()
+    let full_url = url.into();
+    debug!("Full URL = {}", full_url);
+    let client = blocking::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()?;
+    let mut request_builder = client
+        .post(full_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(json.to_string())
+        .timeout(timeout);
+    if let Some((name, value)) = header {
+        request_builder = request_builder.header(name, value);
+    }
+
+    request_builder.send()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_reader() {
+        let mut progress = vec![];
+        let mut reader = ProgressReader::new(&[0u8; 10][..], |n| {
+            progress.push(n);
+            n < 8
+        });
+
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert!(reader.read(&mut buf).is_err());
+        assert_eq!(progress, vec![4, 8]);
+    }
 }
@@ -0,0 +1,86 @@
+use crate::core::media_player::video_frame_to_image;
+use crate::prelude::*;
+
+use anyhow::Result;
+use egui::ColorImage;
+use ffmpeg_the_third as ffmpeg;
+
+use ffmpeg::format::context::input::Input;
+use ffmpeg::format::{input, Pixel};
+use ffmpeg::media::Type;
+use ffmpeg::software::scaling::{context::Context, flag::Flags};
+use ffmpeg::util::frame::video::Video;
+use ffmpeg::{rescale, Rescale};
+use std::path::Path;
+
+/// Decodes single frames by seeking, independently of the playback in [`crate::core::media_player::Player`].
+pub struct Thumbnailer {
+    input_context: Input,
+    video_decoder: ffmpeg::decoder::Video,
+    video_stream_index: usize,
+    height: u32,
+}
+
+impl Thumbnailer {
+    /// Thumbnails are scaled to `height` pixels, keeping the aspect ratio.
+    pub fn new(path: &Path, height: u32) -> Result<Self> {
+        let input_context = input(&path)?;
+        let video_stream = input_context
+            .streams()
+            .best(Type::Video)
+            .ok_or(ffmpeg::Error::StreamNotFound)?;
+        let video_stream_index = video_stream.index();
+        let video_decoder =
+            ffmpeg::codec::context::Context::from_parameters(video_stream.parameters())?
+                .decoder()
+                .video()?;
+
+        Ok(Self {
+            input_context,
+            video_decoder,
+            video_stream_index,
+            height,
+        })
+    }
+
+    /// Decode the keyframe at or in front of `timestamp_ms`.
+    pub fn thumbnail_at(&mut self, timestamp_ms: i64) -> Result<ColorImage> {
+        let position = timestamp_ms.rescale((1, 1000), rescale::TIME_BASE);
+        self.input_context.seek(position, ..position)?;
+        self.video_decoder.flush();
+
+        let mut frame = Video::empty();
+        for (stream, packet) in self.input_context.packets() {
+            if stream.index() != self.video_stream_index {
+                continue;
+            }
+
+            self.video_decoder.send_packet(&packet)?;
+            if self.video_decoder.receive_frame(&mut frame).is_ok() {
+                return scale_frame(&frame, self.height);
+            }
+        }
+
+        self.video_decoder.send_eof()?;
+        self.video_decoder.receive_frame(&mut frame)?;
+
+        scale_frame(&frame, self.height)
+    }
+}
+
+fn scale_frame(frame: &Video, height: u32) -> Result<ColorImage> {
+    let width = (frame.width() * height / frame.height().max(1)).max(1);
+    let mut scaler = Context::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        Pixel::RGB24,
+        width,
+        height,
+        Flags::BILINEAR,
+    )?;
+    let mut rgb_frame = Video::empty();
+    scaler.run(frame, &mut rgb_frame)?;
+
+    Ok(video_frame_to_image(rgb_frame))
+}
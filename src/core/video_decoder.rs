@@ -0,0 +1,85 @@
+use anyhow::Result;
+use ffmpeg_the_third as ffmpeg;
+
+use ffmpeg::format::{input, Pixel};
+use ffmpeg::media::Type;
+use ffmpeg::software::scaling::{context::Context, flag::Flags};
+use ffmpeg::util::frame::video::Video;
+use ffmpeg::Rescale;
+use std::path::Path;
+
+/// Decode every frame of the best video stream of `path` into `width` x `height` grayscale
+/// pixels, e.g. for comparing frames with each other.
+///
+/// `on_frame` receives the timestamp in MILLISECONDS and the pixels row by row,
+/// return `false` from it to stop decoding.
+pub fn decode_gray(
+    path: &Path,
+    width: u32,
+    height: u32,
+    mut on_frame: impl FnMut(i64, &[u8]) -> bool,
+) -> Result<()> {
+    let mut input_context = input(&path)?;
+    let video_stream = input_context
+        .streams()
+        .best(Type::Video)
+        .ok_or(ffmpeg::Error::StreamNotFound)?;
+    let stream_index = video_stream.index();
+    let time_base = video_stream.time_base();
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(video_stream.parameters())?
+        .decoder()
+        .video()?;
+
+    let mut scaler = Context::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        Pixel::GRAY8,
+        width,
+        height,
+        Flags::FAST_BILINEAR,
+    )?;
+
+    let mut decoded_frame = Video::empty();
+    let mut gray_frame = Video::empty();
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    // Return `false` once `on_frame` asks to stop.
+    let mut receive_frames = |decoder: &mut ffmpeg::decoder::Video| -> Result<bool> {
+        while decoder.receive_frame(&mut decoded_frame).is_ok() {
+            let timestamp_ms = decoded_frame
+                .timestamp()
+                .unwrap_or_default()
+                .rescale(time_base, (1, 1000));
+            scaler.run(&decoded_frame, &mut gray_frame)?;
+
+            // Rows of the frame may be padded.
+            let stride = gray_frame.stride(0);
+            pixels.clear();
+            for row in gray_frame.data(0).chunks(stride).take(height as usize) {
+                pixels.extend_from_slice(&row[..width as usize]);
+            }
+
+            if !on_frame(timestamp_ms, &pixels) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    };
+
+    for (stream, packet) in input_context.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+
+        decoder.send_packet(&packet)?;
+        if !receive_frames(&mut decoder)? {
+            return Ok(());
+        }
+    }
+
+    decoder.send_eof()?;
+    receive_frames(&mut decoder)?;
+
+    Ok(())
+}
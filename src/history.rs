@@ -0,0 +1,421 @@
+use crate::prelude::*;
+use crate::subrip::Translation;
+use crate::{Speaker, SpeakerId, Subrip, Timestamp};
+
+const MAX_DEPTH: usize = 1000;
+
+/// An edit which has already been applied to the subrips.
+pub enum EditCommand {
+    Create {
+        subrip: Shared<Subrip>,
+    },
+    Delete {
+        subrips: Vec<Shared<Subrip>>,
+    },
+    Retime {
+        subrip: Shared<Subrip>,
+        /// `(begin_time, end_time)` before the edit
+        old: (Timestamp, Timestamp),
+        /// `(begin_time, end_time)` after the edit
+        new: (Timestamp, Timestamp),
+    },
+    EditText {
+        subrip: Shared<Subrip>,
+        old: String,
+        new: String,
+    },
+    SetSpeaker {
+        subrip: Shared<Subrip>,
+        old: Option<SpeakerId>,
+        new: Option<SpeakerId>,
+    },
+    SetTranslation {
+        subrip: Shared<Subrip>,
+        old: Option<Translation>,
+        new: Option<Translation>,
+    },
+    /// `subrip` is deleted and replaced by `parts`.
+    Split {
+        subrip: Shared<Subrip>,
+        parts: Vec<Shared<Subrip>>,
+    },
+    /// `speaker`, the `index`th of `speakers`, is removed and `subrips` are left without one.
+    RemoveSpeaker {
+        speakers: Shared<Vec<Speaker>>,
+        speaker: Speaker,
+        index: usize,
+        subrips: Vec<Shared<Subrip>>,
+    },
+    /// A speaker of `speakers` is renamed or recoloured, `old` and `new` share the id.
+    EditSpeaker {
+        speakers: Shared<Vec<Speaker>>,
+        old: Speaker,
+        new: Speaker,
+    },
+    /// Several edits as a single step, applied in order.
+    Batch(Vec<EditCommand>),
+}
+
+impl EditCommand {
+    pub fn undo(&self) {
+        match self {
+            EditCommand::Create { subrip } => subrip.borrow_mut().delete(),
+            EditCommand::Delete { subrips } => {
+                subrips.iter().for_each(|i| i.borrow_mut().restore());
+            }
+            EditCommand::Retime { subrip, old, .. } => {
+                let mut subrip = subrip.borrow_mut();
+                subrip.set_begin_time(&old.0);
+                subrip.set_end_time(&old.1);
+            }
+            EditCommand::EditText { subrip, old, .. } => {
+                subrip.borrow_mut().set_content(old.as_str());
+            }
+            EditCommand::SetSpeaker { subrip, old, .. } => subrip.borrow_mut().speaker = *old,
+            EditCommand::SetTranslation { subrip, old, .. } => {
+                subrip.borrow_mut().translation.clone_from(old);
+            }
+            EditCommand::Split { subrip, parts } => {
+                parts.iter().for_each(|i| i.borrow_mut().delete());
+                subrip.borrow_mut().restore();
+            }
+            EditCommand::RemoveSpeaker {
+                speakers,
+                speaker,
+                index,
+                subrips,
+            } => {
+                let mut speakers = speakers.borrow_mut();
+                let index = (*index).min(speakers.len());
+                speakers.insert(index, speaker.clone());
+                subrips
+                    .iter()
+                    .for_each(|i| i.borrow_mut().speaker = Some(speaker.id));
+            }
+            EditCommand::EditSpeaker { speakers, old, .. } => replace_speaker(speakers, old),
+            EditCommand::Batch(commands) => commands.iter().rev().for_each(EditCommand::undo),
+        }
+    }
+
+    pub fn redo(&self) {
+        match self {
+            EditCommand::Create { subrip } => subrip.borrow_mut().restore(),
+            EditCommand::Delete { subrips } => {
+                subrips.iter().for_each(|i| i.borrow_mut().delete());
+            }
+            EditCommand::Retime { subrip, new, .. } => {
+                let mut subrip = subrip.borrow_mut();
+                subrip.set_begin_time(&new.0);
+                subrip.set_end_time(&new.1);
+            }
+            EditCommand::EditText { subrip, new, .. } => {
+                subrip.borrow_mut().set_content(new.as_str());
+            }
+            EditCommand::SetSpeaker { subrip, new, .. } => subrip.borrow_mut().speaker = *new,
+            EditCommand::SetTranslation { subrip, new, .. } => {
+                subrip.borrow_mut().translation.clone_from(new);
+            }
+            EditCommand::Split { subrip, parts } => {
+                subrip.borrow_mut().delete();
+                parts.iter().for_each(|i| i.borrow_mut().restore());
+            }
+            EditCommand::RemoveSpeaker {
+                speakers,
+                speaker,
+                subrips,
+                ..
+            } => {
+                speakers.borrow_mut().retain(|i| i.id != speaker.id);
+                subrips.iter().for_each(|i| i.borrow_mut().speaker = None);
+            }
+            EditCommand::EditSpeaker { speakers, new, .. } => replace_speaker(speakers, new),
+            EditCommand::Batch(commands) => commands.iter().for_each(EditCommand::redo),
+        }
+    }
+
+    /// Drop the subrips `is_forgotten` from the edit, return `false` if nothing is left of it.
+    fn forget(&mut self, is_forgotten: &impl Fn(&Shared<Subrip>) -> bool) -> bool {
+        match self {
+            EditCommand::Delete { subrips } => {
+                subrips.retain(|i| !is_forgotten(i));
+
+                !subrips.is_empty()
+            }
+            EditCommand::Split { subrip, parts } => {
+                !is_forgotten(subrip) && !parts.iter().any(is_forgotten)
+            }
+            // The speaker is still restored without them.
+            EditCommand::RemoveSpeaker { subrips, .. } => {
+                subrips.retain(|i| !is_forgotten(i));
+
+                true
+            }
+            EditCommand::EditSpeaker { .. } => true,
+            EditCommand::Batch(commands) => {
+                commands.retain_mut(|command| command.forget(is_forgotten));
+
+                !commands.is_empty()
+            }
+            EditCommand::Create { subrip }
+            | EditCommand::Retime { subrip, .. }
+            | EditCommand::EditText { subrip, .. }
+            | EditCommand::SetSpeaker { subrip, .. }
+            | EditCommand::SetTranslation { subrip, .. } => !is_forgotten(subrip),
+        }
+    }
+
+    /// Try to absorb a following text edit of the same subrip or speaker.
+    fn merge(&mut self, other: &EditCommand) -> bool {
+        match (self, other) {
+            (EditCommand::Batch(commands), _) => {
+                commands.last_mut().is_some_and(|last| last.merge(other))
+            }
+            (
+                EditCommand::EditText { subrip, new, .. },
+                EditCommand::EditText {
+                    subrip: other_subrip,
+                    new: other_new,
+                    ..
+                },
+            ) if Rc::ptr_eq(subrip, other_subrip) => {
+                new.clone_from(other_new);
+
+                true
+            }
+            (
+                EditCommand::EditSpeaker { new, .. },
+                EditCommand::EditSpeaker { new: other_new, .. },
+            ) if new.id == other_new.id => {
+                new.clone_from(other_new);
+
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Put `speaker` in place of the speaker of the same id.
+fn replace_speaker(speakers: &Shared<Vec<Speaker>>, speaker: &Speaker) {
+    if let Some(i) = speakers
+        .borrow_mut()
+        .iter_mut()
+        .find(|i| i.id == speaker.id)
+    {
+        i.clone_from(speaker);
+    }
+}
+
+/// Undo/redo stacks of the subrip edits.
+#[derive(Default)]
+pub struct EditHistory {
+    undo_stack: Vec<EditCommand>,
+    redo_stack: Vec<EditCommand>,
+    /// The text field the latest edit was typed into, until it loses the focus
+    session: Option<egui::Id>,
+}
+
+impl EditHistory {
+    /// Record an edit which has already been applied.
+    pub fn push(&mut self, command: EditCommand) {
+        self.push_command(command);
+        self.session = None;
+    }
+
+    /// Record a text edit typed into the text field `session`, consecutive keystrokes of
+    /// the same field are merged into one step.
+    pub fn push_typing(&mut self, command: EditCommand, session: egui::Id) {
+        let merged = self.session == Some(session)
+            && self
+                .undo_stack
+                .last_mut()
+                .is_some_and(|last| last.merge(&command));
+
+        if merged {
+            self.redo_stack.clear();
+        } else {
+            self.push_command(command);
+        }
+        self.session = Some(session);
+    }
+
+    /// Stop merging keystrokes into the latest edit, e.g. when its text field loses the focus.
+    pub fn end_session(&mut self) {
+        self.session = None;
+    }
+
+    fn push_command(&mut self, command: EditCommand) {
+        self.undo_stack.push(command);
+        if self.undo_stack.len() > MAX_DEPTH {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    /// Revert the latest edit, return `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if let Some(command) = self.undo_stack.pop() {
+            command.undo();
+            self.redo_stack.push(command);
+            self.session = None;
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Re-apply the latest undone edit, return `false` if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if let Some(command) = self.redo_stack.pop() {
+            command.redo();
+            self.undo_stack.push(command);
+            self.session = None;
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Drop the edits of `subrips`, e.g. when they are removed for good.
+    pub fn forget(&mut self, subrips: &[Shared<Subrip>]) {
+        let is_forgotten = |subrip: &Shared<Subrip>| subrips.iter().any(|i| Rc::ptr_eq(i, subrip));
+
+        self.undo_stack
+            .retain_mut(|command| command.forget(&is_forgotten));
+        self.redo_stack
+            .retain_mut(|command| command.forget(&is_forgotten));
+        self.session = None;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.session = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retime(subrip: &Shared<Subrip>, begin_ms: i64, end_ms: i64) -> EditCommand {
+        let old = {
+            let subrip = subrip.borrow();
+            (subrip.get_begin_time(), subrip.get_end_time())
+        };
+        let new = (
+            Timestamp::from_millis(begin_ms),
+            Timestamp::from_millis(end_ms),
+        );
+        let command = EditCommand::Retime {
+            subrip: subrip.clone(),
+            old,
+            new,
+        };
+        command.redo();
+
+        command
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let subrip = Shared::new(Subrip::new(
+            "hello",
+            Timestamp::ZERO,
+            chrono::Duration::seconds(1),
+        ));
+        let mut history = EditHistory::default();
+
+        history.push(retime(&subrip, 500, 1500));
+        // Separate drags are separate steps.
+        history.push(retime(&subrip, 700, 1700));
+        history.push(EditCommand::Delete {
+            subrips: vec![subrip.clone()],
+        });
+        subrip.borrow_mut().delete();
+
+        assert!(history.undo());
+        assert!(!subrip.borrow().is_deleted());
+        assert!(history.undo());
+        assert_eq!(
+            subrip.borrow().get_begin_time(),
+            Timestamp::from_millis(500)
+        );
+        assert!(history.undo());
+        assert_eq!(subrip.borrow().get_begin_time(), Timestamp::ZERO);
+        assert!(!history.undo());
+
+        assert!(history.redo());
+        assert_eq!(subrip.borrow().get_end_time(), Timestamp::from_millis(1500));
+        assert!(history.can_redo());
+    }
+
+    #[test]
+    fn test_typing_session() {
+        let subrip = Shared::new(Subrip::new(
+            "",
+            Timestamp::ZERO,
+            chrono::Duration::seconds(1),
+        ));
+        let mut history = EditHistory::default();
+        let type_text = |history: &mut EditHistory, text: &str, session: &str| {
+            let command = EditCommand::EditText {
+                subrip: subrip.clone(),
+                old: subrip.borrow().get_content(),
+                new: text.to_string(),
+            };
+            command.redo();
+            history.push_typing(command, egui::Id::new(session));
+        };
+
+        type_text(&mut history, "h", "content");
+        type_text(&mut history, "hi", "content");
+        // Another field, e.g. the edit window, starts a new step.
+        type_text(&mut history, "hi!", "window");
+        history.end_session();
+        type_text(&mut history, "hi!!", "window");
+
+        assert!(history.undo());
+        assert_eq!(subrip.borrow().get_content(), "hi!");
+        assert!(history.undo());
+        assert_eq!(subrip.borrow().get_content(), "hi");
+        assert!(history.undo());
+        assert_eq!(subrip.borrow().get_content(), "");
+        assert!(!history.undo());
+    }
+
+    #[test]
+    fn test_speaker_edits() {
+        let speakers = Shared::new(vec![Speaker::new(0, "A")]);
+        let mut history = EditHistory::default();
+        for name in ["An", "Ann"] {
+            let old = speakers.borrow()[0].clone();
+            let command = EditCommand::EditSpeaker {
+                speakers: speakers.clone(),
+                new: Speaker {
+                    name: name.to_string(),
+                    ..old.clone()
+                },
+                old,
+            };
+            command.redo();
+            history.push_typing(command, egui::Id::new("name"));
+        }
+        assert_eq!(speakers.borrow()[0].name, "Ann");
+
+        // The renaming is a single step.
+        assert!(history.undo());
+        assert_eq!(speakers.borrow()[0].name, "A");
+        assert!(!history.undo());
+    }
+}
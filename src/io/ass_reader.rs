@@ -0,0 +1,320 @@
+use crate::io::srt_reader::normalize;
+use crate::prelude::*;
+use crate::subrip::{AssComment, AssHeader, SubripFormat, SubripHeader};
+use crate::timestamp::ass_timestamp;
+use crate::{Reader, Subrip, Timestamp};
+
+use nom::bytes::complete::{is_not, take_while1};
+use nom::character::complete::{char, space0};
+use nom::combinator::rest;
+use nom::sequence::{delimited, separated_pair, tuple};
+use nom::IResult;
+
+use std::fs;
+use std::path::Path;
+
+pub struct AssReader {
+    content: String,
+    header: Option<SubripHeader>,
+}
+
+impl Reader for AssReader {
+    fn read(&mut self) -> Result<Vec<Shared<Subrip>>> {
+        let (header, mut subrips) = parse(&self.content)?;
+
+        for (k, v) in subrips.iter_mut().enumerate() {
+            v.set_index((k + 1) as u32);
+        }
+        self.header = Some(header);
+
+        Ok(subrips.into_iter().map(Shared::new).collect())
+    }
+
+    fn header(&self) -> Option<SubripHeader> {
+        self.header.clone()
+    }
+}
+
+impl AssReader {
+    pub fn new(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+
+        Ok(Self::with_content(content))
+    }
+
+    pub fn with_content(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            header: None,
+        }
+    }
+}
+
+enum Section {
+    None,
+    ScriptInfo,
+    Styles,
+    Events,
+    Extra,
+}
+
+fn split_columns(str: &str, n: usize) -> Vec<String> {
+    str.splitn(n.max(1), ',')
+        .map(|i| i.trim().to_string())
+        .collect()
+}
+
+fn parse(content: &str) -> Result<(SubripHeader, Vec<Subrip>)> {
+    let content = normalize(content);
+    let mut header = SubripHeader::default();
+    let mut ass = AssHeader {
+        script_info: vec![],
+        style_format: vec![],
+        styles: vec![],
+        ..Default::default()
+    };
+    let mut subrips = vec![];
+    let mut section = Section::None;
+
+    for (k, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Ok((_, name)) = section_heading(line) {
+            section = match name.to_lowercase().as_str() {
+                "script info" => Section::ScriptInfo,
+                "v4+ styles" | "v4 styles" => {
+                    ass.style_section = line.trim().to_string();
+                    Section::Styles
+                }
+                "events" => Section::Events,
+                _ => {
+                    ass.extra_sections.push(line.to_string());
+                    Section::Extra
+                }
+            };
+
+            continue;
+        }
+
+        match section {
+            Section::None => {
+                return Err(anyhow!("Invalid ASS content at line {}", k + 1));
+            }
+            Section::ScriptInfo => ass.script_info.push(line.to_string()),
+            Section::Styles => match key_value(line) {
+                Ok((_, ("Format", value))) => ass.style_format = split_columns(value, usize::MAX),
+                Ok((_, ("Style", value))) => ass
+                    .styles
+                    .push(split_columns(value, ass.style_format.len())),
+                _ => warn!("Unknown line of ASS styles is dropped: {}", line),
+            },
+            Section::Events => match key_value(line) {
+                Ok((_, ("Format", value))) => ass.event_format = split_columns(value, usize::MAX),
+                Ok((_, ("Dialogue", value))) => {
+                    let subrip = dialogue(&ass.event_format, value)
+                        .map_err(|err| anyhow!("{} at line {}", err, k + 1))?;
+                    // The events since the last dialogue came before this one.
+                    for comment in ass.comments.iter_mut().rev() {
+                        if comment.before.is_some() {
+                            break;
+                        }
+                        comment.before = Some(subrip.begin_time);
+                    }
+                    subrips.push(subrip);
+                }
+                _ => {
+                    let last_time = subrips.last().map(|i: &Subrip| i.begin_time);
+                    ass.comments.push(AssComment {
+                        line: line.to_string(),
+                        time: event_start(&ass.event_format, line)
+                            .or(last_time)
+                            .unwrap_or_default(),
+                        before: None,
+                    });
+                }
+            },
+            Section::Extra => {
+                if let Some(extra_section) = ass.extra_sections.last_mut() {
+                    extra_section.push('\n');
+                    extra_section.push_str(line);
+                }
+            }
+        }
+    }
+
+    if ass.style_format.is_empty() {
+        let default = AssHeader::default();
+        ass.style_format = default.style_format;
+        ass.styles = default.styles;
+    }
+    header.ass = ass;
+
+    Ok((header, subrips))
+}
+
+/// `[Section Name]`
+fn section_heading(input: &str) -> IResult<&str, &str> {
+    delimited(
+        tuple((space0, char('['))),
+        is_not("]"),
+        tuple((char(']'), space0)),
+    )(input)
+}
+
+/// `Key: value`
+fn key_value(input: &str) -> IResult<&str, (&str, &str)> {
+    separated_pair(
+        take_while1(|c: char| c.is_alphanumeric()),
+        tuple((char(':'), space0)),
+        rest,
+    )(input)
+}
+
+/// The `Start` of an event line like `Comment: 0,0:00:01.00,…`.
+pub fn event_start(format: &[String], line: &str) -> Option<Timestamp> {
+    let (_, (_, value)) = key_value(line).ok()?;
+    let index = format.iter().position(|i| i == "Start")?;
+    let start = split_columns(value, format.len()).into_iter().nth(index)?;
+
+    ass_timestamp(&start).ok().map(|(_, timestamp)| timestamp)
+}
+
+fn dialogue(format: &[String], value: &str) -> Result<Subrip> {
+    let mut subrip = Subrip {
+        format: SubripFormat::ASS,
+        ..Default::default()
+    };
+
+    for (column, value) in format.iter().zip(split_columns(value, format.len())) {
+        match column.as_str() {
+            "Layer" => subrip.style.layer = value.parse().unwrap_or_default(),
+            "Start" => {
+                subrip.begin_time = ass_timestamp(&value)
+                    .map_err(|_| anyhow!("Invalid ASS timestamp {}", value))?
+                    .1
+            }
+            "End" => {
+                subrip.end_time = ass_timestamp(&value)
+                    .map_err(|_| anyhow!("Invalid ASS timestamp {}", value))?
+                    .1
+            }
+            "Style" => subrip.style.name = value,
+            "Name" | "Actor" => subrip.style.actor = value,
+            "MarginL" => subrip.style.margin_l = value.parse().unwrap_or_default(),
+            "MarginR" => subrip.style.margin_r = value.parse().unwrap_or_default(),
+            "MarginV" => subrip.style.margin_v = value.parse().unwrap_or_default(),
+            "Effect" => subrip.style.effect = value,
+            // Override blocks like `{\an8}` stay in the content.
+            "Text" => subrip.content = value.replace("\\N", "\n"),
+            _ => {}
+        }
+    }
+
+    Ok(subrip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::ass_writer::AssWriter;
+    use crate::{Timestamp, Writer};
+
+    const SAMPLE: &str = "[Script Info]
+; Made by hand
+Title: Sample
+ScriptType: v4.00+
+PlayResX: 1920
+PlayResY: 1080
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, Alignment
+Style: Default,Arial,48,&H00FFFFFF,2
+Style: Sign,Arial,32,&H0000FFFF,8
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Comment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,timing notes
+Dialogue: 0,0:00:01.00,0:00:02.50,Default,Alice,0,0,0,,Hello, {\\i1}world{\\i0}\\Nagain
+Dialogue: 1,1:00:03.00,1:00:04.00,Sign,,10,20,30,,{\\an8\\pos(960,100)}Shop
+
+[Fonts]
+fontname: font.ttf
+";
+
+    #[test]
+    fn test_read_ass() {
+        let mut reader = AssReader::with_content(SAMPLE);
+        let subrips = reader.read().unwrap();
+        let header = reader.header().unwrap();
+
+        assert_eq!(subrips.len(), 2);
+        assert_eq!(header.ass.styles.len(), 2);
+        assert_eq!(
+            header.ass.comments,
+            vec![AssComment {
+                line: "Comment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,timing notes".to_string(),
+                time: Timestamp::ZERO,
+                before: Some(Timestamp::from_millis(1000)),
+            }]
+        );
+        assert_eq!(
+            subrips[0].borrow().get_content(),
+            "Hello, {\\i1}world{\\i0}\nagain"
+        );
+        assert_eq!(subrips[0].borrow().style.actor, "Alice");
+        assert_eq!(subrips[1].borrow().style.margin_v, 30);
+        assert_eq!(
+            subrips[1].borrow().get_begin_time(),
+            Timestamp::from_hms_milli(1, 0, 3, 0)
+        );
+    }
+
+    #[test]
+    fn test_ass_round_trip() {
+        let mut reader = AssReader::with_content(SAMPLE);
+        let subrips = reader
+            .read()
+            .unwrap()
+            .iter()
+            .map(|i| i.borrow().clone())
+            .collect::<Vec<_>>();
+
+        let mut writer = AssWriter::new();
+        writer.write_header(&reader.header().unwrap()).unwrap();
+        writer.write_multi(&subrips).unwrap();
+
+        assert_eq!(writer.finish(), SAMPLE);
+    }
+
+    #[test]
+    fn test_ssa_round_trip() {
+        let sample = "[Script Info]
+ScriptType: v4.00
+
+[V4 Styles]
+Format: Name, Fontname, Fontsize
+Style: Default,Arial,20
+
+[Events]
+Format: Marked, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: Marked=0,0:00:01.00,0:00:02.00,Default,,0,0,0,,One
+Comment: Marked=0,0:00:02.00,0:00:03.00,Default,,0,0,0,,between
+Dialogue: Marked=0,0:00:03.00,0:00:04.00,Default,,0,0,0,,Two
+";
+        let mut reader = AssReader::with_content(sample);
+        let subrips = reader
+            .read()
+            .unwrap()
+            .iter()
+            .map(|i| i.borrow().clone())
+            .collect::<Vec<_>>();
+
+        let mut writer = AssWriter::new();
+        writer.write_header(&reader.header().unwrap()).unwrap();
+        writer.write_multi(&subrips).unwrap();
+
+        assert_eq!(writer.finish(), sample);
+    }
+}
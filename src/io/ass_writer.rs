@@ -0,0 +1,272 @@
+use crate::prelude::*;
+use crate::subrip::{AssComment, AssHeader, SubripFormat, SubripHeader};
+use crate::{Subrip, Timestamp, Writer};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+pub struct AssWriter {
+    buffer: String,
+    event_format: Vec<String>,
+    comments: Vec<AssComment>,
+    extra_sections: Vec<String>,
+    word_timing: bool,
+}
+
+impl Writer for AssWriter {
+    fn format(&self) -> SubripFormat {
+        SubripFormat::ASS
+    }
+
+    fn write_header(&mut self, header: &SubripHeader) -> Result<()> {
+        let default = AssHeader::default();
+        let mut ass = &header.ass;
+
+        // Subrips imported from other formats carry no styles at all.
+        if ass.style_format.is_empty() {
+            ass = &default;
+        }
+
+        writeln!(self.buffer, "[Script Info]")?;
+        for line in ass.script_info.iter() {
+            writeln!(self.buffer, "{}", line)?;
+        }
+
+        write!(
+            self.buffer,
+            "\n{}\nFormat: {}\n",
+            ass.style_section,
+            ass.style_format.join(", ")
+        )?;
+        for style in ass.styles.iter() {
+            writeln!(self.buffer, "Style: {}", style.join(","))?;
+        }
+
+        self.event_format.clone_from(&ass.event_format);
+        write!(
+            self.buffer,
+            "\n[Events]\nFormat: {}\n",
+            self.event_format.join(", ")
+        )?;
+
+        self.comments.clone_from(&ass.comments);
+        self.extra_sections.clone_from(&ass.extra_sections);
+
+        Ok(())
+    }
+
+    fn write(&mut self, subrip: &crate::Subrip) -> Result<()> {
+        if subrip.format != SubripFormat::ASS {
+            return Err(anyhow!("Invalid format (expected ASS)"));
+        }
+
+        let style = &subrip.style;
+        let columns = self
+            .event_format
+            .iter()
+            .map(|column| match column.as_str() {
+                "Layer" => style.layer.to_string(),
+                "Marked" => "Marked=0".to_string(),
+                "Start" => subrip.begin_time.format_ass(),
+                "End" => subrip.end_time.format_ass(),
+                "Style" if style.name.is_empty() => "Default".to_string(),
+                "Style" => style.name.clone(),
+                "Name" | "Actor" => style.actor.clone(),
+                "MarginL" => style.margin_l.to_string(),
+                "MarginR" => style.margin_r.to_string(),
+                "MarginV" => style.margin_v.to_string(),
+                "Effect" => style.effect.clone(),
+                "Text" => if self.word_timing {
+                    karaoke_content(subrip)
+                } else {
+                    None
+                }
+                .unwrap_or_else(|| subrip.content.replace('\n', "\\N")),
+                _ => String::new(),
+            })
+            .collect::<Vec<_>>();
+
+        writeln!(self.buffer, "Dialogue: {}", columns.join(","))?;
+
+        Ok(())
+    }
+
+    fn write_multi(&mut self, subrips: &[crate::Subrip]) -> Result<()> {
+        // Other events stay in front of the dialogue they came before. Those whose dialogue
+        // is gone (e.g. deleted or retimed) go before the first subrip beginning after them,
+        // those after the last dialogue stay last.
+        let begin_times = subrips.iter().map(|i| i.begin_time).collect::<HashSet<_>>();
+        let mut anchored: HashMap<Timestamp, Vec<AssComment>> = HashMap::new();
+        let mut loose = vec![];
+        let mut trailing = vec![];
+        for comment in std::mem::take(&mut self.comments) {
+            match comment.before {
+                Some(time) if begin_times.contains(&time) => {
+                    anchored.entry(time).or_default().push(comment)
+                }
+                Some(_) => loose.push(comment),
+                None => trailing.push(comment),
+            }
+        }
+        loose.sort_by_key(|i| i.time);
+
+        let mut loose = loose.into_iter().peekable();
+        for subrip in subrips {
+            while let Some(comment) = loose.next_if(|i| i.time < subrip.begin_time) {
+                writeln!(self.buffer, "{}", comment.line)?;
+            }
+            for comment in anchored.remove(&subrip.begin_time).unwrap_or_default() {
+                writeln!(self.buffer, "{}", comment.line)?;
+            }
+            self.write(subrip)?
+        }
+        for comment in loose.chain(trailing) {
+            writeln!(self.buffer, "{}", comment.line)?;
+        }
+
+        for section in self.extra_sections.iter() {
+            write!(self.buffer, "\n{}\n", section)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_word_timing(&mut self, enabled: bool) {
+        self.word_timing = enabled;
+    }
+
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.buffer)
+    }
+}
+
+impl Default for AssWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AssWriter {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+            event_format: AssHeader::default().event_format,
+            comments: vec![],
+            extra_sections: vec![],
+            word_timing: false,
+        }
+    }
+}
+
+/// The content with a `{\k}` tag in front of each timed word, `None` without timed words.
+///
+/// The durations are in centiseconds, a pause before a word gets a tag of its own. Words are
+/// cut off at the end of the subrip.
+fn karaoke_content(subrip: &Subrip) -> Option<String> {
+    let length_cs = (subrip.get_duration().num_milliseconds() / 10).max(0);
+    let mut elapsed_cs = 0;
+    let mut lines = vec![];
+    for line in subrip.timed_lines()? {
+        let mut words = vec![];
+        for (token, word) in line {
+            let begin_cs = (word.begin.as_millis() / 10).clamp(elapsed_cs, length_cs);
+            let end_cs = (word.end.as_millis() / 10).clamp(begin_cs, length_cs);
+            let pause = match begin_cs - elapsed_cs {
+                0 => String::new(),
+                pause_cs => format!("{{\\k{}}}", pause_cs),
+            };
+            words.push(format!("{}{{\\k{}}}{}", pause, end_cs - begin_cs, token));
+            elapsed_cs = end_cs;
+        }
+        lines.push(words.join(" "));
+    }
+
+    Some(lines.join("\\N"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subrip::WordTiming;
+    use crate::Timestamp;
+
+    #[test]
+    fn test_karaoke_content() {
+        let mut subrip = Subrip::new(
+            "Hello,\nworld",
+            Timestamp::ZERO,
+            chrono::Duration::seconds(2),
+        );
+        assert_eq!(karaoke_content(&subrip), None);
+
+        subrip.words = vec![
+            WordTiming {
+                text: "Hello".to_string(),
+                begin: Timestamp::from_millis(100),
+                end: Timestamp::from_millis(500),
+            },
+            WordTiming {
+                text: "world".to_string(),
+                begin: Timestamp::from_millis(800),
+                end: Timestamp::from_millis(1500),
+            },
+        ];
+        assert_eq!(
+            karaoke_content(&subrip).unwrap(),
+            "{\\k10}{\\k40}Hello,\\N{\\k30}{\\k70}world"
+        );
+
+        // A word running past the end of the subrip is cut off.
+        subrip.end_time = Timestamp::from_millis(1000);
+        assert_eq!(
+            karaoke_content(&subrip).unwrap(),
+            "{\\k10}{\\k40}Hello,\\N{\\k30}{\\k20}world"
+        );
+    }
+
+    #[test]
+    fn test_comment_anchors() {
+        let comment = |line: &str, time_ms, before_ms: Option<i64>| AssComment {
+            line: line.to_string(),
+            time: Timestamp::from_millis(time_ms),
+            before: before_ms.map(Timestamp::from_millis),
+        };
+        let subrip = |content: &str, begin_ms| {
+            let mut subrip = Subrip::new(
+                content,
+                Timestamp::from_millis(begin_ms),
+                chrono::Duration::seconds(1),
+            );
+            subrip.set_format(SubripFormat::ASS);
+            subrip
+        };
+
+        let mut writer = AssWriter::new();
+        writer.comments = vec![
+            comment("Comment: gone", 1500, Some(1000)),
+            comment("Comment: two", 0, Some(3000)),
+            comment("Comment: last", 0, None),
+        ];
+        // The first dialogue was deleted and one was added in front of the others.
+        writer
+            .write_multi(&[subrip("added", 0), subrip("one", 2000), subrip("two", 3000)])
+            .unwrap();
+
+        let lines = writer
+            .finish()
+            .lines()
+            .map(|line| line.rsplit(',').next().unwrap_or(line).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "added",
+                "Comment: gone",
+                "one",
+                "Comment: two",
+                "two",
+                "Comment: last"
+            ]
+        );
+    }
+}
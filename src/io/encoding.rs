@@ -0,0 +1,66 @@
+/// The text encoding of an exported file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    /// Some players on Windows only detect UTF-8 with a byte order mark.
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl TextEncoding {
+    pub const ALL: [TextEncoding; 4] = [
+        TextEncoding::Utf8,
+        TextEncoding::Utf8Bom,
+        TextEncoding::Utf16Le,
+        TextEncoding::Utf16Be,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf8Bom => "UTF-8 with BOM",
+            TextEncoding::Utf16Le => "UTF-16 LE",
+            TextEncoding::Utf16Be => "UTF-16 BE",
+        }
+    }
+
+    /// Encode `text`, UTF-16 is always written with a byte order mark.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            TextEncoding::Utf8 => text.as_bytes().to_vec(),
+            TextEncoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat(),
+            TextEncoding::Utf16Le => std::iter::once(0xFEFF)
+                .chain(text.encode_utf16())
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+            TextEncoding::Utf16Be => std::iter::once(0xFEFF)
+                .chain(text.encode_utf16())
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(TextEncoding::Utf8.encode("é"), vec![0xC3, 0xA9]);
+        assert_eq!(
+            TextEncoding::Utf8Bom.encode("a"),
+            vec![0xEF, 0xBB, 0xBF, b'a']
+        );
+        assert_eq!(
+            TextEncoding::Utf16Le.encode("a"),
+            vec![0xFF, 0xFE, b'a', 0x00]
+        );
+        assert_eq!(
+            TextEncoding::Utf16Be.encode("a"),
+            vec![0xFE, 0xFF, 0x00, b'a']
+        );
+    }
+}
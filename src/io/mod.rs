@@ -1,99 +1,403 @@
+mod ass_reader;
+mod ass_writer;
+mod encoding;
+mod srt_reader;
 mod srt_writer;
+mod vtt_reader;
+mod vtt_writer;
 
-use chrono::Timelike;
-
+use crate::io::ass_reader::AssReader;
+use crate::io::ass_writer::AssWriter;
+use crate::io::srt_reader::SrtReader;
 use crate::io::srt_writer::SrtWriter;
+use crate::io::vtt_reader::VttReader;
+use crate::io::vtt_writer::VttWriter;
 use crate::prelude::*;
-use crate::subrip::SubripFormat;
+use crate::subrip::{SubripFormat, SubripHeader};
+use crate::{Track, TrackId};
 
+use std::fs;
 use std::path::{Path, PathBuf};
 
+pub(crate) use ass_reader::event_start;
+pub use encoding::TextEncoding;
+
 pub trait Writer {
+    /// The format of the subrips accepted by the writer.
+    fn format(&self) -> SubripFormat;
+
+    /// Write the data which belongs to the whole file. It's called before any subrip is written.
+    fn write_header(&mut self, _header: &SubripHeader) -> Result<()> {
+        Ok(())
+    }
+
     fn write(&mut self, subrip: &crate::Subrip) -> Result<()>;
 
     fn write_multi(&mut self, subrips: &[crate::Subrip]) -> Result<()>;
+
+    /// Write the timing of the words of the subrips which have it, if the format can carry it.
+    fn set_word_timing(&mut self, _enabled: bool) {}
+
+    /// Mark the text with the actor of the subrips which have one, if the format can carry it.
+    fn set_voice_tags(&mut self, _enabled: bool) {}
+
+    /// Take out everything written so far.
+    fn finish(&mut self) -> String;
 }
 
-pub struct SubripSaveHelper {
-    save_dir_path: PathBuf,
-    writers: Vec<Box<dyn Writer>>,
-    app_state: Shared<crate::app::AppState>,
+pub trait Reader {
+    fn read(&mut self) -> Result<Vec<Shared<crate::Subrip>>>;
+
+    /// The data which belongs to the whole file, available after [`Reader::read`].
+    /// Formats without such data return `None`.
+    fn header(&self) -> Option<SubripHeader> {
+        None
+    }
 }
 
-pub struct SubripWriterBuilder {}
+/// How the speakers of the subrips are marked in exported files, the `Name` column of ASS
+/// events is filled in anyway.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpeakerLabel {
+    #[default]
+    None,
+    /// `- ` in front of the text whenever the speaker changes
+    Dash,
+    /// `NAME: ` in front of the text whenever the speaker changes
+    Name,
+    /// `<v Name>` in front of the text of each subrip (WebVTT), other formats get `NAME: `
+    VoiceTag,
+}
 
-impl SubripSaveHelper {
-    pub fn new(app_state: Shared<crate::app::AppState>) -> Self {
-        let doc_dir = dirs::download_dir().unwrap_or(PathBuf::from("./"));
+impl SpeakerLabel {
+    pub const ALL: [SpeakerLabel; 4] = [
+        SpeakerLabel::None,
+        SpeakerLabel::Dash,
+        SpeakerLabel::Name,
+        SpeakerLabel::VoiceTag,
+    ];
 
-        Self {
-            save_dir_path: doc_dir,
-            writers: vec![],
-            app_state,
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpeakerLabel::None => "None",
+            SpeakerLabel::Dash => "- Dash",
+            SpeakerLabel::Name => "NAME:",
+            SpeakerLabel::VoiceTag => "<v Name> (WebVTT)",
         }
     }
+}
 
-    pub fn save_dir_path(&self) -> &Path {
-        self.save_dir_path.as_path()
-    }
+/// Where and how to export the subrips of a track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    pub track: TrackId,
+    pub path: PathBuf,
+    pub format: SubripFormat,
+    pub encoding: TextEncoding,
+    /// Write the timing of the words as karaoke tags (ASS) or inline timestamps (WebVTT)
+    pub word_timing: bool,
+    pub speaker_label: SpeakerLabel,
+}
 
-    pub fn add_writer(&mut self, writer: Box<dyn Writer>) {
-        self.writers.push(writer);
-    }
+/// Suggest an export path next to the media, named after it and the track.
+pub fn default_export_path(
+    media_path: Option<&Path>,
+    track: &Track,
+    format: SubripFormat,
+) -> PathBuf {
+    let dir = media_path
+        .and_then(|path| path.parent())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .or_else(dirs::download_dir)
+        .unwrap_or(PathBuf::from("./"));
+    let stem = media_path
+        .and_then(|path| path.file_stem())
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("exported");
+
+    dir.join(format!(
+        "{}{}.{}",
+        stem,
+        track.file_suffix(),
+        format.extension()
+    ))
+}
+
+pub struct SubripSaveHelper {
+    app_state: Shared<crate::app::AppState>,
+}
+
+pub struct SubripWriterBuilder {}
 
-    pub fn save(&mut self, _: &()) {
-        let mut subrips = vec![];
+pub struct SubripReaderBuilder {}
 
-        if self.app_state.borrow().subrips.is_empty() {
-            error!("Subrips are empty!!!");
+impl SubripSaveHelper {
+    pub fn new(app_state: Shared<crate::app::AppState>) -> Self {
+        Self { app_state }
+    }
 
-            return;
+    /// Export the subrips of a track to the file described by `options`, which is only
+    /// created here. A track without any subrip left isn't exported.
+    pub fn export(&self, options: &ExportOptions) -> Result<()> {
+        let is_empty = self
+            .app_state
+            .borrow()
+            .subrips_of_track(options.track)
+            .iter()
+            .all(|i| i.borrow().is_deleted());
+        if is_empty {
+            return Err(anyhow!("The track {} has no subrips", options.track));
         }
 
-        info!("Save to {}", self.save_dir_path.to_str().unwrap_or("null"));
+        info!("Export to {}", options.path.display());
 
-        self.app_state.borrow().subrips.iter().for_each(|i| {
-            let subrip = i.borrow().clone();
-            if !subrip.is_deleted() {
-                subrips.push(subrip);
-            }
-        });
+        let content = self.render(options)?;
+        fs::write(&options.path, options.encoding.encode(&content))
+            .map_err(|err| anyhow!("Exporting to {} failed: {}", options.path.display(), err))
+    }
 
-        // Re-sort subrips by `begin_time` of the subrip
-        subrips.sort_by(|a, b| {
-            let t0 = a.begin_time.num_seconds_from_midnight();
-            let t1 = b.begin_time.num_seconds_from_midnight();
+    /// Write the subrips of the track which aren't deleted as described by `options`.
+    fn render(&self, options: &ExportOptions) -> Result<String> {
+        let format = options.format;
+        let app_state = self.app_state.borrow();
+        let mut subrips = app_state
+            .subrips_of_track(options.track)
+            .iter()
+            .map(|i| i.borrow().clone())
+            .filter(|subrip| !subrip.is_deleted())
+            .collect::<Vec<_>>();
 
-            t0.cmp(&t1)
-        });
+        // Re-sort subrips by `begin_time` of the subrip
+        subrips.sort_by_key(|subrip| subrip.begin_time);
 
-        // Rectify the index field of the subrip in subrips.
+        // Rectify the index and the format of the subrip in subrips.
         for (k, v) in subrips.iter_mut().enumerate() {
             v.set_index((k + 1) as u32);
+            v.set_format(format);
         }
 
-        for writer in self.writers.iter_mut() {
-            if let Err(err) = writer.write_multi(&subrips) {
-                error!("{}", err.to_string());
+        let mut last_speaker = None;
+        for subrip in subrips.iter_mut() {
+            let speaker = subrip
+                .speaker
+                .and_then(|id| app_state.speaker(id))
+                .map(|i| i.name);
+            // Labels are only written where the speaker changes.
+            let is_changed = speaker.is_some() && speaker != last_speaker;
+            let label = match (options.speaker_label, speaker.as_ref()) {
+                (SpeakerLabel::Dash, Some(_)) => Some("- ".to_string()),
+                (SpeakerLabel::Name, Some(name)) => Some(format!("{}: ", name.to_uppercase())),
+                (SpeakerLabel::VoiceTag, Some(name)) if format != SubripFormat::VTT => {
+                    Some(format!("{}: ", name.to_uppercase()))
+                }
+                _ => None,
+            };
+            if let Some(name) = speaker.as_ref() {
+                subrip.style.actor.clone_from(name);
             }
+            if let Some(label) = label.filter(|_| is_changed) {
+                subrip.prefix_content(&label);
+            }
+            last_speaker = speaker;
         }
+
+        let mut writer = SubripWriterBuilder::generate_writer_from_format(format);
+        writer.set_word_timing(options.word_timing);
+        writer.set_voice_tags(options.speaker_label == SpeakerLabel::VoiceTag);
+        let header = app_state
+            .track(options.track)
+            .map(|i| i.header.clone())
+            .unwrap_or_default();
+        writer.write_header(&header)?;
+        writer.write_multi(&subrips)?;
+
+        Ok(writer.finish())
     }
 }
 
 impl SubripWriterBuilder {
-    pub fn generate_writer_from_format(
-        save_dir_path: &std::path::Path,
+    pub fn generate_writer_from_format(format: SubripFormat) -> Box<dyn Writer> {
+        match format {
+            SubripFormat::SRT => Box::new(SrtWriter::new()),
+            SubripFormat::VTT => Box::new(VttWriter::new()),
+            SubripFormat::ASS => Box::new(AssWriter::new()),
+        }
+    }
+}
+
+impl SubripReaderBuilder {
+    pub fn generate_reader_from_format(
+        file_path: &std::path::Path,
         format: SubripFormat,
-    ) -> Option<Box<dyn Writer>> {
+    ) -> Result<Box<dyn Reader>> {
         match format {
-            SubripFormat::SRT => {
-                let file_path = save_dir_path.join("exported.srt");
-                if let Ok(srt_writer) = SrtWriter::new(file_path.as_path()) {
-                    Some(Box::new(srt_writer))
-                } else {
-                    None
-                }
-            }
+            SubripFormat::SRT => Ok(Box::new(SrtReader::new(file_path)?)),
+            SubripFormat::VTT => Ok(Box::new(VttReader::new(file_path)?)),
+            SubripFormat::ASS => Ok(Box::new(AssReader::new(file_path)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppState;
+    use crate::{Subrip, Timestamp};
+
+    #[test]
+    fn test_default_export_path() {
+        let mut track = Track::default();
+        assert_eq!(
+            default_export_path(
+                Some(Path::new("/videos/talk.mp4")),
+                &track,
+                SubripFormat::VTT
+            ),
+            PathBuf::from("/videos/talk.vtt")
+        );
+        assert!(default_export_path(None, &track, SubripFormat::SRT).ends_with("exported.srt"));
+
+        track.language = "de".to_string();
+        assert_eq!(
+            default_export_path(
+                Some(Path::new("/videos/talk.mp4")),
+                &track,
+                SubripFormat::SRT
+            ),
+            PathBuf::from("/videos/talk.de.srt")
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let app_state = Shared::new(AppState::default());
+        let other_track = app_state.borrow_mut().add_track("Other");
+        for (content, begin_ms, track) in [
+            ("second", 2000, 0),
+            ("first", 0, 0),
+            ("other", 1000, other_track),
+        ] {
+            app_state.borrow_mut().active_track = track;
+            app_state.borrow_mut().add_subrip(&Shared::new(Subrip::new(
+                content,
+                Timestamp::from_millis(begin_ms),
+                chrono::Duration::seconds(1),
+            )));
         }
+
+        let path = std::env::temp_dir().join("mksubrip_test_export.srt");
+        let helper = SubripSaveHelper::new(app_state.clone());
+        let options = ExportOptions {
+            track: 0,
+            path: path.clone(),
+            format: SubripFormat::SRT,
+            encoding: TextEncoding::Utf8Bom,
+            word_timing: false,
+            speaker_label: SpeakerLabel::None,
+        };
+        helper.export(&options).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "\u{feff}1\n00:00:00,000 --> 00:00:01,000\nfirst\n\n\
+             2\n00:00:02,000 --> 00:00:03,000\nsecond\n\n"
+        );
+
+        // Nothing is written for a track whose subrips are all deleted.
+        fs::remove_file(&path).unwrap();
+        for subrip in app_state.borrow().subrips_of_track(0) {
+            subrip.borrow_mut().delete();
+        }
+        assert!(helper.export(&options).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_speaker_labels() {
+        let app_state = Shared::new(AppState::default());
+        let ana = app_state.borrow_mut().add_speaker("Ana");
+        let bob = app_state.borrow_mut().add_speaker("Bob");
+        for (k, speaker) in [Some(ana), Some(ana), Some(bob), None]
+            .into_iter()
+            .enumerate()
+        {
+            let mut subrip = Subrip::new(
+                format!("line {}", k + 1),
+                Timestamp::from_millis(k as i64 * 1000),
+                chrono::Duration::seconds(1),
+            );
+            subrip.speaker = speaker;
+            app_state.borrow_mut().add_subrip(&Shared::new(subrip));
+        }
+
+        let helper = SubripSaveHelper::new(app_state);
+        let render = |format, speaker_label| {
+            helper
+                .render(&ExportOptions {
+                    track: 0,
+                    path: PathBuf::new(),
+                    format,
+                    encoding: TextEncoding::Utf8,
+                    word_timing: false,
+                    speaker_label,
+                })
+                .unwrap()
+        };
+
+        let srt = render(SubripFormat::SRT, SpeakerLabel::Name);
+        assert!(srt.contains("\nANA: line 1\n"));
+        assert!(srt.contains("\nline 2\n"));
+        assert!(srt.contains("\nBOB: line 3\n"));
+        assert!(srt.contains("\nline 4\n"));
+
+        let srt = render(SubripFormat::SRT, SpeakerLabel::Dash);
+        assert!(srt.contains("\n- line 1\n") && srt.contains("\n- line 3\n"));
+
+        let vtt = render(SubripFormat::VTT, SpeakerLabel::VoiceTag);
+        assert!(vtt.contains("\n<v Ana>line 2\n"));
+        assert!(vtt.contains("\nline 4\n"));
+
+        let ass = render(SubripFormat::ASS, SpeakerLabel::None);
+        assert!(ass.contains(",Default,Bob,0,0,0,,line 3\n"));
+    }
+
+    #[test]
+    fn test_track_headers() {
+        let app_state = Shared::new(AppState::default());
+        let german = app_state.borrow_mut().add_track("German");
+        for track in [0, german] {
+            let subrip = Shared::new(Subrip::new(
+                "line",
+                Timestamp::ZERO,
+                chrono::Duration::seconds(1),
+            ));
+            app_state.borrow_mut().add_subrip_to_track(&subrip, track);
+        }
+        app_state
+            .borrow_mut()
+            .track_mut(german)
+            .unwrap()
+            .header
+            .vtt
+            .description = " - German".to_string();
+
+        let helper = SubripSaveHelper::new(app_state);
+        let render = |track| {
+            helper
+                .render(&ExportOptions {
+                    track,
+                    path: PathBuf::new(),
+                    format: SubripFormat::VTT,
+                    encoding: TextEncoding::Utf8,
+                    word_timing: false,
+                    speaker_label: SpeakerLabel::None,
+                })
+                .unwrap()
+        };
+
+        // Each track is written with its own header.
+        assert!(render(0).starts_with("WEBVTT\n\n"));
+        assert!(render(german).starts_with("WEBVTT - German\n\n"));
     }
 }
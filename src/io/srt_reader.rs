@@ -0,0 +1,162 @@
+use crate::prelude::*;
+use crate::timestamp::{srt_timestamp, Timestamp};
+use crate::{Reader, Subrip};
+
+use nom::bytes::complete::tag;
+use nom::character::complete::{digit1, line_ending, multispace0, not_line_ending, space0};
+use nom::combinator::{map_res, not, opt, peek, verify};
+use nom::multi::many0;
+use nom::sequence::{delimited, preceded, terminated, tuple};
+use nom::IResult;
+
+use std::fs;
+use std::path::Path;
+
+pub struct SrtReader {
+    content: String,
+}
+
+impl Reader for SrtReader {
+    fn read(&mut self) -> Result<Vec<Shared<Subrip>>> {
+        let mut subrips = parse(&self.content)?;
+
+        // Indices in the wild are not reliable, so order cues by `begin_time` and
+        // rectify the index field of every subrip.
+        subrips.sort_by_key(|subrip| subrip.begin_time);
+        for (k, v) in subrips.iter_mut().enumerate() {
+            v.set_index((k + 1) as u32);
+        }
+
+        Ok(subrips.into_iter().map(Shared::new).collect())
+    }
+}
+
+impl SrtReader {
+    pub fn new(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+
+        Ok(Self::with_content(content))
+    }
+
+    pub fn with_content(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+        }
+    }
+}
+
+/// Strip the BOM and turn every line ending into `\n`.
+pub(super) fn normalize(content: &str) -> String {
+    content
+        .trim_start_matches('\u{feff}')
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+}
+
+fn parse(content: &str) -> Result<Vec<Subrip>> {
+    let content = normalize(content);
+
+    let (rest, subrips) = many0(preceded(multispace0, cue))(content.as_str())
+        .map_err(|err| anyhow!("Parsing SRT failed: {}", err))?;
+    let (rest, _) = multispace0::<_, nom::error::Error<&str>>(rest)
+        .map_err(|err| anyhow!("Parsing SRT failed: {}", err))?;
+
+    if !rest.is_empty() {
+        let line = content[..content.len() - rest.len()].lines().count() + 1;
+
+        return Err(anyhow!("Invalid SRT content at line {}", line));
+    }
+
+    Ok(subrips)
+}
+
+fn number(input: &str) -> IResult<&str, u32> {
+    map_res(digit1, str::parse::<u32>)(input)
+}
+
+fn index_line(input: &str) -> IResult<&str, u32> {
+    terminated(delimited(space0, number, space0), line_ending)(input)
+}
+
+/// `begin --> end`, anything behind the end timestamp (e.g. coordinates) is ignored.
+fn timing_line(input: &str) -> IResult<&str, (Timestamp, Timestamp)> {
+    let (input, (_, begin, _, _, _, end, _)) = tuple((
+        space0,
+        srt_timestamp,
+        space0,
+        tag("-->"),
+        space0,
+        srt_timestamp,
+        not_line_ending,
+    ))(input)?;
+    let (input, _) = opt(line_ending)(input)?;
+
+    Ok((input, (begin, end)))
+}
+
+fn cue_head(input: &str) -> IResult<&str, (Option<u32>, (Timestamp, Timestamp))> {
+    tuple((opt(index_line), timing_line))(input)
+}
+
+/// A non-blank line which doesn't start the next cue.
+fn text_line(input: &str) -> IResult<&str, &str> {
+    preceded(
+        not(peek(cue_head)),
+        terminated(
+            verify(not_line_ending, |s: &str| !s.trim().is_empty()),
+            opt(line_ending),
+        ),
+    )(input)
+}
+
+fn cue(input: &str) -> IResult<&str, Subrip> {
+    let (input, (index, (begin_time, end_time))) = cue_head(input)?;
+    let (input, lines) = many0(text_line)(input)?;
+
+    let subrip = Subrip {
+        index: index.unwrap_or_default(),
+        begin_time,
+        end_time,
+        content: lines.join("\n"),
+        ..Default::default()
+    };
+
+    Ok((input, subrip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_srt() {
+        let text = "\u{feff}2\r\n00:00:05,000 --> 00:00:06,500\r\nWorld\r\n\r\n\
+                    1\r\n00:00:01,000 --> 00:00:02,000\r\nHello\r\nthere";
+        let subrips = SrtReader::with_content(text).read().unwrap();
+
+        assert_eq!(subrips.len(), 2);
+        assert_eq!(subrips[0].borrow().get_index(), 1);
+        assert_eq!(subrips[0].borrow().get_content(), "Hello\nthere");
+        assert_eq!(
+            subrips[1].borrow().get_end_time(),
+            Timestamp::from_hms_milli(0, 0, 6, 500)
+        );
+    }
+
+    #[test]
+    fn test_read_srt_without_blank_lines() {
+        let text = "1\n00:00:01,000 --> 00:00:02,000\nHello\n\
+                    2\n00:00:03,000 --> 00:00:04,000\nWorld\n";
+        let subrips = SrtReader::with_content(text).read().unwrap();
+
+        assert_eq!(subrips.len(), 2);
+        assert_eq!(subrips[1].borrow().get_content(), "World");
+    }
+
+    #[test]
+    fn test_read_invalid_srt() {
+        let text = "1\n00:00:01,000 --> 00:00:02,000\nHello\n\nnot a cue\n";
+
+        assert!(SrtReader::with_content(text).read().is_err());
+    }
+}
@@ -1,22 +1,25 @@
 use crate::prelude::*;
+use crate::subrip::SubripFormat;
 use crate::Writer;
 
-use std::fs;
-use std::io::Write;
-use std::path::Path;
+use std::fmt::Write;
 
 pub struct SrtWriter {
-    file: fs::File,
+    buffer: String,
 }
 
 impl Writer for SrtWriter {
+    fn format(&self) -> SubripFormat {
+        SubripFormat::SRT
+    }
+
     fn write(&mut self, subrip: &crate::Subrip) -> Result<()> {
-        if subrip.format != crate::subrip::SubripFormat::SRT {
+        if subrip.format != SubripFormat::SRT {
             return Err(anyhow!("Invalid format (expected SRT)"));
         }
         let index = subrip.index;
-        let begin_time = subrip.begin_time.format("%H:%M:%S,%3f").to_string();
-        let end_time = subrip.end_time.format("%H:%M:%S,%3f").to_string();
+        let begin_time = subrip.begin_time.format_srt();
+        let end_time = subrip.end_time.format_srt();
         let content = &subrip.content;
 
         let str = format!(
@@ -27,7 +30,7 @@ impl Writer for SrtWriter {
 "#
         );
 
-        write!(self.file, "{}", str)?;
+        write!(self.buffer, "{}", str)?;
 
         Ok(())
     }
@@ -39,14 +42,22 @@ impl Writer for SrtWriter {
 
         Ok(())
     }
-}
 
-impl SrtWriter {
-    pub fn new(path: &Path) -> Result<Self> {
-        let file = fs::File::create(path)?;
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.buffer)
+    }
+}
 
-        let writer = Self { file };
+impl Default for SrtWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        Ok(writer)
+impl SrtWriter {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+        }
     }
 }
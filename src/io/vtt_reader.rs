@@ -0,0 +1,242 @@
+use crate::io::srt_reader::normalize;
+use crate::prelude::*;
+use crate::subrip::{CueSettings, SubripFormat, SubripHeader};
+use crate::timestamp::{vtt_timestamp, Timestamp};
+use crate::{Reader, Subrip};
+
+use nom::bytes::complete::tag;
+use nom::character::complete::{line_ending, multispace0, not_line_ending, space0, space1};
+use nom::combinator::{opt, verify};
+use nom::multi::{many0, many1};
+use nom::sequence::{preceded, terminated, tuple};
+use nom::IResult;
+
+use std::fs;
+use std::path::Path;
+
+pub struct VttReader {
+    content: String,
+    header: Option<SubripHeader>,
+}
+
+impl Reader for VttReader {
+    fn read(&mut self) -> Result<Vec<Shared<Subrip>>> {
+        let (header, mut subrips) = parse(&self.content)?;
+
+        for (k, v) in subrips.iter_mut().enumerate() {
+            v.set_index((k + 1) as u32);
+        }
+        self.header = Some(header);
+
+        Ok(subrips.into_iter().map(Shared::new).collect())
+    }
+
+    fn header(&self) -> Option<SubripHeader> {
+        self.header.clone()
+    }
+}
+
+impl VttReader {
+    pub fn new(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+
+        Ok(Self::with_content(content))
+    }
+
+    pub fn with_content(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            header: None,
+        }
+    }
+}
+
+fn parse(content: &str) -> Result<(SubripHeader, Vec<Subrip>)> {
+    let content = normalize(content);
+    let mut header = SubripHeader::default();
+    let mut subrips = vec![];
+
+    let (rest, description) =
+        signature(&content).map_err(|_| anyhow!("Missing the WEBVTT signature"))?;
+    let (rest, blocks) = many0(preceded(multispace0, block))(rest)
+        .map_err(|err| anyhow!("Parsing WebVTT failed: {}", err))?;
+    let (rest, _) = multispace0::<_, nom::error::Error<&str>>(rest)
+        .map_err(|err| anyhow!("Parsing WebVTT failed: {}", err))?;
+    if !rest.is_empty() {
+        let line = content[..content.len() - rest.len()].lines().count() + 1;
+
+        return Err(anyhow!("Invalid WebVTT content at line {}", line));
+    }
+
+    header.vtt.description = description;
+
+    let mut notes = vec![];
+    for lines in blocks.iter() {
+        if is_keyword_line(lines[0], "NOTE") {
+            notes.push(lines.join("\n"));
+        } else if is_keyword_line(lines[0], "STYLE") {
+            header.vtt.styles.push(lines[1..].join("\n"));
+        } else if is_keyword_line(lines[0], "REGION") {
+            header.vtt.regions.push(lines[1..].join("\n"));
+        } else {
+            let mut subrip = cue(lines)?;
+            subrip.notes = std::mem::take(&mut notes);
+            subrips.push(subrip);
+        }
+    }
+    header.vtt.trailing_notes = notes;
+
+    Ok((header, subrips))
+}
+
+fn is_keyword_line(line: &str, keyword: &str) -> bool {
+    line == keyword || (line.starts_with(keyword) && line[keyword.len()..].starts_with([' ', '\t']))
+}
+
+fn nonblank_line(input: &str) -> IResult<&str, &str> {
+    terminated(
+        verify(not_line_ending, |s: &str| !s.trim().is_empty()),
+        opt(line_ending),
+    )(input)
+}
+
+/// `WEBVTT` and the header lines up to the first blank line.
+fn signature(input: &str) -> IResult<&str, String> {
+    let (input, _) = tag("WEBVTT")(input)?;
+    let (input, first_line) = verify(not_line_ending, |s: &str| {
+        s.is_empty() || s.starts_with([' ', '\t'])
+    })(input)?;
+    let (input, _) = opt(line_ending)(input)?;
+    let (input, lines) = many0(nonblank_line)(input)?;
+
+    let mut description = first_line.to_string();
+    for line in lines {
+        description.push('\n');
+        description.push_str(line);
+    }
+
+    Ok((input, description))
+}
+
+fn block(input: &str) -> IResult<&str, Vec<&str>> {
+    many1(nonblank_line)(input)
+}
+
+fn timing_line(input: &str) -> IResult<&str, (Timestamp, Timestamp, &str)> {
+    let (input, (_, begin, _, _, _, end, settings)) = tuple((
+        space0,
+        vtt_timestamp,
+        space1,
+        tag("-->"),
+        space1,
+        vtt_timestamp,
+        not_line_ending,
+    ))(input)?;
+
+    Ok((input, (begin, end, settings)))
+}
+
+fn cue_settings(input: &str) -> CueSettings {
+    let mut settings = CueSettings::default();
+
+    for setting in input.split_whitespace() {
+        match setting.split_once(':') {
+            Some(("vertical", value)) => settings.vertical = Some(value.to_string()),
+            Some(("line", value)) => settings.line = Some(value.to_string()),
+            Some(("position", value)) => settings.position = Some(value.to_string()),
+            Some(("size", value)) => settings.size = Some(value.to_string()),
+            Some(("align", value)) => settings.align = Some(value.to_string()),
+            Some(("region", value)) => settings.region = Some(value.to_string()),
+            _ => warn!("Unknown cue setting `{}` is dropped", setting),
+        }
+    }
+
+    settings
+}
+
+/// An optional identifier line, the timing line and the payload.
+fn cue(lines: &[&str]) -> Result<Subrip> {
+    let (identifier, timing, payload) = if lines[0].contains("-->") {
+        ("", lines[0], &lines[1..])
+    } else if lines.len() > 1 {
+        (lines[0], lines[1], &lines[2..])
+    } else {
+        return Err(anyhow!("Invalid WebVTT cue: {}", lines[0]));
+    };
+
+    let (_, (begin_time, end_time, settings)) =
+        timing_line(timing).map_err(|_| anyhow!("Invalid WebVTT timing: {}", timing))?;
+
+    Ok(Subrip {
+        format: SubripFormat::VTT,
+        begin_time,
+        end_time,
+        content: payload.join("\n"),
+        identifier: identifier.to_string(),
+        settings: cue_settings(settings),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::vtt_writer::VttWriter;
+    use crate::Writer;
+
+    const SAMPLE: &str = "WEBVTT - Sample
+
+STYLE
+::cue {
+  color: yellow;
+}
+
+NOTE This is a comment
+
+intro
+00:00:01.000 --> 00:00:02.500 line:0 position:50% align:center
+Hello
+
+00:00:03.000 --> 00:00:04.000
+World
+again
+
+NOTE trailing
+
+";
+
+    #[test]
+    fn test_read_vtt() {
+        let mut reader = VttReader::with_content(SAMPLE.replace('\n', "\r\n"));
+        let subrips = reader.read().unwrap();
+        let header = reader.header().unwrap();
+
+        assert_eq!(subrips.len(), 2);
+        assert_eq!(header.vtt.description, " - Sample");
+        assert_eq!(header.vtt.styles.len(), 1);
+        assert_eq!(subrips[0].borrow().identifier, "intro");
+        assert_eq!(subrips[0].borrow().notes, vec!["NOTE This is a comment"]);
+        assert_eq!(
+            subrips[0].borrow().settings.align.as_deref(),
+            Some("center")
+        );
+        assert_eq!(subrips[1].borrow().get_content(), "World\nagain");
+    }
+
+    #[test]
+    fn test_vtt_round_trip() {
+        let mut reader = VttReader::with_content(SAMPLE);
+        let subrips = reader
+            .read()
+            .unwrap()
+            .iter()
+            .map(|i| i.borrow().clone())
+            .collect::<Vec<_>>();
+
+        let mut writer = VttWriter::new();
+        writer.write_header(&reader.header().unwrap()).unwrap();
+        writer.write_multi(&subrips).unwrap();
+
+        assert_eq!(writer.finish(), SAMPLE);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::prelude::*;
+use crate::subrip::{CueSettings, SubripFormat, SubripHeader};
+use crate::{Subrip, Timestamp, Writer};
+
+use std::fmt::Write;
+
+pub struct VttWriter {
+    buffer: String,
+    trailing_notes: Vec<String>,
+    word_timing: bool,
+    voice_tags: bool,
+}
+
+impl Writer for VttWriter {
+    fn format(&self) -> SubripFormat {
+        SubripFormat::VTT
+    }
+
+    fn write_header(&mut self, header: &SubripHeader) -> Result<()> {
+        let vtt = &header.vtt;
+
+        write!(self.buffer, "WEBVTT{}\n\n", vtt.description)?;
+
+        for style in vtt.styles.iter() {
+            write!(self.buffer, "STYLE\n{}\n\n", style)?;
+        }
+
+        for region in vtt.regions.iter() {
+            write!(self.buffer, "REGION\n{}\n\n", region)?;
+        }
+
+        self.trailing_notes.clone_from(&vtt.trailing_notes);
+
+        Ok(())
+    }
+
+    fn write(&mut self, subrip: &crate::Subrip) -> Result<()> {
+        if subrip.format != SubripFormat::VTT {
+            return Err(anyhow!("Invalid format (expected VTT)"));
+        }
+
+        for note in subrip.notes.iter() {
+            write!(self.buffer, "{}\n\n", note)?;
+        }
+
+        if !subrip.identifier.is_empty() {
+            writeln!(self.buffer, "{}", subrip.identifier)?;
+        }
+
+        let begin_time = subrip.begin_time.format_vtt();
+        let end_time = subrip.end_time.format_vtt();
+        let settings = format_settings(&subrip.settings);
+        // A blank line terminates the cue, so it mustn't appear in the payload.
+        let content = if self.word_timing {
+            timed_content(subrip)
+        } else {
+            None
+        }
+        .unwrap_or_else(|| {
+            subrip
+                .content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        let voice = match subrip.style.actor.trim() {
+            actor if self.voice_tags && !actor.is_empty() => format!("<v {}>", escape(actor)),
+            _ => String::new(),
+        };
+
+        let str = format!(
+            r#"{begin_time} --> {end_time}{settings}
+{voice}{content}
+
+"#
+        );
+
+        write!(self.buffer, "{}", str)?;
+
+        Ok(())
+    }
+
+    fn write_multi(&mut self, subrips: &[crate::Subrip]) -> Result<()> {
+        for subrip in subrips.iter() {
+            self.write(subrip)?
+        }
+
+        for note in self.trailing_notes.iter() {
+            write!(self.buffer, "{}\n\n", note)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_word_timing(&mut self, enabled: bool) {
+        self.word_timing = enabled;
+    }
+
+    fn set_voice_tags(&mut self, enabled: bool) {
+        self.voice_tags = enabled;
+    }
+
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.buffer)
+    }
+}
+
+impl Default for VttWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VttWriter {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+            trailing_notes: vec![],
+            word_timing: false,
+            voice_tags: false,
+        }
+    }
+}
+
+/// The content with the time of each timed word but the first in front of it, `None`
+/// without timed words. The times are kept within the cue and in order.
+fn timed_content(subrip: &Subrip) -> Option<String> {
+    let mut last_time = subrip.begin_time;
+    let mut lines = vec![];
+    for (k, line) in subrip.timed_lines()?.into_iter().enumerate() {
+        let mut words = vec![];
+        for (i, (token, word)) in line.into_iter().enumerate() {
+            if k == 0 && i == 0 {
+                words.push(token.to_string());
+                continue;
+            }
+
+            let time = (subrip.begin_time + (word.begin - Timestamp::ZERO))
+                .min(subrip.end_time)
+                .max(last_time);
+            words.push(format!("<{}>{}", time.format_vtt(), token));
+            last_time = time;
+        }
+        lines.push(words.join(" "));
+    }
+
+    Some(lines.join("\n"))
+}
+
+/// The text with `&`, `<` and `>` escaped, which would end a tag or begin an entity.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn format_settings(settings: &CueSettings) -> String {
+    let mut str = String::new();
+
+    for (key, value) in [
+        ("vertical", &settings.vertical),
+        ("line", &settings.line),
+        ("position", &settings.position),
+        ("size", &settings.size),
+        ("align", &settings.align),
+        ("region", &settings.region),
+    ] {
+        if let Some(value) = value {
+            str.push_str(&format!(" {key}:{value}"));
+        }
+    }
+
+    str
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subrip::WordTiming;
+
+    #[test]
+    fn test_timed_content() {
+        let mut subrip = Subrip::new(
+            "Hello, big\nworld",
+            Timestamp::from_millis(1000),
+            chrono::Duration::milliseconds(1500),
+        );
+        subrip.words = ["hello", "big", "world"]
+            .iter()
+            .enumerate()
+            .map(|(k, text)| WordTiming {
+                text: text.to_string(),
+                begin: Timestamp::from_millis(k as i64 * 800),
+                end: Timestamp::from_millis(k as i64 * 800 + 500),
+            })
+            .collect();
+
+        // The last word begins after the cue ends, its time is kept within it.
+        assert_eq!(
+            timed_content(&subrip).unwrap(),
+            "Hello, <00:00:01.800>big\n<00:00:02.500>world"
+        );
+    }
+
+    #[test]
+    fn test_voice_tag() {
+        let mut subrip = Subrip::new("Hi", Timestamp::ZERO, chrono::Duration::seconds(1));
+        subrip.set_format(SubripFormat::VTT);
+        subrip.style.actor = "Tom & <Jerry>".to_string();
+
+        let mut writer = VttWriter::new();
+        writer.set_voice_tags(true);
+        writer.write(&subrip).unwrap();
+        assert!(writer
+            .finish()
+            .contains("\n<v Tom &amp; &lt;Jerry&gt;>Hi\n"));
+    }
+}
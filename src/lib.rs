@@ -2,13 +2,23 @@
 
 pub mod ai;
 pub mod app;
+pub mod autosave;
 pub mod core;
+pub mod history;
 pub mod io;
 pub mod prelude;
+pub mod project;
+pub mod speaker;
 pub mod subrip;
+pub mod timestamp;
+pub mod track;
 pub mod ui;
 pub mod utils;
 
 pub use app::App;
+pub use io::Reader;
 pub use io::Writer;
+pub use speaker::{Speaker, SpeakerId};
 pub use subrip::Subrip;
+pub use timestamp::Timestamp;
+pub use track::{Track, TrackId};
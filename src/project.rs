@@ -0,0 +1,422 @@
+use crate::app::AppState;
+use crate::io::event_start;
+use crate::prelude::*;
+use crate::subrip::{AssComment, SubripId};
+use crate::ui::{Monitor, SubripListWidget, Timeline};
+use crate::{Speaker, SpeakerId, Subrip, Track, TrackId};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The schema version written by this build.
+pub const PROJECT_VERSION: u32 = 4;
+pub const PROJECT_EXTENSION: &str = "mksubrip";
+
+/// `MIGRATIONS[k]` upgrades a project from the version `k + 1` to `k + 2`.
+const MIGRATIONS: [fn(&mut Value); PROJECT_VERSION as usize - 1] = [
+    add_default_track,
+    move_header_to_tracks,
+    anchor_ass_comments,
+];
+
+/// Version 2 splits subrips into tracks, the subrips of older projects go to the default track.
+fn add_default_track(value: &mut Value) {
+    if let Ok(track) = serde_json::to_value(Track::default()) {
+        value["tracks"] = Value::Array(vec![track]);
+    }
+}
+
+/// Version 3 keeps a header per track, the header of older projects was written with every
+/// track so each track takes it.
+fn move_header_to_tracks(value: &mut Value) {
+    let Some(header) = value.as_object_mut().and_then(|i| i.remove("header")) else {
+        return;
+    };
+    if let Some(tracks) = value["tracks"].as_array_mut() {
+        for track in tracks.iter_mut() {
+            track["header"] = header.clone();
+        }
+    }
+}
+
+/// Version 4 anchors the other ASS events to the start of the dialogue they came before,
+/// older projects matched them to the subrips of the track by position.
+fn anchor_ass_comments(value: &mut Value) {
+    let subrips: Vec<Subrip> = serde_json::from_value(value["subrips"].clone()).unwrap_or_default();
+    let Some(tracks) = value["tracks"].as_array_mut() else {
+        return;
+    };
+
+    for track in tracks.iter_mut() {
+        let id = track["id"].as_u64().unwrap_or_default() as TrackId;
+        let mut begin_times = subrips
+            .iter()
+            .filter(|i| i.track == id && !i.is_deleted())
+            .map(|i| i.begin_time)
+            .collect::<Vec<_>>();
+        begin_times.sort();
+
+        let Some(ass) = track.pointer_mut("/header/ass") else {
+            continue;
+        };
+        let format: Vec<String> =
+            serde_json::from_value(ass["event_format"].clone()).unwrap_or_default();
+        let Some(Value::Array(events)) = ass.as_object_mut().and_then(|i| i.remove("events"))
+        else {
+            continue;
+        };
+
+        let mut comments: Vec<AssComment> = vec![];
+        let mut dialogues = 0_usize;
+        let mut pending = 0;
+        for event in events.iter() {
+            if let Some(line) = event["Comment"].as_str() {
+                let last_time = dialogues
+                    .checked_sub(1)
+                    .and_then(|k| begin_times.get(k).copied());
+                comments.push(AssComment {
+                    line: line.to_string(),
+                    time: event_start(&format, line).or(last_time).unwrap_or_default(),
+                    before: None,
+                });
+            } else if event == "Dialogue" {
+                for comment in comments[pending..].iter_mut() {
+                    comment.before = begin_times.get(dialogues).copied();
+                }
+                pending = comments.len();
+                dialogues += 1;
+            }
+        }
+        if let Ok(comments) = serde_json::to_value(comments) {
+            ass["comments"] = comments;
+        }
+    }
+}
+
+/// Zoom and scroll position of the timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimelineView {
+    /// Seconds per pixel
+    pub granularity: f32,
+    /// Visible range in MILLISECONDS
+    pub duration_range: [i64; 2],
+}
+
+impl Default for TimelineView {
+    fn default() -> Self {
+        Self {
+            granularity: 0.1,
+            duration_range: [0, 0],
+        }
+    }
+}
+
+/// A whole editing session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    #[serde(default)]
+    pub media_path: Option<PathBuf>,
+    /// All subrips, deleted and loaded ones included.
+    #[serde(default)]
+    pub subrips: Vec<Subrip>,
+    #[serde(default)]
+    pub tracks: Vec<Track>,
+    #[serde(default)]
+    pub active_track: TrackId,
+    #[serde(default)]
+    pub speakers: Vec<Speaker>,
+    #[serde(default)]
+    pub next_speaker_id: SpeakerId,
+    #[serde(default)]
+    pub next_subrip_id: SubripId,
+    #[serde(default)]
+    pub timeline: TimelineView,
+}
+
+impl Project {
+    pub fn new(app_state: &AppState, timeline: TimelineView) -> Self {
+        Self {
+            version: PROJECT_VERSION,
+            media_path: app_state.file_path_opening.clone(),
+            subrips: app_state
+                .subrips
+                .iter()
+                .map(|i| i.borrow().clone())
+                .collect(),
+            tracks: app_state.tracks.clone(),
+            active_track: app_state.active_track,
+            speakers: app_state.speakers.borrow().clone(),
+            next_speaker_id: app_state.next_speaker_id,
+            next_subrip_id: app_state.next_subrip_id,
+            timeline,
+        }
+    }
+
+    pub fn from_json(json_str: &str) -> Result<Self> {
+        let value = migrate(serde_json::from_str(json_str)?)?;
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json()?)?;
+
+        Ok(())
+    }
+}
+
+/// Upgrade a project written by an older build to [`PROJECT_VERSION`].
+fn migrate(mut value: Value) -> Result<Value> {
+    let version = value["version"]
+        .as_u64()
+        .ok_or(anyhow!("Missing the project version"))?;
+
+    if version == 0 {
+        return Err(anyhow!("Invalid project version 0"));
+    }
+    if version > PROJECT_VERSION as u64 {
+        return Err(anyhow!(
+            "The project version {} is newer than the supported version {}",
+            version,
+            PROJECT_VERSION
+        ));
+    }
+
+    for migration in MIGRATIONS[version as usize - 1..].iter() {
+        migration(&mut value);
+    }
+    value["version"] = PROJECT_VERSION.into();
+
+    Ok(value)
+}
+
+/// Open and save projects for the main window.
+pub struct ProjectHelper {
+    app_state: Shared<AppState>,
+    monitor: Shared<Monitor>,
+    timeline: Shared<Timeline>,
+    subrip_list_widget: Shared<SubripListWidget>,
+}
+
+impl ProjectHelper {
+    pub fn new(
+        app_state: Shared<AppState>,
+        monitor: Shared<Monitor>,
+        timeline: Shared<Timeline>,
+        subrip_list_widget: Shared<SubripListWidget>,
+    ) -> Self {
+        Self {
+            app_state,
+            monitor,
+            timeline,
+            subrip_list_widget,
+        }
+    }
+
+    /// Replace the current session with the project at `path`.
+    #[allow(clippy::ptr_arg)]
+    pub fn open(&mut self, path: &PathBuf) {
+        match Project::load(path) {
+            Ok(project) => {
+                info!("Open the project {}", path.display());
+                self.replace_session(project, Some(path.clone()));
+            }
+            Err(err) => error!("Opening the project {} failed: {}", path.display(), err),
+        }
+    }
+
+    /// Replace the current session with an autosaved one. It stays unsaved until "Save As".
+    pub fn recover(&mut self, path: &Path) {
+        match Project::load(path) {
+            Ok(project) => {
+                info!("Recover the session from {}", path.display());
+                self.replace_session(project, None);
+            }
+            Err(err) => error!("Recovering from {} failed: {}", path.display(), err),
+        }
+    }
+
+    fn replace_session(&mut self, project: Project, project_path: Option<PathBuf>) {
+        self.subrip_list_widget.borrow_mut().clear();
+        self.timeline.borrow_mut().clear_blocks();
+        {
+            let mut app_state = self.app_state.borrow_mut();
+            app_state.subrips.clear();
+            app_state.tracks = project.tracks;
+            if app_state.tracks.is_empty() {
+                app_state.tracks.push(Track::default());
+            }
+            app_state.active_track = match app_state.track(project.active_track) {
+                Some(track) => track.id,
+                None => app_state.tracks[0].id,
+            };
+            // Edits share the list, only its contents are replaced.
+            *app_state.speakers.borrow_mut() = project.speakers;
+            app_state.next_speaker_id = project.next_speaker_id;
+            app_state.next_subrip_id = project.next_subrip_id;
+            app_state.history.borrow_mut().clear();
+            app_state.project_path = project_path;
+        }
+
+        if let Some(media_path) = project.media_path.as_ref() {
+            if media_path.exists() {
+                self.monitor.borrow_mut().set_media_path(media_path);
+            } else {
+                warn!(
+                    "The media {} of the project is missing",
+                    media_path.display()
+                );
+            }
+        }
+
+        for subrip in project.subrips.into_iter() {
+            let subrip = Shared::new(subrip);
+            let is_loaded = subrip.borrow().is_loaded();
+
+            self.app_state.borrow_mut().subrips.push(subrip.clone());
+            self.subrip_list_widget.borrow_mut().add(subrip.clone());
+            if is_loaded {
+                self.timeline.borrow_mut().add_block_from_subrip(&subrip);
+            }
+        }
+
+        self.app_state.borrow_mut().assign_missing_ids();
+
+        // Loading the media resets the timeline, so restore the view at last.
+        self.timeline.borrow_mut().set_view(&project.timeline);
+    }
+
+    /// Save to the path the project was opened from or saved to, ask for one if there is none.
+    pub fn save(&mut self, _: &()) {
+        let project_path = self.app_state.borrow().project_path.clone();
+
+        if let Some(path) = project_path {
+            self.save_as(&path);
+        } else if let Some(path) = rfd::FileDialog::new()
+            .add_filter("project", &[PROJECT_EXTENSION])
+            .save_file()
+        {
+            self.save_as(&path.with_extension(PROJECT_EXTENSION));
+        }
+    }
+
+    #[allow(clippy::ptr_arg)]
+    pub fn save_as(&mut self, path: &PathBuf) {
+        let view = self.timeline.borrow().view();
+        let project = Project::new(&self.app_state.borrow(), view);
+
+        match project.save(path) {
+            Ok(_) => {
+                info!("Saved the project to {}", path.display());
+                self.app_state.borrow_mut().project_path = Some(path.clone());
+            }
+            Err(err) => error!("Saving the project to {} failed: {}", path.display(), err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timestamp;
+
+    #[test]
+    fn test_project_round_trip() {
+        let mut app_state = AppState {
+            file_path_opening: Some(PathBuf::from("/tmp/video.mp4")),
+            ..Default::default()
+        };
+        let mut subrip = Subrip::new(
+            "hello",
+            Timestamp::from_millis(-500),
+            chrono::Duration::milliseconds(1500),
+        );
+        subrip.delete();
+        app_state.active_track = app_state.add_track("English");
+        subrip.speaker = Some(app_state.add_speaker("Host"));
+        let subrip = Shared::new(subrip);
+        app_state.add_subrip(&subrip);
+        let subrip = subrip.borrow().clone();
+
+        let view = TimelineView {
+            granularity: 0.5,
+            duration_range: [1000, 9000],
+        };
+        let json_str = Project::new(&app_state, view).to_json().unwrap();
+        let project = Project::from_json(&json_str).unwrap();
+
+        assert_eq!(project.media_path, app_state.file_path_opening);
+        assert_eq!(project.subrips, vec![subrip]);
+        assert!(project.subrips[0].is_deleted());
+        assert_eq!(project.subrips[0].track, 1);
+        assert_eq!(project.tracks, app_state.tracks);
+        assert_eq!(project.active_track, 1);
+        assert_eq!(project.speakers, *app_state.speakers.borrow());
+        assert_eq!(project.next_speaker_id, 1);
+        assert_eq!(project.next_subrip_id, 2);
+        assert_eq!(project.timeline, view);
+    }
+
+    #[test]
+    fn test_project_version() {
+        assert!(Project::from_json(r#"{"subrips": []}"#).is_err());
+        assert!(Project::from_json(r#"{"version": 99}"#).is_err());
+        assert!(Project::from_json(r#"{"version": 1}"#).is_ok());
+    }
+
+    #[test]
+    fn test_project_migration() {
+        let json_str = r#"{"version": 1, "subrips": [{"content": "hello"}]}"#;
+        let project = Project::from_json(json_str).unwrap();
+
+        assert_eq!(project.version, PROJECT_VERSION);
+        assert_eq!(project.tracks, vec![Track::default()]);
+        assert_eq!(project.subrips[0].track, project.tracks[0].id);
+
+        // The header of the project goes to its tracks.
+        let json_str = r#"{"version": 2, "header": {"vtt": {"description": " - talk"}},
+            "tracks": [{"id": 0}, {"id": 1}]}"#;
+        let project = Project::from_json(json_str).unwrap();
+        assert!(project
+            .tracks
+            .iter()
+            .all(|i| i.header.vtt.description == " - talk"));
+
+        // Other ASS events are anchored to the dialogue they came before.
+        let json_str = r#"{"version": 3,
+            "subrips": [{"begin_time": 2000}, {"begin_time": 1000}],
+            "tracks": [{"id": 0, "header": {"ass": {
+                "event_format": ["Layer", "Start"],
+                "events": [{"Comment": "Comment: 0,0:00:00.50"}, "Dialogue", "Dialogue",
+                    {"Comment": "Comment: notes"}]}}}]}"#;
+        let project = Project::from_json(json_str).unwrap();
+        assert_eq!(
+            project.tracks[0].header.ass.comments,
+            vec![
+                AssComment {
+                    line: "Comment: 0,0:00:00.50".to_string(),
+                    time: Timestamp::from_millis(500),
+                    before: Some(Timestamp::from_millis(1000)),
+                },
+                AssComment {
+                    line: "Comment: notes".to_string(),
+                    time: Timestamp::from_millis(2000),
+                    before: None,
+                },
+            ]
+        );
+    }
+}
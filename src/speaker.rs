@@ -0,0 +1,52 @@
+use crate::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+/// Identifies a [`Speaker`], it never changes once the speaker is created.
+pub type SpeakerId = u32;
+
+/// New speakers take the colours in turn, they stand out on the dark timeline.
+pub const SPEAKER_COLORS: [[u8; 3]; 8] = [
+    [0x89, 0xB4, 0xFA],
+    [0xF3, 0x8B, 0xA8],
+    [0xA6, 0xE3, 0xA1],
+    [0xF9, 0xE2, 0xAF],
+    [0xCB, 0xA6, 0xF7],
+    [0x94, 0xE2, 0xD5],
+    [0xFA, 0xB3, 0x87],
+    [0xF5, 0xC2, 0xE7],
+];
+
+/// Someone speaking in the media, e.g. in an interview. A subrip may name its speaker.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Speaker {
+    pub id: SpeakerId,
+    pub name: String,
+    /// sRGB colour of the subrips of the speaker on the timeline
+    pub color: [u8; 3],
+}
+
+impl Default for Speaker {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            name: String::new(),
+            color: SPEAKER_COLORS[0],
+        }
+    }
+}
+
+impl Speaker {
+    pub fn new(id: SpeakerId, name: impl Into<String>) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            color: SPEAKER_COLORS[id as usize % SPEAKER_COLORS.len()],
+        }
+    }
+
+    pub fn color32(&self) -> egui::Color32 {
+        egui::Color32::from_rgb(self.color[0], self.color[1], self.color[2])
+    }
+}
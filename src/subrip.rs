@@ -1,34 +1,233 @@
 use crate::prelude::*;
+use crate::{SpeakerId, Timestamp, TrackId};
 
-use chrono::{Duration, NaiveTime};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Eq, Default, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Default, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum SubripFormat {
     #[default]
     SRT,
+    VTT,
+    ASS,
 }
 
-#[derive(Debug, Clone, Default)]
+impl SubripFormat {
+    pub const ALL: [SubripFormat; 3] = [SubripFormat::SRT, SubripFormat::VTT, SubripFormat::ASS];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SubripFormat::SRT => "SRT",
+            SubripFormat::VTT => "VTT",
+            SubripFormat::ASS => "ASS",
+        }
+    }
+
+    /// File extensions of the format, the first one is preferred.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            SubripFormat::SRT => &["srt"],
+            SubripFormat::VTT => &["vtt"],
+            SubripFormat::ASS => &["ass", "ssa"],
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.extensions()[0]
+    }
+}
+
+/// Data of a subtitle file which doesn't belong to any single cue.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubripHeader {
+    pub vtt: VttHeader,
+    pub ass: AssHeader,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VttHeader {
+    /// Everything following the `WEBVTT` signature up to the first blank line.
+    pub description: String,
+    /// Bodies of the `STYLE` blocks.
+    pub styles: Vec<String>,
+    /// Bodies of the `REGION` blocks.
+    pub regions: Vec<String>,
+    /// Raw `NOTE` blocks behind the last cue.
+    pub trailing_notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssHeader {
+    /// Lines of `[Script Info]`, comments included.
+    pub script_info: Vec<String>,
+    /// Heading of the styles section, `[V4+ Styles]` or `[V4 Styles]`.
+    pub style_section: String,
+    /// Column names of the `Format:` line in the styles section.
+    pub style_format: Vec<String>,
+    /// `Style:` lines split by column.
+    pub styles: Vec<Vec<String>>,
+    /// Column names of the `Format:` line in `[Events]`, SSA has `Marked` instead of `Layer`.
+    pub event_format: Vec<String>,
+    /// Lines of `[Events]` other than dialogues, the subrips hold the dialogues themselves.
+    pub comments: Vec<AssComment>,
+    /// Unknown sections (e.g. `[Fonts]`), kept verbatim with their heading.
+    pub extra_sections: Vec<String>,
+}
+
+/// An event of `[Events]` other than a dialogue (e.g. `Comment:`), kept verbatim.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssComment {
+    pub line: String,
+    /// Start of the event, or of the dialogue before it if it has none. Events whose
+    /// dialogue is gone are written in the order of this time.
+    pub time: Timestamp,
+    /// Start of the dialogue the event came before, `None` after the last dialogue
+    pub before: Option<Timestamp>,
+}
+
+const ASS_EVENT_FORMAT: &str =
+    "Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text";
+const ASS_STYLE_FORMAT: &str = "Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, \
+    OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+    BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding";
+const ASS_DEFAULT_STYLE: &str = "Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,\
+    0,0,0,0,100,100,0,0,1,2,2,2,10,10,10,1";
+
+impl Default for AssHeader {
+    fn default() -> Self {
+        Self {
+            script_info: vec![
+                "; Script generated by mksubrip".to_string(),
+                "ScriptType: v4.00+".to_string(),
+                "WrapStyle: 0".to_string(),
+                "ScaledBorderAndShadow: yes".to_string(),
+            ],
+            style_section: "[V4+ Styles]".to_string(),
+            style_format: ASS_STYLE_FORMAT
+                .split(',')
+                .map(|i| i.trim().to_string())
+                .collect(),
+            styles: vec![ASS_DEFAULT_STYLE
+                .split(',')
+                .map(|i| i.trim().to_string())
+                .collect()],
+            event_format: ASS_EVENT_FORMAT
+                .split(',')
+                .map(|i| i.trim().to_string())
+                .collect(),
+            comments: vec![],
+            extra_sections: vec![],
+        }
+    }
+}
+
+/// Presentation attributes of a cue (ASS)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CueStyle {
+    /// Name of a style declared in the styles section
+    pub name: String,
+    pub layer: i32,
+    pub margin_l: i32,
+    pub margin_r: i32,
+    pub margin_v: i32,
+    /// Speaker of the cue, the `Name` column of an event
+    pub actor: String,
+    pub effect: String,
+}
+
+impl Default for CueStyle {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_string(),
+            layer: 0,
+            margin_l: 0,
+            margin_r: 0,
+            margin_v: 0,
+            actor: String::new(),
+            effect: String::new(),
+        }
+    }
+}
+
+/// WebVTT cue settings, kept as written so that they survive a round-trip.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CueSettings {
+    pub vertical: Option<String>,
+    pub line: Option<String>,
+    pub position: Option<String>,
+    pub size: Option<String>,
+    pub align: Option<String>,
+    pub region: Option<String>,
+}
+
+/// Identifies a subrip within a session, `0` until it's added to [`crate::app::AppState`].
+pub type SubripId = u64;
+
+/// Marks a subrip as the translation of another one, in another track.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Translation {
+    /// [`Subrip::id`] of the source subrip
+    pub source: SubripId,
+    /// The source text the translation was made from, it's stale once the source text changes
+    pub source_text: String,
+}
+
+/// The timing of a word (or syllable) of [`Subrip::content`], e.g. from speech recognition.
+///
+/// The times are relative to [`Subrip::begin_time`], so the words move along with the subrip.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WordTiming {
+    pub text: String,
+    pub begin: Timestamp,
+    pub end: Timestamp,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SubripState {
     pub is_loaded: bool,
     pub is_deleted: bool,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Subrip {
     pub state: SubripState,
 
+    pub id: SubripId,
     pub format: SubripFormat,
+    /// The [`crate::Track`] the subrip belongs to
+    pub track: TrackId,
     pub index: u32,
-    pub begin_time: NaiveTime,
-    pub end_time: NaiveTime,
+    pub begin_time: Timestamp,
+    pub end_time: Timestamp,
     pub content: String,
+
+    /// Cue identifier (WebVTT)
+    pub identifier: String,
+    pub settings: CueSettings,
+    /// Raw `NOTE` blocks in front of the cue (WebVTT)
+    pub notes: Vec<String>,
+    pub style: CueStyle,
+    pub translation: Option<Translation>,
+    /// Timed words of the content, empty if unknown
+    pub words: Vec<WordTiming>,
+    /// The [`crate::Speaker`] of the subrip
+    pub speaker: Option<SpeakerId>,
 }
 
 impl Subrip {
     pub fn new(
         content: impl Into<String>,
-        begin_time: impl Into<NaiveTime>,
+        begin_time: impl Into<Timestamp>,
         duration: Duration,
     ) -> Self {
         let begin_time = begin_time.into();
@@ -39,13 +238,14 @@ impl Subrip {
             begin_time,
             end_time: begin_time + duration,
             content: content.into(),
+            ..Default::default()
         }
     }
 
     pub fn from_vec_str(str: [&str; 4]) -> Result<Self> {
         let index = str[0].parse::<u32>()?;
-        let start = utils::naive_time_from_str(str[1])?;
-        let end = utils::naive_time_from_str(str[2])?;
+        let start = str[1].parse::<Timestamp>()?;
+        let end = str[2].parse::<Timestamp>()?;
         let text = str[3];
 
         Ok(Self {
@@ -54,7 +254,7 @@ impl Subrip {
             begin_time: start,
             end_time: end,
             content: text.to_string(),
-            state: SubripState::default(),
+            ..Default::default()
         })
     }
 
@@ -66,12 +266,12 @@ impl Subrip {
         self.format = format;
     }
 
-    pub fn set_begin_time(&mut self, naive_time: &NaiveTime) {
-        self.begin_time = *naive_time;
+    pub fn set_begin_time(&mut self, timestamp: &Timestamp) {
+        self.begin_time = *timestamp;
     }
 
-    pub fn set_end_time(&mut self, naive_time: &NaiveTime) {
-        self.end_time = *naive_time;
+    pub fn set_end_time(&mut self, timestamp: &Timestamp) {
+        self.end_time = *timestamp;
     }
 
     pub fn set_content(&mut self, content: impl Into<String>) {
@@ -86,11 +286,11 @@ impl Subrip {
         self.format
     }
 
-    pub fn get_begin_time(&self) -> NaiveTime {
+    pub fn get_begin_time(&self) -> Timestamp {
         self.begin_time
     }
 
-    pub fn get_end_time(&self) -> NaiveTime {
+    pub fn get_end_time(&self) -> Timestamp {
         self.end_time
     }
 
@@ -106,12 +306,14 @@ impl Subrip {
         self.end_time - self.begin_time
     }
 
+    /// Shift `begin_time` by `delta` seconds, rounded to the nearest millisecond.
     pub fn add_begin_delta(&mut self, delta: f32) {
-        self.begin_time += chrono::TimeDelta::seconds(delta as i64);
+        self.begin_time += Duration::milliseconds((delta * 1000.0).round() as i64);
     }
 
+    /// Shift `end_time` by `delta` seconds, rounded to the nearest millisecond.
     pub fn add_end_delta(&mut self, delta: f32) {
-        self.end_time += chrono::TimeDelta::seconds(delta as i64);
+        self.end_time += Duration::milliseconds((delta * 1000.0).round() as i64);
     }
 
     pub fn is_loaded(&self) -> bool {
@@ -126,6 +328,10 @@ impl Subrip {
         self.state.is_deleted = true;
     }
 
+    pub fn restore(&mut self) {
+        self.state.is_deleted = false;
+    }
+
     pub fn is_deleted(&self) -> bool {
         self.state.is_deleted
     }
@@ -133,6 +339,157 @@ impl Subrip {
     pub fn toggle_loading(&mut self) {
         self.state.is_loaded = !self.state.is_loaded;
     }
+
+    /// The words of each line of the content paired with their timing, `None` if the words
+    /// don't match the content any more, e.g. it's been edited since they were transcribed.
+    ///
+    /// Only letters and digits are compared, recognizers often leave the punctuation out.
+    pub fn timed_lines(&self) -> Option<Vec<Vec<(&str, &WordTiming)>>> {
+        if self.words.is_empty() {
+            return None;
+        }
+
+        let mut words = self.words.iter();
+        let mut lines = vec![];
+        for line in self.content.lines() {
+            let mut timed_line = vec![];
+            for token in line.split_whitespace() {
+                let word = words.next()?;
+                if word_key(token) != word_key(&word.text) {
+                    return None;
+                }
+                timed_line.push((token, word));
+            }
+            if !timed_line.is_empty() {
+                lines.push(timed_line);
+            }
+        }
+
+        words.next().is_none().then_some(lines)
+    }
+
+    /// Put `prefix` in front of the content, e.g. a speaker label. The timed words get an
+    /// untimed word for each word of it, so they still match.
+    pub fn prefix_content(&mut self, prefix: &str) {
+        if self.timed_lines().is_some() {
+            let words = prefix.split_whitespace().map(|text| WordTiming {
+                text: text.to_string(),
+                ..Default::default()
+            });
+            self.words.splice(0..0, words);
+        }
+        self.content.insert_str(0, prefix);
+    }
+
+    /// Split into two subrips at the word boundary which suits best, `None` for a single word.
+    ///
+    /// Boundaries around the middle of the text are preferred, the end of a sentence over that
+    /// of a clause, then the longest pause between the timed words. Without timed words, the
+    /// time is split in proportion to the characters on either side.
+    pub fn split(&self) -> Option<(Subrip, Subrip)> {
+        let tokens = self.content.split_whitespace().collect::<Vec<_>>();
+        if tokens.len() < 2 {
+            return None;
+        }
+        let timed_words = self
+            .timed_lines()
+            .map(|lines| lines.into_iter().flatten().map(|i| i.1).collect::<Vec<_>>());
+
+        // Byte offsets of the words, like `split_whitespace` finds them.
+        let mut previous = ' ';
+        let offsets = self
+            .content
+            .char_indices()
+            .filter_map(|(i, c)| {
+                let begins_word = !c.is_whitespace() && previous.is_whitespace();
+                previous = c;
+
+                begins_word.then_some(i)
+            })
+            .collect::<Vec<_>>();
+
+        let total_chars = tokens.iter().map(|i| i.chars().count()).sum::<usize>();
+        let chars_before = |k: usize| tokens[..k].iter().map(|i| i.chars().count()).sum::<usize>();
+        let is_middle = |k: usize| (total_chars..=total_chars * 3).contains(&(chars_before(k) * 4));
+        let has_middle = (1..tokens.len()).any(is_middle);
+        let pause_ms = |k: usize| match timed_words.as_ref() {
+            Some(words) => (words[k].begin - words[k - 1].end).num_milliseconds(),
+            None => 0,
+        };
+
+        let k = (1..tokens.len())
+            .filter(|k| !has_middle || is_middle(*k))
+            .max_by_key(|k| {
+                let distance = (chars_before(*k) * 2).abs_diff(total_chars);
+                (
+                    punctuation_rank(tokens[k - 1]),
+                    pause_ms(*k),
+                    std::cmp::Reverse(distance),
+                )
+            })?;
+
+        let (first_end, second_begin) = match timed_words.as_ref() {
+            Some(words) => (
+                self.begin_time + (words[k - 1].end - Timestamp::ZERO),
+                self.begin_time + (words[k].begin - Timestamp::ZERO),
+            ),
+            None => {
+                let ms = self.get_duration().num_milliseconds() * chars_before(k) as i64
+                    / total_chars as i64;
+                let time = self.begin_time + Duration::milliseconds(ms);
+                (time, time)
+            }
+        };
+        // The first word of the second part, the line breaks are kept.
+        let offset = offsets[k];
+
+        let mut first = self.clone();
+        first.content = self.content[..offset].trim_end().to_string();
+        first.end_time = first_end.max(first.begin_time);
+        first
+            .words
+            .truncate(if timed_words.is_some() { k } else { 0 });
+
+        let mut second = self.clone();
+        second.content = self.content[offset..].to_string();
+        second.begin_time = second_begin.min(second.end_time);
+        second.identifier.clear();
+        second.notes.clear();
+        second.translation = None;
+        second.words = match timed_words {
+            Some(_) => {
+                let shift = second.begin_time - self.begin_time;
+                self.words[k..]
+                    .iter()
+                    .map(|word| WordTiming {
+                        text: word.text.clone(),
+                        begin: word.begin - shift,
+                        end: word.end - shift,
+                    })
+                    .collect()
+            }
+            None => vec![],
+        };
+
+        Some((first, second))
+    }
+}
+
+/// The letters and digits of a word in lower case, for comparing words.
+fn word_key(word: &str) -> String {
+    word.chars()
+        .filter(|i| i.is_alphanumeric())
+        .flat_map(|i| i.to_lowercase())
+        .collect()
+}
+
+/// How well a cue can be split after `word`: 2 at the end of a sentence, 1 at that of a clause.
+fn punctuation_rank(word: &str) -> u8 {
+    match word.chars().last() {
+        Some('.' | '!' | '?' | '…' | '。' | '！' | '？') => 2,
+        Some(',' | ';' | ':' | '，' | '、') => 1,
+        _ => 0,
+    }
 }
 
 impl PartialEq for Subrip {
@@ -142,7 +499,13 @@ impl PartialEq for Subrip {
             && self.begin_time == other.begin_time
             && self.end_time == other.end_time
             && self.format == other.format
+            && self.identifier == other.identifier
+            && self.settings == other.settings
+            && self.notes == other.notes
+            && self.style == other.style
             && self.state == other.state
+            && self.words == other.words
+            && self.speaker == other.speaker
     }
 }
 
@@ -155,21 +518,132 @@ impl PartialEq for SubripState {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::{Duration, NaiveTime};
+    use chrono::Duration;
 
     fn test_subrip() {
         let subrip = Subrip::new(
             "hello world",
-            NaiveTime::parse_from_str("00:00.001", "%M:%S%.3f").unwrap(),
+            "00:00.001".parse::<Timestamp>().unwrap(),
             Duration::seconds(5),
         );
 
         let other = Subrip::new(
             "hello world",
-            NaiveTime::parse_from_str("00:00.001", "%M:%S%.3f").unwrap(),
+            "00:00.001".parse::<Timestamp>().unwrap(),
             Duration::seconds(5),
         );
 
         assert_eq!(subrip, other);
     }
+
+    #[test]
+    fn test_add_delta_in_millis() {
+        let mut subrip = Subrip::new(
+            "hello world",
+            Timestamp::from_hms_milli(0, 0, 1, 0),
+            Duration::seconds(5),
+        );
+
+        subrip.add_begin_delta(0.0416);
+        subrip.add_end_delta(-1.5);
+
+        assert_eq!(
+            subrip.get_begin_time(),
+            Timestamp::from_hms_milli(0, 0, 1, 42)
+        );
+        assert_eq!(
+            subrip.get_end_time(),
+            Timestamp::from_hms_milli(0, 0, 4, 500)
+        );
+    }
+
+    fn word(text: &str, begin_ms: i64, end_ms: i64) -> WordTiming {
+        WordTiming {
+            text: text.to_string(),
+            begin: Timestamp::from_millis(begin_ms),
+            end: Timestamp::from_millis(end_ms),
+        }
+    }
+
+    #[test]
+    fn test_timed_lines() {
+        let mut subrip = Subrip::new(
+            "Hello, world\nagain",
+            Timestamp::from_millis(1000),
+            Duration::seconds(3),
+        );
+        assert!(subrip.timed_lines().is_none());
+
+        subrip.words = vec![
+            word(" hello", 0, 500),
+            word(" world", 600, 1200),
+            word(" again", 2000, 2800),
+        ];
+        let lines = subrip.timed_lines().unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0][0].0, "Hello,");
+        assert_eq!(lines[1][0].1.begin, Timestamp::from_millis(2000));
+
+        subrip.prefix_content("- ");
+        let lines = subrip.timed_lines().unwrap();
+        assert_eq!(lines[0][0].0, "-");
+        assert_eq!(lines[0][1].0, "Hello,");
+
+        subrip.set_content("Hello, world");
+        assert!(subrip.timed_lines().is_none());
+    }
+
+    #[test]
+    fn test_split_at_pause() {
+        let mut subrip = Subrip::new(
+            "I came, I saw I conquered",
+            Timestamp::from_millis(10_000),
+            Duration::seconds(4),
+        );
+        subrip.words = vec![
+            word("I", 0, 200),
+            word("came", 200, 600),
+            word("I", 700, 800),
+            word("saw", 800, 1200),
+            word("I", 2000, 2100),
+            word("conquered", 2100, 3800),
+        ];
+
+        // The clause ends after "came,", which wins over the longer pause after "saw".
+        let (first, second) = subrip.split().unwrap();
+        assert_eq!(first.content, "I came,");
+        assert_eq!(first.end_time, Timestamp::from_millis(10_600));
+        assert_eq!(first.words.len(), 2);
+        assert_eq!(second.content, "I saw I conquered");
+        assert_eq!(second.begin_time, Timestamp::from_millis(10_700));
+        assert_eq!(second.end_time, Timestamp::from_millis(14_000));
+        assert_eq!(second.words[0].begin, Timestamp::ZERO);
+        assert_eq!(second.words[3].end, Timestamp::from_millis(3100));
+
+        // Without punctuation, the longest pause wins.
+        subrip.set_content("I came I saw I conquered");
+        let (first, second) = subrip.split().unwrap();
+        assert_eq!(first.content, "I came I saw");
+        assert_eq!(second.begin_time, Timestamp::from_millis(12_000));
+    }
+
+    #[test]
+    fn test_split_without_words() {
+        let subrip = Subrip::new("abcd efgh", Timestamp::ZERO, Duration::seconds(2));
+
+        let (first, second) = subrip.split().unwrap();
+        assert_eq!(first.content, "abcd");
+        assert_eq!(second.content, "efgh");
+
+        let subrip = Subrip::new(" äöü\u{3000}ß\nçé", Timestamp::ZERO, Duration::seconds(2));
+        let (first, second) = subrip.split().unwrap();
+        assert_eq!(first.content, " äöü");
+        assert_eq!(second.content, "ß\nçé");
+        assert_eq!(first.end_time, Timestamp::from_millis(1000));
+        assert_eq!(second.begin_time, Timestamp::from_millis(1000));
+
+        assert!(Subrip::new("word", Timestamp::ZERO, Duration::seconds(1))
+            .split()
+            .is_none());
+    }
 }
@@ -0,0 +1,325 @@
+use crate::prelude::*;
+
+use chrono::Duration;
+use nom::character::complete::{char, digit1, one_of};
+use nom::combinator::{map_opt, map_res, opt, verify};
+use nom::sequence::{preceded, tuple};
+use nom::IResult;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+/// A signed offset on the media timeline in milliseconds.
+///
+/// Unlike [`chrono::NaiveTime`], it doesn't wrap at 24 hours and it can be negative,
+/// e.g. while shifting cues in front of the beginning of the media.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub const ZERO: Timestamp = Timestamp(0);
+    /// The largest timestamp taken from text, far beyond any media but small enough that
+    /// sums and differences of such timestamps can't overflow.
+    pub const MAX: Timestamp = Timestamp::from_hms_milli(999_999, 59, 59, 999);
+
+    pub const fn from_millis(ms: i64) -> Self {
+        Self(ms)
+    }
+
+    /// `None` beyond [`Timestamp::MAX`] either way.
+    pub fn checked_from_millis(ms: i64) -> Option<Self> {
+        (ms.checked_abs()? <= Self::MAX.0).then_some(Self(ms))
+    }
+
+    /// Build a timestamp from seconds, rounded to the nearest millisecond.
+    pub fn from_secs_f32(secs: f32) -> Self {
+        Self((secs as f64 * 1000.0).round() as i64)
+    }
+
+    /// Like [`Timestamp::from_secs_f32`], `None` if `secs` isn't finite or is beyond
+    /// [`Timestamp::MAX`] either way.
+    pub fn checked_from_secs_f64(secs: f64) -> Option<Self> {
+        let ms = (secs * 1000.0).round();
+
+        (ms.abs() <= Self::MAX.0 as f64).then_some(Self(ms as i64))
+    }
+
+    /// Panics if minutes or seconds aren't below 60, use [`Timestamp::checked_from_hms_milli`]
+    /// for fields which come from input.
+    pub const fn from_hms_milli(h: i64, m: i64, s: i64, ms: i64) -> Self {
+        assert!(
+            m >= 0 && m < 60 && s >= 0 && s < 60,
+            "Invalid minutes or seconds"
+        );
+
+        Self(((h * 60 + m) * 60 + s) * 1000 + ms)
+    }
+
+    /// `None` if minutes or seconds aren't below 60, milliseconds aren't below 1000, or the
+    /// timestamp is beyond [`Timestamp::MAX`].
+    pub fn checked_from_hms_milli(h: i64, m: i64, s: i64, ms: i64) -> Option<Self> {
+        if h < 0 || !(0..60).contains(&m) || !(0..60).contains(&s) || !(0..1000).contains(&ms) {
+            return None;
+        }
+
+        h.checked_mul(60)?
+            .checked_add(m)?
+            .checked_mul(60)?
+            .checked_add(s)?
+            .checked_mul(1000)?
+            .checked_add(ms)
+            .and_then(Self::checked_from_millis)
+    }
+
+    pub const fn as_millis(&self) -> i64 {
+        self.0
+    }
+
+    pub fn as_secs_f32(&self) -> f32 {
+        self.0 as f32 / 1000.0
+    }
+
+    pub const fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    /// Hours, minutes, seconds and milliseconds of the absolute value.
+    fn hms_milli(&self) -> (i64, i64, i64, i64) {
+        let ms = self.0.abs();
+
+        (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+    }
+
+    /// `HH:MM:SS,mmm`. Negative timestamps are clamped to zero since SRT can't express them.
+    pub fn format_srt(&self) -> String {
+        let (h, m, s, ms) = (*self).max(Self::ZERO).hms_milli();
+
+        format!("{:02}:{:02}:{:02},{:03}", h, m, s, ms)
+    }
+
+    /// `HH:MM:SS.mmm`. Negative timestamps are clamped to zero since WebVTT can't express them.
+    pub fn format_vtt(&self) -> String {
+        let (h, m, s, ms) = (*self).max(Self::ZERO).hms_milli();
+
+        format!("{:02}:{:02}:{:02}.{:03}", h, m, s, ms)
+    }
+
+    /// `H:MM:SS.cc`. Negative timestamps are clamped to zero since ASS can't express them.
+    pub fn format_ass(&self) -> String {
+        let (h, m, s, ms) = (*self).max(Self::ZERO).hms_milli();
+
+        format!("{}:{:02}:{:02}.{:02}", h, m, s, ms / 10)
+    }
+}
+
+/// `[-]HH:MM:SS.mmm`
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (h, m, s, ms) = self.hms_milli();
+        let sign = if self.is_negative() { "-" } else { "" };
+
+        write!(f, "{}{:02}:{:02}:{:02}.{:03}", sign, h, m, s, ms)
+    }
+}
+
+/// Accept `[-][[H:]MM:]SS[.mmm]`, a comma may be used instead of the dot. Minutes and
+/// seconds behind a colon must be below 60, a lone number of seconds may be larger.
+impl FromStr for Timestamp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (rest, (sign, a, b, c, ms)) = tuple((
+            opt(char('-')),
+            number,
+            opt(preceded(char(':'), number)),
+            opt(preceded(char(':'), number)),
+            opt(preceded(one_of(",."), millis)),
+        ))(s.trim())
+        .map_err(|_: nom::Err<nom::error::Error<&str>>| anyhow!("Invalid timestamp: {}", s))?;
+
+        if !rest.is_empty() {
+            return Err(anyhow!("Invalid timestamp: {}", s));
+        }
+
+        let ms = ms.unwrap_or_default();
+        let timestamp = match (b, c) {
+            (Some(b), Some(c)) => Self::checked_from_hms_milli(a, b, c, ms),
+            (Some(b), None) => Self::checked_from_hms_milli(0, a, b, ms),
+            _ => a
+                .checked_mul(1000)
+                .and_then(|i| i.checked_add(ms))
+                .and_then(Self::checked_from_millis),
+        }
+        .ok_or_else(|| anyhow!("Timestamp out of range: {}", s))?;
+
+        Ok(if sign.is_some() {
+            -timestamp
+        } else {
+            timestamp
+        })
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Self(self.0 + rhs.num_milliseconds())
+    }
+}
+
+impl AddAssign<Duration> for Timestamp {
+    fn add_assign(&mut self, rhs: Duration) {
+        self.0 += rhs.num_milliseconds();
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        Self(self.0 - rhs.num_milliseconds())
+    }
+}
+
+impl SubAssign<Duration> for Timestamp {
+    fn sub_assign(&mut self, rhs: Duration) {
+        self.0 -= rhs.num_milliseconds();
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Duration;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Duration::milliseconds(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Timestamp {
+    type Output = Timestamp;
+
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+
+fn number(input: &str) -> IResult<&str, i64> {
+    map_res(digit1, str::parse::<i64>)(input)
+}
+
+/// The fractional part of a timestamp. "5" means 500 milliseconds.
+fn millis(input: &str) -> IResult<&str, i64> {
+    map_res(
+        verify(digit1, |s: &str| s.len() <= 3),
+        |s: &str| -> Result<i64> { Ok(s.parse::<i64>()? * 10i64.pow(3 - s.len() as u32)) },
+    )(input)
+}
+
+/// `HH:MM:SS,mmm` (a dot is accepted instead of the comma)
+pub fn srt_timestamp(input: &str) -> IResult<&str, Timestamp> {
+    map_opt(
+        tuple((
+            number,
+            char(':'),
+            number,
+            char(':'),
+            number,
+            one_of(",."),
+            millis,
+        )),
+        |(h, _, m, _, s, _, ms)| Timestamp::checked_from_hms_milli(h, m, s, ms),
+    )(input)
+}
+
+/// `mm:ss.ttt` or `hh:mm:ss.ttt`
+pub fn vtt_timestamp(input: &str) -> IResult<&str, Timestamp> {
+    map_opt(
+        tuple((
+            number,
+            char(':'),
+            number,
+            opt(preceded(char(':'), number)),
+            char('.'),
+            millis,
+        )),
+        |(a, _, b, c, _, ms)| match c {
+            Some(c) => Timestamp::checked_from_hms_milli(a, b, c, ms),
+            None => Timestamp::checked_from_hms_milli(0, a, b, ms),
+        },
+    )(input)
+}
+
+/// `H:MM:SS.cc`
+pub fn ass_timestamp(input: &str) -> IResult<&str, Timestamp> {
+    map_opt(
+        tuple((
+            number,
+            char(':'),
+            number,
+            char(':'),
+            number,
+            char('.'),
+            millis,
+        )),
+        |(h, _, m, _, s, _, ms)| Timestamp::checked_from_hms_milli(h, m, s, ms),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_from_str() {
+        assert_eq!(
+            "00:05.001".parse::<Timestamp>().unwrap(),
+            Timestamp::from_millis(5001)
+        );
+        assert_eq!(
+            "-00:00:01.5".parse::<Timestamp>().unwrap(),
+            Timestamp::from_millis(-1500)
+        );
+        assert_eq!(
+            "25:00:00,000".parse::<Timestamp>().unwrap(),
+            Timestamp::from_hms_milli(25, 0, 0, 0)
+        );
+        assert_eq!(
+            "90.5".parse::<Timestamp>().unwrap(),
+            Timestamp::from_millis(90_500)
+        );
+        assert!("00:0a:00".parse::<Timestamp>().is_err());
+        assert!("00:60:00".parse::<Timestamp>().is_err());
+        assert!("00:00:75".parse::<Timestamp>().is_err());
+        assert!("99999999999999999:00:00".parse::<Timestamp>().is_err());
+        assert!("9999999999999999999".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn test_timestamp_parsers() {
+        assert_eq!(
+            srt_timestamp("01:02:03,004").unwrap().1,
+            Timestamp::from_hms_milli(1, 2, 3, 4)
+        );
+        assert!(srt_timestamp("01:61:03,004").is_err());
+        assert!(vtt_timestamp("60:00.000").is_err());
+        assert!(ass_timestamp("0:00:60.00").is_err());
+        assert!(ass_timestamp("9223372036854775807:00:00.00").is_err());
+    }
+
+    #[test]
+    fn test_timestamp_format() {
+        let timestamp = Timestamp::from_hms_milli(26, 3, 4, 56);
+
+        assert_eq!(timestamp.to_string(), "26:03:04.056");
+        assert_eq!((-timestamp).to_string(), "-26:03:04.056");
+        assert_eq!(timestamp.format_srt(), "26:03:04,056");
+        assert_eq!(timestamp.format_vtt(), "26:03:04.056");
+        assert_eq!(timestamp.format_ass(), "26:03:04.05");
+        assert_eq!((-timestamp).format_srt(), "00:00:00,000");
+    }
+}
@@ -0,0 +1,81 @@
+use crate::subrip::{SubripFormat, SubripHeader};
+
+use serde::{Deserialize, Serialize};
+
+/// Identifies a [`Track`], it never changes once the track is created.
+pub type TrackId = u32;
+
+/// A subtitle track of the media, e.g. one language. Each subrip belongs to one track.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Track {
+    pub id: TrackId,
+    pub name: String,
+    /// BCP 47 language tag, e.g. `en` or `pt-BR`
+    pub language: String,
+    /// The format the track is exported in by default
+    pub format: SubripFormat,
+    /// Chosen by players when the viewer doesn't pick a track
+    pub is_default: bool,
+    /// Shown even with subtitles turned off, e.g. for foreign dialogue
+    pub is_forced: bool,
+    /// Of the file imported into the track last, written when the track is exported
+    pub header: SubripHeader,
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            name: "Default".to_string(),
+            language: String::new(),
+            format: SubripFormat::SRT,
+            is_default: true,
+            is_forced: false,
+            header: SubripHeader::default(),
+        }
+    }
+}
+
+impl Track {
+    pub fn new(id: TrackId, name: impl Into<String>) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            is_default: false,
+            ..Default::default()
+        }
+    }
+
+    /// Inserted between the stem and the extension of exported files, e.g. `.en.forced`
+    /// in `talk.en.forced.srt`, which is how players pair subtitles with the media.
+    pub fn file_suffix(&self) -> String {
+        let mut suffix = String::new();
+        if !self.language.trim().is_empty() {
+            suffix.push('.');
+            suffix.push_str(self.language.trim());
+        }
+        if self.is_forced {
+            suffix.push_str(".forced");
+        }
+
+        suffix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_suffix() {
+        let mut track = Track::new(1, "Portuguese");
+        assert_eq!(track.file_suffix(), "");
+
+        track.language = "pt-BR".to_string();
+        assert_eq!(track.file_suffix(), ".pt-BR");
+
+        track.is_forced = true;
+        assert_eq!(track.file_suffix(), ".pt-BR.forced");
+    }
+}
@@ -0,0 +1,271 @@
+use crate::ai::{
+    AIModelScale, AIModelType, AiSettings, TranslationBackend, API_KEY_VAR, TRANSLATION_API_KEY_VAR,
+};
+use crate::app::AppState;
+use crate::prelude::*;
+use crate::ui::Drawable;
+
+/// Edit where the transcription server is and which model it runs, saved on confirming.
+pub struct AiSettingsWindow {
+    app_state: Shared<AppState>,
+    visible: bool,
+    /// The settings being edited, they only take effect when saved
+    draft: AiSettings,
+}
+
+impl AiSettingsWindow {
+    pub fn new(app_state: Shared<AppState>) -> Self {
+        Self {
+            app_state,
+            visible: false,
+            draft: AiSettings::default(),
+        }
+    }
+
+    pub fn toggle_visible(&mut self, _: &()) {
+        self.visible = !self.visible;
+        if self.visible {
+            self.draft = self.app_state.borrow().ai_settings.clone();
+        }
+    }
+
+    fn save(&mut self) {
+        self.app_state.borrow_mut().ai_settings = self.draft.clone();
+
+        let path = AiSettings::path();
+        match self.draft.save(&path) {
+            Ok(()) => info!("Saved the AI settings to {}", path.display()),
+            Err(err) => error!(
+                "Saving the AI settings to {} failed: {}",
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    fn draw_scale(&mut self, eui: &mut egui::Ui) {
+        let scale = &mut self.draft.scale;
+        let is_specified = matches!(scale, AIModelScale::Specified(_));
+
+        eui.horizontal(|eui| {
+            egui::ComboBox::from_id_source("ai_model_scale")
+                .selected_text(match scale {
+                    AIModelScale::Tiny => "Tiny",
+                    AIModelScale::Base => "Base",
+                    AIModelScale::Large => "Large",
+                    AIModelScale::Specified(_) => "Custom",
+                })
+                .show_ui(eui, |eui| {
+                    eui.selectable_value(scale, AIModelScale::Tiny, "Tiny");
+                    eui.selectable_value(scale, AIModelScale::Base, "Base");
+                    eui.selectable_value(scale, AIModelScale::Large, "Large");
+                    if eui.selectable_label(is_specified, "Custom").clicked() && !is_specified {
+                        *scale = AIModelScale::Specified(String::new());
+                    }
+                });
+
+            if let AIModelScale::Specified(name) = scale {
+                eui.add(
+                    egui::TextEdit::singleline(name)
+                        .hint_text("large-v3")
+                        .desired_width(120.0),
+                );
+            }
+        });
+    }
+
+    fn draw_model_path(&mut self, eui: &mut egui::Ui) {
+        eui.horizontal(|eui| {
+            eui.add(
+                egui::TextEdit::singleline(&mut self.draft.model_path)
+                    .hint_text("ggml-base.bin")
+                    .desired_width(200.0),
+            );
+            if eui.button("…").clicked() {
+                if let Some(path_buf) = rfd::FileDialog::new()
+                    .add_filter("whisper.cpp model", &["bin"])
+                    .pick_file()
+                {
+                    self.draft.model_path = path_buf.display().to_string();
+                }
+            }
+        });
+    }
+
+    fn draw_translation(&mut self, eui: &mut egui::Ui) {
+        let settings = &mut self.draft.translation;
+
+        egui::Grid::new("ai_translation_settings_grid")
+            .num_columns(2)
+            .show(eui, |eui| {
+                eui.label("Backend");
+                egui::ComboBox::from_id_source("ai_translation_backend")
+                    .selected_text(settings.backend.name())
+                    .show_ui(eui, |eui| {
+                        for i in TranslationBackend::ALL {
+                            eui.selectable_value(&mut settings.backend, i, i.name());
+                        }
+                    });
+                eui.end_row();
+
+                eui.label("Base URL");
+                eui.text_edit_singleline(&mut settings.base_url);
+                eui.end_row();
+
+                eui.label("API Key");
+                eui.add(
+                    egui::TextEdit::singleline(&mut settings.api_key)
+                        .password(true)
+                        .hint_text(format!("${TRANSLATION_API_KEY_VAR}")),
+                );
+                eui.end_row();
+
+                if settings.backend == TranslationBackend::OpenaiChat {
+                    eui.label("Model");
+                    eui.add(
+                        egui::TextEdit::singleline(&mut settings.model).hint_text("gpt-4o-mini"),
+                    );
+                    eui.end_row();
+                }
+
+                eui.label("Batch Size");
+                eui.add(egui::DragValue::new(&mut settings.batch_size).clamp_range(1..=200));
+                eui.end_row();
+
+                eui.label("Context");
+                eui.add(
+                    egui::DragValue::new(&mut settings.context_size)
+                        .clamp_range(0..=20)
+                        .suffix(" cues"),
+                );
+                eui.end_row();
+
+                eui.label("Max Line Length");
+                eui.add(
+                    egui::DragValue::new(&mut settings.max_line_length)
+                        .clamp_range(10..=200)
+                        .suffix(" chars"),
+                );
+                eui.end_row();
+            });
+    }
+}
+
+impl Drawable for AiSettingsWindow {
+    fn draw(&mut self, ctx: &egui::Context, _eui: &mut egui::Ui) {
+        if !self.visible {
+            return;
+        }
+
+        let mut visible = self.visible;
+        let mut saved = false;
+
+        egui::Window::new("AI Settings")
+            .collapsible(false)
+            .open(&mut visible)
+            .show(ctx, |eui| {
+                egui::Grid::new("ai_settings_grid")
+                    .num_columns(2)
+                    .show(eui, |eui| {
+                        eui.label("Base URL");
+                        eui.text_edit_singleline(&mut self.draft.base_url);
+                        eui.end_row();
+
+                        eui.label("API Key Header");
+                        eui.add(
+                            egui::TextEdit::singleline(&mut self.draft.api_key_header)
+                                .hint_text("Authorization"),
+                        );
+                        eui.end_row();
+
+                        eui.label("API Key");
+                        eui.add(
+                            egui::TextEdit::singleline(&mut self.draft.api_key)
+                                .password(true)
+                                .hint_text(format!("${API_KEY_VAR}")),
+                        );
+                        eui.end_row();
+
+                        eui.label("Model");
+                        egui::ComboBox::from_id_source("ai_model_type")
+                            .selected_text(self.draft.model_type.name())
+                            .show_ui(eui, |eui| {
+                                for i in AIModelType::ALL {
+                                    eui.selectable_value(&mut self.draft.model_type, i, i.name());
+                                }
+                            });
+                        eui.end_row();
+
+                        if self.draft.model_type == AIModelType::LocalWhisper {
+                            eui.label("Model File");
+                            self.draw_model_path(eui);
+                        } else {
+                            eui.label("Scale");
+                            self.draw_scale(eui);
+                        }
+                        eui.end_row();
+
+                        eui.label("Chunk Length");
+                        eui.add(
+                            egui::DragValue::new(&mut self.draft.chunk_minutes)
+                                .clamp_range(1..=120)
+                                .suffix(" min"),
+                        );
+                        eui.end_row();
+
+                        if self.draft.model_type != AIModelType::LocalWhisper {
+                            eui.label("Parallel Requests");
+                            eui.add(
+                                egui::DragValue::new(&mut self.draft.parallel_requests)
+                                    .clamp_range(1..=8),
+                            );
+                            eui.end_row();
+                        }
+
+                        eui.label("Language");
+                        eui.add(
+                            egui::TextEdit::singleline(&mut self.draft.language)
+                                .hint_text("detect"),
+                        );
+                        eui.end_row();
+
+                        if self.draft.model_type != AIModelType::LocalWhisper {
+                            eui.label("Diarization");
+                            eui.checkbox(&mut self.draft.diarization, "Speakers")
+                                .on_hover_text(
+                                    "Add the speakers labelled by the server, \
+                                     chunks of long media label them apart",
+                                );
+                            eui.end_row();
+                        }
+                    });
+
+                eui.separator();
+                eui.strong("Translation");
+                self.draw_translation(eui);
+
+                eui.separator();
+                eui.weak(format!(
+                    "API keys are saved unencrypted in {}. Leave them empty to read ${} and ${} instead.",
+                    AiSettings::path().display(),
+                    API_KEY_VAR,
+                    TRANSLATION_API_KEY_VAR
+                ));
+
+                eui.horizontal(|eui| {
+                    if eui.button("Save").clicked() {
+                        saved = true;
+                    }
+                    if eui.button("Reset").clicked() {
+                        self.draft = AiSettings::default();
+                    }
+                });
+            });
+
+        if saved {
+            self.save();
+            visible = false;
+        }
+        self.visible = visible;
+    }
+}
@@ -4,6 +4,7 @@ use crate::ui::Drawable;
 pub struct ControlBar {
     pub sig_btn_play_clicked: Signal<()>,
     pub sig_btn_clear_clicked: Signal<()>,
+    pub sig_btn_overlay_clicked: Signal<()>,
 
     player: Option<Shared<media_player::Player>>,
 }
@@ -19,6 +20,7 @@ impl ControlBar {
         Self {
             sig_btn_play_clicked: Signal::new(),
             sig_btn_clear_clicked: Signal::new(),
+            sig_btn_overlay_clicked: Signal::new(),
             player: None,
         }
     }
@@ -62,6 +64,10 @@ impl Drawable for ControlBar {
             eui.label(label_text);
             eui.add_space(eui.available_width() * 0.8);
 
+            if eui.button("Aa").on_hover_text("Subtitle overlay").clicked() {
+                self.sig_btn_overlay_clicked.emit(&());
+            }
+
             if eui.button("Clear").clicked() {
                 self.sig_btn_clear_clicked.emit(&());
             }
@@ -1,3 +1,4 @@
+use crate::history::{EditCommand, EditHistory};
 use crate::prelude::*;
 use crate::ui::Drawable;
 use crate::Subrip;
@@ -8,6 +9,10 @@ pub struct EditSubripWindow {
 
     visible: bool,
     text: String,
+    /// Set when the subrip is to be split, see [`EditSubripWindow::take_split_request`]
+    split_requested: bool,
+
+    history: Shared<EditHistory>,
 }
 
 impl EditSubripWindow {
@@ -16,9 +21,15 @@ impl EditSubripWindow {
             subrip: None,
             visible: false,
             text: String::new(),
+            split_requested: false,
+            history: Shared::default(),
         }
     }
 
+    pub fn set_history(&mut self, history: Shared<EditHistory>) {
+        self.history = history;
+    }
+
     pub fn edit_subrip(&mut self, subrip: &Shared<Subrip>) {
         self.set_subrip(subrip);
         self.visible = true;
@@ -31,11 +42,29 @@ impl EditSubripWindow {
 
     pub fn submit(&mut self) {
         if let Some(subrip) = self.subrip.as_ref() {
-            subrip.borrow_mut().set_content(self.text.as_str());
+            let old = subrip.borrow().get_content();
+            if old != self.text {
+                let command = EditCommand::EditText {
+                    subrip: subrip.clone(),
+                    old,
+                    new: self.text.clone(),
+                };
+                command.redo();
+                self.history.borrow_mut().push(command);
+            }
         }
 
         self.visible = false;
     }
+
+    /// The subrip whose split was requested since the last call, the caller splits it.
+    pub fn take_split_request(&mut self) -> Option<Shared<Subrip>> {
+        if !std::mem::take(&mut self.split_requested) {
+            return None;
+        }
+
+        self.subrip.clone()
+    }
 }
 
 impl Drawable for EditSubripWindow {
@@ -45,9 +74,19 @@ impl Drawable for EditSubripWindow {
             window.show(ctx, |ui| {
                 ui.text_edit_multiline(&mut self.text);
 
-                if ui.button("Submit").clicked() {
-                    self.submit();
-                }
+                ui.horizontal(|ui| {
+                    if ui.button("Submit").clicked() {
+                        self.submit();
+                    }
+                    if ui
+                        .button("Split")
+                        .on_hover_text("Split in two at the best word boundary")
+                        .clicked()
+                    {
+                        self.submit();
+                        self.split_requested = true;
+                    }
+                });
             });
         }
     }
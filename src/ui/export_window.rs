@@ -0,0 +1,343 @@
+use crate::app::AppState;
+use crate::io::{default_export_path, ExportOptions, SpeakerLabel, SubripSaveHelper, TextEncoding};
+use crate::prelude::*;
+use crate::subrip::SubripFormat;
+use crate::ui::Drawable;
+use crate::{Track, TrackId};
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// E.g. `talk.2.srt` for `talk.srt` of the track 2.
+fn path_with_track_id(path: &Path, id: TrackId) -> PathBuf {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => path.with_extension(format!("{}.{}", id, ext)),
+        None => path.with_extension(id.to_string()),
+    }
+}
+
+/// Choose the track, the path, the format and the encoding before exporting.
+pub struct ExportWindow {
+    app_state: Shared<AppState>,
+    save_helper: SubripSaveHelper,
+    visible: bool,
+    track: TrackId,
+    path_text: String,
+    format: SubripFormat,
+    encoding: TextEncoding,
+    word_timing: bool,
+    speaker_label: SpeakerLabel,
+    /// Of the last export, the window stays open to show it
+    export_error: Option<String>,
+}
+
+impl ExportWindow {
+    pub fn new(app_state: Shared<AppState>) -> Self {
+        Self {
+            save_helper: SubripSaveHelper::new(app_state.clone()),
+            app_state,
+            visible: false,
+            track: 0,
+            path_text: String::new(),
+            format: SubripFormat::SRT,
+            encoding: TextEncoding::default(),
+            word_timing: false,
+            speaker_label: SpeakerLabel::None,
+            export_error: None,
+        }
+    }
+
+    /// Show the window with the active track and `format` selected, the path is named after
+    /// the opening media.
+    pub fn open(&mut self, format: &SubripFormat) {
+        let app_state = self.app_state.borrow();
+        let media_path = app_state.file_path_opening.clone();
+        let track = match app_state.track(app_state.active_track) {
+            Some(track) => track.clone(),
+            None => Track::default(),
+        };
+        drop(app_state);
+
+        self.track = track.id;
+        self.format = *format;
+        self.path_text = default_export_path(media_path.as_deref(), &track, *format)
+            .display()
+            .to_string();
+        self.export_error = None;
+        self.visible = true;
+    }
+
+    /// Show the window with `track` selected in the format of the track.
+    pub fn open_track(&mut self, id: &TrackId) {
+        let format = match self.app_state.borrow().track(*id) {
+            Some(track) => track.format,
+            None => return,
+        };
+
+        self.open(&format);
+        self.set_track(*id);
+    }
+
+    /// The default file name of `track` in the directory of the current path.
+    fn path_of_track(&self, track: &Track, format: SubripFormat) -> PathBuf {
+        let media_path = self.app_state.borrow().file_path_opening.clone();
+        let default_path = default_export_path(media_path.as_deref(), track, format);
+        let path = PathBuf::from(self.path_text.trim());
+
+        match (path.parent(), default_path.file_name()) {
+            (Some(dir), Some(name)) if !path.as_os_str().is_empty() => dir.join(name),
+            _ => default_path,
+        }
+    }
+
+    fn set_track(&mut self, id: TrackId) {
+        if self.track == id {
+            return;
+        }
+        self.track = id;
+
+        let track = self.app_state.borrow().track(id).cloned();
+        if let Some(track) = track {
+            self.path_text = self
+                .path_of_track(&track, self.format)
+                .display()
+                .to_string();
+        }
+    }
+
+    fn set_format(&mut self, format: SubripFormat) {
+        if self.format == format {
+            return;
+        }
+        self.format = format;
+
+        if !self.path_text.is_empty() {
+            self.path_text = PathBuf::from(&self.path_text)
+                .with_extension(format.extension())
+                .display()
+                .to_string();
+        }
+    }
+
+    fn browse(&mut self) {
+        let path = PathBuf::from(&self.path_text);
+        let mut dialog = rfd::FileDialog::new().add_filter("subrip", self.format.extensions());
+        if let Some(dir) = path.parent() {
+            dialog = dialog.set_directory(dir);
+        }
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            dialog = dialog.set_file_name(name);
+        }
+
+        if let Some(path) = dialog.save_file() {
+            self.path_text = path.display().to_string();
+        }
+    }
+
+    /// Return `false` if the user refused to overwrite an existing file.
+    fn confirm_overwrite(path: &std::path::Path) -> bool {
+        if !path.exists() {
+            return true;
+        }
+
+        rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title("Export")
+            .set_description(format!("{} already exists. Overwrite it?", path.display()))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show()
+            == rfd::MessageDialogResult::Yes
+    }
+
+    fn submit(&mut self) {
+        let path = PathBuf::from(self.path_text.trim());
+        if path.as_os_str().is_empty() || !Self::confirm_overwrite(&path) {
+            return;
+        }
+
+        let result = self.save_helper.export(&ExportOptions {
+            track: self.track,
+            path,
+            format: self.format,
+            encoding: self.encoding,
+            word_timing: self.word_timing,
+            speaker_label: self.speaker_label,
+        });
+        self.finish(vec![result]);
+    }
+
+    /// Export every track in its own format next to the current path. Tracks which would
+    /// share a path, e.g. without a language, get their id in the file name.
+    fn submit_all(&mut self) {
+        let tracks = self.app_state.borrow().tracks.clone();
+        let mut paths = HashSet::new();
+        let mut results = Vec::new();
+        for track in tracks.iter() {
+            let mut path = self.path_of_track(track, track.format);
+            if paths.contains(&path) {
+                path = path_with_track_id(&path, track.id);
+            }
+            paths.insert(path.clone());
+            if !Self::confirm_overwrite(&path) {
+                continue;
+            }
+
+            results.push(self.save_helper.export(&ExportOptions {
+                track: track.id,
+                path,
+                format: track.format,
+                encoding: self.encoding,
+                word_timing: self.word_timing,
+                speaker_label: self.speaker_label,
+            }));
+        }
+        self.finish(results);
+    }
+
+    /// Close the window unless an export failed, whose errors are shown instead.
+    fn finish(&mut self, results: Vec<Result<()>>) {
+        let errors = results
+            .into_iter()
+            .filter_map(|result| result.err())
+            .map(|err| err.to_string())
+            .collect::<Vec<_>>();
+        for err in errors.iter() {
+            error!("{}", err);
+        }
+
+        if errors.is_empty() {
+            self.export_error = None;
+            self.visible = false;
+        } else {
+            self.export_error = Some(errors.join("\n"));
+        }
+    }
+}
+
+impl Drawable for ExportWindow {
+    fn draw(&mut self, ctx: &egui::Context, _eui: &mut egui::Ui) {
+        if !self.visible {
+            return;
+        }
+
+        let tracks = self.app_state.borrow().tracks.clone();
+        let mut visible = self.visible;
+        let mut track = self.track;
+        let mut format = self.format;
+        let mut browse = false;
+        let mut submit = false;
+        let mut submit_all = false;
+
+        egui::Window::new("Export")
+            .collapsible(false)
+            .open(&mut visible)
+            .show(ctx, |eui| {
+                egui::Grid::new("export_grid")
+                    .num_columns(2)
+                    .show(eui, |eui| {
+                        eui.label("Track");
+                        let selected_text = tracks
+                            .iter()
+                            .find(|i| i.id == track)
+                            .map(|i| i.name.clone())
+                            .unwrap_or_default();
+                        egui::ComboBox::from_id_source("export_track")
+                            .selected_text(selected_text)
+                            .show_ui(eui, |eui| {
+                                for i in tracks.iter() {
+                                    eui.selectable_value(&mut track, i.id, &i.name);
+                                }
+                            });
+                        eui.end_row();
+
+                        eui.label("Format");
+                        egui::ComboBox::from_id_source("export_format")
+                            .selected_text(format.name())
+                            .show_ui(eui, |eui| {
+                                for i in SubripFormat::ALL {
+                                    eui.selectable_value(&mut format, i, i.name());
+                                }
+                            });
+                        eui.end_row();
+
+                        eui.label("Encoding");
+                        egui::ComboBox::from_id_source("export_encoding")
+                            .selected_text(self.encoding.name())
+                            .show_ui(eui, |eui| {
+                                for i in TextEncoding::ALL {
+                                    eui.selectable_value(&mut self.encoding, i, i.name());
+                                }
+                            });
+                        eui.end_row();
+
+                        eui.label("Word Timing");
+                        eui.add_enabled(
+                            format != SubripFormat::SRT,
+                            egui::Checkbox::new(&mut self.word_timing, "Karaoke"),
+                        )
+                        .on_hover_text(
+                            "Time the transcribed words with \\k tags (ASS) or inline timestamps (WebVTT)",
+                        );
+                        eui.end_row();
+
+                        eui.label("Speakers");
+                        egui::ComboBox::from_id_source("export_speaker_label")
+                            .selected_text(self.speaker_label.name())
+                            .show_ui(eui, |eui| {
+                                for i in SpeakerLabel::ALL {
+                                    eui.selectable_value(&mut self.speaker_label, i, i.name());
+                                }
+                            });
+                        eui.end_row();
+
+                        eui.label("Path");
+                        eui.horizontal(|eui| {
+                            eui.text_edit_singleline(&mut self.path_text);
+                            browse = eui.button("…").clicked();
+                        });
+                        eui.end_row();
+                    });
+
+                if let Some(err) = self.export_error.as_ref() {
+                    eui.colored_label(egui::Color32::from_hex("#D20F39").unwrap(), err);
+                }
+
+                eui.horizontal(|eui| {
+                    submit = eui.button("Export").clicked();
+                    submit_all = eui
+                        .button("Export All Tracks")
+                        .on_hover_text("Each track in its own format, next to the path")
+                        .clicked();
+                });
+            });
+
+        self.visible = visible;
+        self.set_track(track);
+        self.set_format(format);
+        if browse {
+            self.browse();
+        }
+        if submit {
+            self.submit();
+        } else if submit_all {
+            self.submit_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_with_track_id() {
+        assert_eq!(
+            path_with_track_id(Path::new("/media/talk.en.srt"), 2),
+            PathBuf::from("/media/talk.en.2.srt")
+        );
+        assert_eq!(
+            path_with_track_id(Path::new("talk"), 3),
+            PathBuf::from("talk.3")
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use crate::app::AppState;
-use crate::io::{SubripSaveHelper, SubripWriterBuilder};
+use crate::autosave::Autosave;
+use crate::history::EditCommand;
 use crate::prelude::*;
+use crate::project::ProjectHelper;
 use crate::ui::{self, Drawable};
 
 pub struct MainWindow {
@@ -8,36 +10,59 @@ pub struct MainWindow {
 
     pub sig_toggle_new_subrip_win: Signal<()>,
     pub sig_toggle_media_play: Signal<()>,
+    pub sig_undo: Signal<()>,
+    pub sig_redo: Signal<()>,
     // TODO: use to implement Shortcut
     pub sig_delete_subrip: Signal<()>,
 
     menu_bar: Shared<ui::MenuBar>,
     new_subrip_win: Shared<ui::NewSubripWindow>,
+    export_win: Shared<ui::ExportWindow>,
+    track_win: Shared<ui::TrackWindow>,
+    speaker_win: Shared<ui::SpeakerWindow>,
+    ai_settings_win: Shared<ui::AiSettingsWindow>,
     subrip_list_widget: Shared<ui::SubripListWidget>,
     timeline: Shared<ui::Timeline>,
     monitor: Shared<ui::Monitor>,
     control_bar: Shared<ui::ControlBar>,
 
-    subrip_save_helper: Shared<SubripSaveHelper>,
+    project_helper: Shared<ProjectHelper>,
+    autosave: Autosave,
 }
 
 impl MainWindow {
     pub fn new(app_state: Shared<AppState>) -> Self {
+        let subrip_list_widget = Shared::new(ui::SubripListWidget::new(app_state.clone()));
+        let timeline = Shared::new(ui::Timeline::new(app_state.clone()));
+        let monitor = Shared::new(ui::Monitor::new(app_state.clone()));
+
         let mut ret = Self {
             app_state: app_state.clone(),
 
             sig_toggle_new_subrip_win: Signal::new(),
             sig_toggle_media_play: Signal::new(),
+            sig_undo: Signal::new(),
+            sig_redo: Signal::new(),
             sig_delete_subrip: Signal::new(),
 
             menu_bar: Shared::new(ui::MenuBar::new()),
             new_subrip_win: Shared::new(ui::NewSubripWindow::new()),
-            subrip_list_widget: Shared::new(ui::SubripListWidget::new(app_state.clone())),
-            timeline: Shared::new(ui::Timeline::new(app_state.clone())),
-            monitor: Shared::new(ui::Monitor::new(app_state.clone())),
+            export_win: Shared::new(ui::ExportWindow::new(app_state.clone())),
+            track_win: Shared::new(ui::TrackWindow::new(app_state.clone())),
+            speaker_win: Shared::new(ui::SpeakerWindow::new(app_state.clone())),
+            ai_settings_win: Shared::new(ui::AiSettingsWindow::new(app_state.clone())),
+            subrip_list_widget: subrip_list_widget.clone(),
+            timeline: timeline.clone(),
+            monitor: monitor.clone(),
             control_bar: Shared::new(ui::ControlBar::new()),
 
-            subrip_save_helper: Shared::new(SubripSaveHelper::new(app_state.clone())),
+            project_helper: Shared::new(ProjectHelper::new(
+                app_state.clone(),
+                monitor,
+                timeline.clone(),
+                subrip_list_widget,
+            )),
+            autosave: Autosave::new(app_state.clone(), timeline),
         };
 
         ret.init();
@@ -47,19 +72,6 @@ impl MainWindow {
 
     /// Initialize connections between Signals and Functions
     fn init(&mut self) {
-        let save_dir_path_buf = self
-            .subrip_save_helper
-            .borrow()
-            .save_dir_path()
-            .to_path_buf();
-
-        if let Some(srt_writer) = SubripWriterBuilder::generate_writer_from_format(
-            save_dir_path_buf.as_path(),
-            crate::subrip::SubripFormat::SRT,
-        ) {
-            self.subrip_save_helper.borrow_mut().add_writer(srt_writer);
-        }
-
         self.menu_bar
             .borrow_mut()
             .sig_open_selected
@@ -74,9 +86,32 @@ impl MainWindow {
 
         self.menu_bar
             .borrow_mut()
-            .sig_export_srt_selected
-            .connect_func(|_| {
-                debug!("Selected export srt...");
+            .sig_open_project_selected
+            .connect_method(self.project_helper.clone(), ProjectHelper::open);
+
+        self.menu_bar
+            .borrow_mut()
+            .sig_save_project_selected
+            .connect_method(self.project_helper.clone(), ProjectHelper::save);
+
+        self.menu_bar
+            .borrow_mut()
+            .sig_save_project_as_selected
+            .connect_method(self.project_helper.clone(), ProjectHelper::save_as);
+
+        self.menu_bar
+            .borrow_mut()
+            .sig_import_selected
+            .connect_method(
+                self.subrip_list_widget.clone(),
+                ui::SubripListWidget::import_subrips,
+            );
+
+        self.menu_bar
+            .borrow_mut()
+            .sig_export_selected
+            .connect_func(|format| {
+                debug!("Selected export {}...", format.name());
             });
 
         let state = self.app_state.clone();
@@ -85,8 +120,15 @@ impl MainWindow {
             .borrow_mut()
             .sig_created_subrip
             .connect_func(move |subrip| {
-                state.borrow_mut().subrips.push(subrip.clone());
+                state.borrow_mut().add_subrip(subrip);
                 subrip_list_widget.borrow_mut().add(subrip.clone());
+                state
+                    .borrow()
+                    .history
+                    .borrow_mut()
+                    .push(EditCommand::Create {
+                        subrip: subrip.clone(),
+                    });
             });
 
         self.sig_toggle_new_subrip_win.connect_method(
@@ -94,6 +136,14 @@ impl MainWindow {
             ui::NewSubripWindow::toggle_visible,
         );
 
+        let subrip_list_widget = self.subrip_list_widget.clone();
+        self.timeline
+            .borrow_mut()
+            .sig_subrip_created
+            .connect_func(move |subrip| {
+                subrip_list_widget.borrow_mut().add(subrip.clone());
+            });
+
         self.subrip_list_widget
             .borrow_mut()
             .sig_subrip_loaded
@@ -101,8 +151,8 @@ impl MainWindow {
 
         self.monitor
             .borrow_mut()
-            .sig_media_duration_s_changed
-            .connect_method(self.timeline.clone(), ui::Timeline::set_media_duration_s);
+            .sig_media_duration_ms_changed
+            .connect_method(self.timeline.clone(), ui::Timeline::set_media_duration_ms);
 
         self.timeline
             .borrow_mut()
@@ -129,11 +179,13 @@ impl MainWindow {
 
         self.menu_bar
             .borrow_mut()
-            .sig_export_srt_selected
-            .connect_method(
-                self.subrip_save_helper.clone(),
-                crate::io::SubripSaveHelper::save,
-            );
+            .sig_export_selected
+            .connect_method(self.export_win.clone(), ui::ExportWindow::open);
+
+        self.subrip_list_widget
+            .borrow_mut()
+            .sig_export_track_selected
+            .connect_method(self.export_win.clone(), ui::ExportWindow::open_track);
 
         self.menu_bar
             .borrow_mut()
@@ -143,6 +195,35 @@ impl MainWindow {
                 ui::SubripListWidget::translate_by_ai,
             );
 
+        self.menu_bar
+            .borrow_mut()
+            .sig_tracks_selected
+            .connect_method(self.track_win.clone(), ui::TrackWindow::toggle_visible);
+
+        self.menu_bar
+            .borrow_mut()
+            .sig_speakers_selected
+            .connect_method(self.speaker_win.clone(), ui::SpeakerWindow::toggle_visible);
+
+        self.menu_bar
+            .borrow_mut()
+            .sig_ai_settings_selected
+            .connect_method(
+                self.ai_settings_win.clone(),
+                ui::AiSettingsWindow::toggle_visible,
+            );
+
+        self.sig_undo
+            .connect_method(self.subrip_list_widget.clone(), ui::SubripListWidget::undo);
+
+        self.sig_redo
+            .connect_method(self.subrip_list_widget.clone(), ui::SubripListWidget::redo);
+
+        self.control_bar
+            .borrow_mut()
+            .sig_btn_overlay_clicked
+            .connect_method(self.monitor.clone(), ui::Monitor::toggle_overlay_settings);
+
         self.control_bar
             .borrow_mut()
             .sig_btn_clear_clicked
@@ -152,6 +233,18 @@ impl MainWindow {
             );
     }
 
+    /// Restore the session autosaved at `path`.
+    pub fn recover_session(&mut self, ctx: &egui::Context, path: &std::path::Path) {
+        // Loading the media needs the context before the first frame.
+        self.monitor.borrow_mut().set_ctx(ctx);
+        self.timeline.borrow_mut().set_ctx(ctx);
+        self.project_helper.borrow_mut().recover(path);
+    }
+
+    pub fn on_exit(&mut self) {
+        self.autosave.discard();
+    }
+
     /// Poll and handle input events
     fn update_input_event(&mut self, ctx: &egui::Context) {
         if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::Enter)) {
@@ -161,14 +254,30 @@ impl MainWindow {
         if ctx.input(|i| i.key_pressed(egui::Key::Space)) {
             self.sig_toggle_media_play.emit(&());
         }
+
+        // Text fields have their own undo while they are focused.
+        if !ctx.wants_keyboard_input() {
+            if ctx
+                .input(|i| i.modifiers.command && i.modifiers.shift && i.key_pressed(egui::Key::Z))
+            {
+                self.sig_redo.emit(&());
+            } else if ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::Z)) {
+                self.sig_undo.emit(&());
+            }
+        }
     }
 }
 
 impl Drawable for MainWindow {
     fn draw(&mut self, ctx: &egui::Context, eui: &mut egui::Ui) {
         self.update_input_event(ctx);
+        self.autosave.update();
 
         self.new_subrip_win.borrow_mut().draw(ctx, eui);
+        self.export_win.borrow_mut().draw(ctx, eui);
+        self.track_win.borrow_mut().draw(ctx, eui);
+        self.speaker_win.borrow_mut().draw(ctx, eui);
+        self.ai_settings_win.borrow_mut().draw(ctx, eui);
 
         egui::TopBottomPanel::bottom("b1")
             .resizable(true)
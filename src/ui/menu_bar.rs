@@ -1,18 +1,31 @@
 use crate::prelude::*;
+use crate::project::PROJECT_EXTENSION;
+use crate::subrip::SubripFormat;
 use crate::ui::Drawable;
 
 use std::path::PathBuf;
 
 pub struct MenuBar {
     pub sig_open_selected: Signal<PathBuf>,
-    pub sig_export_srt_selected: Signal<()>,
+    pub sig_open_project_selected: Signal<PathBuf>,
+    pub sig_save_project_selected: Signal<()>,
+    pub sig_save_project_as_selected: Signal<PathBuf>,
+    pub sig_import_selected: Signal<(PathBuf, SubripFormat)>,
+    pub sig_export_selected: Signal<SubripFormat>,
     pub sig_translate_by_ai_selected: Signal<()>,
+    pub sig_ai_settings_selected: Signal<()>,
+    pub sig_tracks_selected: Signal<()>,
+    pub sig_speakers_selected: Signal<()>,
 }
 
 #[derive(Default)]
 struct MenuBarOutput {
     path_buf: Option<PathBuf>,
-    show_export_win_srt: bool,
+    open_project: Option<PathBuf>,
+    save_project: bool,
+    save_project_as: Option<PathBuf>,
+    import: Option<(PathBuf, SubripFormat)>,
+    export: Option<SubripFormat>,
 }
 
 impl Default for MenuBar {
@@ -25,8 +38,15 @@ impl MenuBar {
     pub fn new() -> Self {
         Self {
             sig_open_selected: Signal::new(),
-            sig_export_srt_selected: Signal::new(),
+            sig_open_project_selected: Signal::new(),
+            sig_save_project_selected: Signal::new(),
+            sig_save_project_as_selected: Signal::new(),
+            sig_import_selected: Signal::new(),
+            sig_export_selected: Signal::new(),
             sig_translate_by_ai_selected: Signal::new(),
+            sig_ai_settings_selected: Signal::new(),
+            sig_tracks_selected: Signal::new(),
+            sig_speakers_selected: Signal::new(),
         }
     }
 
@@ -43,10 +63,55 @@ impl MenuBar {
             }
         }
 
+        eui.separator();
+
+        if eui.button("OPEN PROJECT").clicked() {
+            if let Some(path_buf) = rfd::FileDialog::new()
+                .add_filter("project", &[PROJECT_EXTENSION])
+                .pick_file()
+            {
+                output.open_project = Some(path_buf);
+            }
+            eui.close_menu();
+        }
+
+        if eui.button("SAVE PROJECT").clicked() {
+            output.save_project = true;
+            eui.close_menu();
+        }
+
+        if eui.button("SAVE PROJECT AS").clicked() {
+            if let Some(path_buf) = rfd::FileDialog::new()
+                .add_filter("project", &[PROJECT_EXTENSION])
+                .save_file()
+            {
+                output.save_project_as = Some(path_buf.with_extension(PROJECT_EXTENSION));
+            }
+            eui.close_menu();
+        }
+
+        eui.separator();
+
+        eui.menu_button("IMPORT", |eui| {
+            for format in SubripFormat::ALL {
+                if eui.button(format.name()).clicked() {
+                    if let Some(path_buf) = rfd::FileDialog::new()
+                        .add_filter("subrip", format.extensions())
+                        .pick_file()
+                    {
+                        output.import = Some((path_buf, format));
+                    }
+                    eui.close_menu();
+                }
+            }
+        });
+
         eui.menu_button("EXPORT", |eui| {
-            if eui.button("SRT").clicked() {
-                output.show_export_win_srt = true;
-                eui.close_menu();
+            for format in SubripFormat::ALL {
+                if eui.button(format.name()).clicked() {
+                    output.export = Some(format);
+                    eui.close_menu();
+                }
             }
         });
 
@@ -61,13 +126,45 @@ impl Drawable for MenuBar {
                 self.sig_open_selected.emit(&path_buf);
             }
 
-            if output.show_export_win_srt {
-                self.sig_export_srt_selected.emit(&());
+            if let Some(path_buf) = output.open_project {
+                self.sig_open_project_selected.emit(&path_buf);
+            }
+
+            if output.save_project {
+                self.sig_save_project_selected.emit(&());
+            }
+
+            if let Some(path_buf) = output.save_project_as {
+                self.sig_save_project_as_selected.emit(&path_buf);
+            }
+
+            if let Some(import) = output.import {
+                self.sig_import_selected.emit(&import);
+            }
+
+            if let Some(format) = output.export {
+                self.sig_export_selected.emit(&format);
             }
         }
 
-        if eui.button("AI").clicked() {
-            self.sig_translate_by_ai_selected.emit(&());
+        eui.menu_button("AI", |eui| {
+            if eui.button("TRANSCRIBE").clicked() {
+                self.sig_translate_by_ai_selected.emit(&());
+                eui.close_menu();
+            }
+
+            if eui.button("SETTINGS").clicked() {
+                self.sig_ai_settings_selected.emit(&());
+                eui.close_menu();
+            }
+        });
+
+        if eui.button("TRACKS").clicked() {
+            self.sig_tracks_selected.emit(&());
+        }
+
+        if eui.button("SPEAKERS").clicked() {
+            self.sig_speakers_selected.emit(&());
         }
     }
 }
@@ -1,23 +1,35 @@
+pub mod ai_settings_window;
 pub mod control_bar;
 pub mod edit_subrip_window;
+pub mod export_window;
 pub mod mainwindow;
 pub mod menu_bar;
 pub mod monitor;
 pub mod new_subrip_window;
+pub mod speaker_window;
 pub mod subrip_list_item;
 pub mod subrip_list_widget;
+pub mod subtitle_overlay;
 pub mod timeline;
+pub mod track_window;
+pub mod translation_view;
 
+pub use ai_settings_window::AiSettingsWindow;
 pub use control_bar::ControlBar;
 pub use edit_subrip_window::EditSubripWindow;
+pub use export_window::ExportWindow;
 pub use mainwindow::MainWindow;
 pub use menu_bar::MenuBar;
 pub use monitor::Monitor;
 pub use new_subrip_window::NewSubripWindow;
+pub use speaker_window::SpeakerWindow;
 pub use subrip_list_item::SubripListItem;
 pub use subrip_list_widget::SubripListWidget;
+pub use subtitle_overlay::SubtitleOverlay;
 pub use timeline::subrip_block::SubripBlock;
 pub use timeline::Timeline;
+pub use track_window::TrackWindow;
+pub use translation_view::TranslationView;
 
 use eframe::egui;
 
@@ -1,11 +1,13 @@
 use crate::app::AppState;
 use crate::core::media_player::{AudioDevice, Player};
 use crate::prelude::*;
+use crate::ui::subtitle_overlay::SubtitleOverlay;
 use crate::ui::Drawable;
+use crate::Timestamp;
 
 pub struct Monitor {
     pub sig_media_loaded: Signal<Shared<Player>>,
-    pub sig_media_duration_s_changed: Signal<i64>,
+    pub sig_media_duration_ms_changed: Signal<i64>,
 
     pub app_state: Shared<AppState>,
 
@@ -13,13 +15,16 @@ pub struct Monitor {
     pub player: Option<Shared<Player>>,
     pub audio_device: Option<AudioDevice>,
     pub media_path: String,
+
+    overlay: SubtitleOverlay,
+    overlay_settings_visible: bool,
 }
 
 impl Monitor {
     pub fn new(app_state: Shared<AppState>) -> Self {
         Self {
             sig_media_loaded: Signal::new(),
-            sig_media_duration_s_changed: Signal::new(),
+            sig_media_duration_ms_changed: Signal::new(),
 
             app_state,
 
@@ -27,6 +32,9 @@ impl Monitor {
             player: None,
             audio_device: None,
             media_path: String::new(),
+
+            overlay: SubtitleOverlay::default(),
+            overlay_settings_visible: false,
         }
     }
 
@@ -36,8 +44,7 @@ impl Monitor {
             self.media_path = str.to_string();
             if let Some(ctx) = &self.ctx {
                 if let Ok(mut player) = media_player::Player::new(ctx, &self.media_path) {
-                    self.sig_media_duration_s_changed
-                        .emit(&(player.duration_ms / 1000));
+                    self.sig_media_duration_ms_changed.emit(&player.duration_ms);
                     // `Player` without control bar
                     player.options.without_control_bar = true;
                     // Set audio volume
@@ -91,6 +98,10 @@ impl Monitor {
         }
     }
 
+    pub fn toggle_overlay_settings(&mut self, _: &()) {
+        self.overlay_settings_visible = !self.overlay_settings_visible;
+    }
+
     pub fn set_ctx(&mut self, ctx: &egui::Context) {
         self.ctx = Some(ctx.clone());
     }
@@ -103,11 +114,12 @@ impl Monitor {
         }
     }
 
+    /// The elapsed duration of the media in MILLISECONDS.
     pub fn current_timestamp(&self) -> i64 {
         use crate::core::media_player::Streamer;
 
         if let Some(player) = &self.player {
-            player.borrow_mut().video_streamer.lock().elapsed_ms().get() / 1000
+            player.borrow_mut().video_streamer.lock().elapsed_ms().get()
         } else {
             error!("The field `player` of ui::Moniter is None!");
 
@@ -115,11 +127,12 @@ impl Monitor {
         }
     }
 
+    /// The total duration of the media in MILLISECONDS.
     pub fn get_media_duration(&self) -> i64 {
         use crate::core::media_player::Streamer;
 
         if let Some(player) = &self.player {
-            player.borrow_mut().video_streamer.lock().duration_ms() / 1000
+            player.borrow_mut().video_streamer.lock().duration_ms()
         } else {
             error!("The field `player` of ui::Moniter is None!");
 
@@ -129,9 +142,27 @@ impl Monitor {
 }
 
 impl Drawable for Monitor {
-    fn draw(&mut self, _ctx: &egui::Context, eui: &mut egui::Ui) {
+    fn draw(&mut self, ctx: &egui::Context, eui: &mut egui::Ui) {
         if let Some(player) = &mut self.player {
-            player.borrow_mut().ui(eui, eui.available_size());
+            let response = player.borrow_mut().ui(eui, eui.available_size());
+            let timestamp = Timestamp::from_millis(player.borrow().elapsed_ms());
+
+            // Preview the track being edited.
+            let app_state = self.app_state.borrow();
+            if let Some(track) = app_state.track(app_state.active_track) {
+                self.overlay.paint(
+                    &eui.painter_at(response.rect),
+                    response.rect,
+                    &app_state.subrips_of_track(track.id),
+                    &track.header.ass,
+                    timestamp,
+                );
+            }
         }
+
+        egui::Window::new("Subtitle Overlay")
+            .collapsible(false)
+            .open(&mut self.overlay_settings_visible)
+            .show(ctx, |eui| self.overlay.settings_ui(eui));
     }
 }
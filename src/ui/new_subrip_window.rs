@@ -34,15 +34,16 @@ impl NewSubripWindow {
     }
 
     fn create_subrip(&mut self) {
-        use chrono::{Duration, NaiveTime};
+        use crate::Timestamp;
+        use chrono::Duration;
 
         // TODO: get begin time from Time Line
-        // let begin_time = NaiveTime::from_hms_milli_opt(12, 34, 56, 789).unwrap();
+        // let begin_time = Timestamp::from_hms_milli(12, 34, 56, 789);
         let begin_time = if let Some(timeline) = self.timeline.as_ref() {
-            let timestamp_s = timeline.borrow().get_cursor_timestamp();
-            NaiveTime::from_num_seconds_from_midnight_opt(timestamp_s as u32, 0).unwrap_or_default()
+            let timestamp_ms = timeline.borrow().get_cursor_timestamp();
+            Timestamp::from_millis(timestamp_ms)
         } else {
-            NaiveTime::default()
+            Timestamp::ZERO
         };
         // Default duration
         let duration = Duration::new(5, 0).unwrap();
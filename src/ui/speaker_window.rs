@@ -0,0 +1,135 @@
+use crate::app::AppState;
+use crate::history::EditCommand;
+use crate::prelude::*;
+use crate::ui::Drawable;
+use crate::SpeakerId;
+
+/// Add, rename, colour, merge and remove the speakers the subrips are attributed to.
+pub struct SpeakerWindow {
+    app_state: Shared<AppState>,
+    visible: bool,
+}
+
+impl SpeakerWindow {
+    pub fn new(app_state: Shared<AppState>) -> Self {
+        Self {
+            app_state,
+            visible: false,
+        }
+    }
+
+    pub fn toggle_visible(&mut self, _: &()) {
+        self.visible = !self.visible;
+    }
+}
+
+impl Drawable for SpeakerWindow {
+    fn draw(&mut self, ctx: &egui::Context, _eui: &mut egui::Ui) {
+        if !self.visible {
+            return;
+        }
+
+        let mut visible = self.visible;
+        let mut removed: Option<SpeakerId> = None;
+        // `(from, into)`
+        let mut merged: Option<(SpeakerId, SpeakerId)> = None;
+        // The edit of a name or colour, with the widget it's typed into
+        let mut edited: Option<(EditCommand, egui::Id)> = None;
+        let mut session_ended = false;
+
+        egui::Window::new("Speakers")
+            .collapsible(false)
+            .open(&mut visible)
+            .show(ctx, |eui| {
+                let mut app_state = self.app_state.borrow_mut();
+                let speakers = app_state.speakers.borrow().clone();
+                let counts = speakers
+                    .iter()
+                    .map(|speaker| {
+                        app_state
+                            .subrips
+                            .iter()
+                            .filter(|i| {
+                                let subrip = i.borrow();
+                                !subrip.is_deleted() && subrip.speaker == Some(speaker.id)
+                            })
+                            .count()
+                    })
+                    .collect::<Vec<_>>();
+
+                if speakers.is_empty() {
+                    eui.label("No speakers yet, they're added by diarized transcriptions too");
+                }
+
+                egui::Grid::new("speaker_grid")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(eui, |eui| {
+                        for (speaker, count) in speakers.iter().zip(counts) {
+                            let mut new = speaker.clone();
+                            let color =
+                                egui::color_picker::color_edit_button_srgb(eui, &mut new.color);
+                            let name = eui.add(
+                                egui::TextEdit::singleline(&mut new.name).desired_width(140.0),
+                            );
+                            for response in [color, name] {
+                                if response.changed() && new != *speaker {
+                                    let command = EditCommand::EditSpeaker {
+                                        speakers: app_state.speakers.clone(),
+                                        old: speaker.clone(),
+                                        new: new.clone(),
+                                    };
+                                    edited = Some((command, response.id));
+                                }
+                                session_ended |= response.lost_focus();
+                            }
+                            eui.label(format!("{} cues", count));
+                            eui.menu_button("Merge into…", |eui| {
+                                for other in speakers.iter().filter(|i| i.id != speaker.id) {
+                                    if eui.button(&other.name).clicked() {
+                                        merged = Some((speaker.id, other.id));
+                                        eui.close_menu();
+                                    }
+                                }
+                            })
+                            .response
+                            .on_hover_text("Give its subrips to another speaker and remove it");
+                            if eui
+                                .button("🗑")
+                                .on_hover_text("Its subrips are left without a speaker")
+                                .clicked()
+                            {
+                                removed = Some(speaker.id);
+                            }
+                            eui.end_row();
+                        }
+                    });
+
+                if eui.button("Add Speaker").clicked() {
+                    let name = format!("Speaker {}", speakers.len() + 1);
+                    app_state.add_speaker(name);
+                }
+            });
+
+        self.visible = visible;
+        let app_state = self.app_state.borrow();
+        let mut history = app_state.history.borrow_mut();
+        if let Some((command, session)) = edited {
+            command.redo();
+            history.push_typing(command, session);
+        }
+        if session_ended {
+            history.end_session();
+        }
+        if let Some(command) = removed.and_then(|id| app_state.remove_speaker_command(id)) {
+            command.redo();
+            history.push(command);
+        }
+        if let Some(command) =
+            merged.and_then(|(from, into)| app_state.merge_speakers_command(from, into))
+        {
+            command.redo();
+            history.push(command);
+        }
+    }
+}
@@ -1,9 +1,8 @@
 use std::hash::Hash;
 
-use chrono::NaiveTime;
-
-use crate::ui::Drawable;
-use crate::{prelude::*, Subrip};
+use crate::app::AppState;
+use crate::history::{EditCommand, EditHistory};
+use crate::{prelude::*, Speaker, Subrip, Timestamp};
 
 #[derive(Default)]
 pub struct SubripListItem {
@@ -13,9 +12,11 @@ pub struct SubripListItem {
     end_time_text: String,
     content_text: String,
 
-    old_begin_time_text: String,
-    old_end_time_text: String,
     old_content_text: String,
+
+    history: Shared<EditHistory>,
+    /// For the subrips retimed along with the subrip
+    app_state: Shared<AppState>,
 }
 
 impl Hash for SubripListItem {
@@ -33,50 +34,126 @@ impl SubripListItem {
         Self {
             subrip: subrip.clone(),
             content_text: subrip.borrow().get_content(),
-            begin_time_text: subrip
-                .borrow()
-                .get_begin_time()
-                .format("%H:%M:%S")
-                .to_string(),
-            end_time_text: subrip
-                .borrow()
-                .get_end_time()
-                .format("%H:%M:%S")
-                .to_string(),
+            begin_time_text: subrip.borrow().get_begin_time().to_string(),
+            end_time_text: subrip.borrow().get_end_time().to_string(),
             old_content_text: String::new(),
-            old_begin_time_text: String::new(),
-            old_end_time_text: String::new(),
+            history: Shared::default(),
+            app_state: Shared::default(),
         }
     }
 
-    fn sync_data(&mut self) {
-        if self.begin_time_text != self.old_begin_time_text {
-            self.old_begin_time_text.clone_from(&self.begin_time_text);
-            if let Ok(naive_time) =
-                NaiveTime::parse_from_str(self.old_begin_time_text.as_str(), "%H:%M:%S")
-            {
-                self.subrip.borrow_mut().set_begin_time(&naive_time);
-            }
+    pub fn set_app_state(&mut self, app_state: Shared<AppState>) {
+        self.app_state = app_state;
+    }
+
+    pub fn set_history(&mut self, history: Shared<EditHistory>) {
+        self.history = history;
+    }
+
+    /// Refresh the text fields from the subrip, e.g. after undo/redo.
+    pub fn reload(&mut self) {
+        let subrip = self.subrip.borrow();
+        self.begin_time_text = subrip.get_begin_time().to_string();
+        self.end_time_text = subrip.get_end_time().to_string();
+        self.content_text = subrip.get_content();
+        self.old_content_text.clone_from(&self.content_text);
+    }
+
+    /// Pick the speaker of the subrip, there's nothing to pick until a speaker is added.
+    fn draw_speaker(&mut self, eui: &mut egui::Ui, speakers: &[Speaker]) {
+        if speakers.is_empty() {
+            return;
+        }
+
+        let (id, old) = {
+            let subrip = self.subrip.borrow();
+            (subrip.id, subrip.speaker)
+        };
+        let mut new = old;
+        let selected_text = match old.and_then(|id| speakers.iter().find(|i| i.id == id)) {
+            Some(speaker) => egui::RichText::new(&speaker.name).color(speaker.color32()),
+            None => egui::RichText::new("No speaker"),
+        };
+        egui::ComboBox::from_id_source(("subrip_speaker", id))
+            .selected_text(selected_text)
+            .width(100.0)
+            .show_ui(eui, |eui| {
+                eui.selectable_value(&mut new, None, "No speaker");
+                for speaker in speakers.iter() {
+                    eui.selectable_value(&mut new, Some(speaker.id), &speaker.name);
+                }
+            });
+
+        if new != old {
+            let command = EditCommand::SetSpeaker {
+                subrip: self.subrip.clone(),
+                old,
+                new,
+            };
+            command.redo();
+            self.history.borrow_mut().push(command);
         }
-        if self.end_time_text != self.old_end_time_text {
-            self.old_end_time_text.clone_from(&self.end_time_text);
-            if let Ok(naive_time) =
-                NaiveTime::parse_from_str(self.old_end_time_text.as_str(), "%H:%M:%S")
-            {
-                self.subrip.borrow_mut().set_end_time(&naive_time);
+    }
+
+    /// Apply the typed times, once their field loses the focus (e.g. on Enter). Times which
+    /// don't parse or don't begin before they end are reverted.
+    fn commit_times(&mut self) {
+        let old = {
+            let subrip = self.subrip.borrow();
+            (subrip.get_begin_time(), subrip.get_end_time())
+        };
+
+        match (
+            self.begin_time_text.parse::<Timestamp>(),
+            self.end_time_text.parse::<Timestamp>(),
+        ) {
+            (Ok(begin_time), Ok(end_time)) if begin_time < end_time => {
+                let new = (begin_time, end_time);
+                if new != old {
+                    // The subrips linked to the subrip follow it.
+                    let command = self
+                        .app_state
+                        .borrow()
+                        .retime_command(&self.subrip, old, new);
+                    command.redo();
+                    self.history.borrow_mut().push(command);
+                }
             }
+            _ => warn!(
+                "{} --> {} isn't a valid time range",
+                self.begin_time_text, self.end_time_text
+            ),
         }
+
+        let subrip = self.subrip.borrow();
+        self.begin_time_text = subrip.get_begin_time().to_string();
+        self.end_time_text = subrip.get_end_time().to_string();
+    }
+
+    /// `content_id` is the id of the text field of the content, keystrokes typed into it
+    /// in a row are a single step of the history.
+    fn sync_data(&mut self, content_id: egui::Id) {
+        let old_content = self.subrip.borrow().get_content();
+
         if self.content_text != self.old_content_text {
             self.old_content_text.clone_from(&self.content_text);
-            self.subrip
-                .borrow_mut()
-                .set_content(self.old_content_text.as_str());
+            if self.content_text != old_content {
+                let command = EditCommand::EditText {
+                    subrip: self.subrip.clone(),
+                    old: old_content,
+                    new: self.content_text.clone(),
+                };
+                command.redo();
+                self.history.borrow_mut().push_typing(command, content_id);
+            }
         }
     }
 }
 
-impl Drawable for SubripListItem {
-    fn draw(&mut self, _ctx: &eframe::egui::Context, eui: &mut eframe::egui::Ui) {
+impl SubripListItem {
+    /// Draw the item, `speakers` are those of [`AppState::speakers`] to pick from. They're
+    /// borrowed once for all items.
+    pub fn draw(&mut self, eui: &mut egui::Ui, speakers: &[Speaker]) {
         // Return directly if the subrip is loaded.
         if self.subrip.borrow_mut().is_loaded() || self.subrip.borrow_mut().is_deleted() {
             return;
@@ -84,24 +161,40 @@ impl Drawable for SubripListItem {
 
         let text_edit_width = 120.0;
 
-        eui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |eui| {
-            let begin = egui::TextEdit::singleline(&mut self.begin_time_text)
-                .margin(egui::Vec2 { x: 10.0, y: 10.0 })
-                .desired_width(text_edit_width);
-            begin.show(eui);
-            eui.add_space(eui.available_width() - text_edit_width);
-            let end = egui::TextEdit::singleline(&mut self.end_time_text)
-                .margin(egui::Vec2 { x: 10.0, y: 10.0 })
-                .desired_width(text_edit_width);
-            end.show(eui);
-        });
-        egui::TextEdit::multiline(&mut self.content_text)
+        let times_committed = eui
+            .with_layout(egui::Layout::left_to_right(egui::Align::TOP), |eui| {
+                let begin = egui::TextEdit::singleline(&mut self.begin_time_text)
+                    .margin(egui::Vec2 { x: 10.0, y: 10.0 })
+                    .desired_width(text_edit_width)
+                    .show(eui)
+                    .response;
+                self.draw_speaker(eui, speakers);
+                eui.add_space(eui.available_width() - text_edit_width);
+                let end = egui::TextEdit::singleline(&mut self.end_time_text)
+                    .margin(egui::Vec2 { x: 10.0, y: 10.0 })
+                    .desired_width(text_edit_width)
+                    .show(eui)
+                    .response;
+
+                begin.lost_focus() || end.lost_focus()
+            })
+            .inner;
+        if times_committed {
+            self.commit_times();
+        }
+        let id = self.subrip.borrow().id;
+        let content = egui::TextEdit::multiline(&mut self.content_text)
+            .id_source(("subrip_content", id))
             .desired_width(eui.available_width())
             .desired_rows(2)
-            .show(eui);
+            .show(eui)
+            .response;
         eui.separator();
 
-        self.sync_data();
+        self.sync_data(content.id);
+        if content.lost_focus() {
+            self.history.borrow_mut().end_session();
+        }
     }
 }
 
@@ -110,19 +203,11 @@ impl From<Shared<Subrip>> for SubripListItem {
         Self {
             subrip: value.clone(),
             content_text: value.borrow_mut().get_content(),
-            begin_time_text: value
-                .borrow_mut()
-                .get_begin_time()
-                .format("%H:%M:%S")
-                .to_string(),
-            end_time_text: value
-                .borrow_mut()
-                .get_end_time()
-                .format("%H:%M:%S")
-                .to_string(),
+            begin_time_text: value.borrow_mut().get_begin_time().to_string(),
+            end_time_text: value.borrow_mut().get_end_time().to_string(),
             old_content_text: String::new(),
-            old_begin_time_text: String::new(),
-            old_end_time_text: String::new(),
+            history: Shared::default(),
+            app_state: Shared::default(),
         }
     }
 }
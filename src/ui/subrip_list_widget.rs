@@ -1,49 +1,335 @@
+use crate::ai::{self, TranscriptionJob, TranscriptionStatus};
 use crate::app::AppState;
-use crate::ui::{Drawable, SubripListItem};
-use crate::{ai, prelude::*, Subrip};
+use crate::history::{EditCommand, EditHistory};
+use crate::io::SubripReaderBuilder;
+use crate::subrip::SubripFormat;
+use crate::ui::{Drawable, SubripListItem, TranslationView};
+use crate::{prelude::*, SpeakerId, Subrip, TrackId};
+
+use std::path::PathBuf;
+
+/// Which subrips the list shows by their speaker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SpeakerFilter {
+    #[default]
+    All,
+    /// The subrips without a speaker
+    Nobody,
+    Speaker(SpeakerId),
+}
 
 pub struct SubripListWidget {
     pub sig_subrip_loaded: Signal<Shared<Subrip>>,
+    pub sig_export_track_selected: Signal<TrackId>,
 
     app_state: Shared<AppState>,
+    history: Shared<EditHistory>,
 
     item_widgets: Vec<Shared<SubripListItem>>,
+    /// Show the subrips of this track only, `None` for all tracks
+    track_filter: Option<TrackId>,
+    speaker_filter: SpeakerFilter,
+    translation_view: TranslationView,
+    is_translating: bool,
+    transcription: Option<TranscriptionJob>,
+    /// Why the last transcription failed, shown until dismissed
+    transcription_error: Option<String>,
 }
 
 impl SubripListWidget {
     pub fn new(app_state: Shared<AppState>) -> Self {
+        let history = app_state.borrow().history.clone();
+
         Self {
             sig_subrip_loaded: Signal::new(),
+            sig_export_track_selected: Signal::new(),
+            translation_view: TranslationView::new(app_state.clone(), history.clone()),
             app_state,
+            history,
             item_widgets: vec![],
+            track_filter: None,
+            speaker_filter: SpeakerFilter::All,
+            is_translating: false,
+            transcription: None,
+            transcription_error: None,
         }
     }
 
     pub fn add(&mut self, item: Shared<Subrip>) {
-        let widget = SubripListItem::new(item);
+        let mut widget = SubripListItem::new(item);
+        widget.set_history(self.history.clone());
+        widget.set_app_state(self.app_state.clone());
         self.item_widgets.push(Shared::new(widget));
     }
 
+    pub fn clear(&mut self) {
+        self.item_widgets.clear();
+    }
+
+    pub fn undo(&mut self, _: &()) {
+        if self.history.borrow_mut().undo() {
+            self.reload_items();
+        }
+    }
+
+    pub fn redo(&mut self, _: &()) {
+        if self.history.borrow_mut().redo() {
+            self.reload_items();
+        }
+    }
+
+    /// Whether the subrip belongs to an existing track and passes the filters.
+    fn is_shown(&self, subrip: &Subrip) -> bool {
+        let passes_speaker_filter = match self.speaker_filter {
+            SpeakerFilter::All => true,
+            SpeakerFilter::Nobody => subrip.speaker.is_none(),
+            SpeakerFilter::Speaker(id) => subrip.speaker == Some(id),
+        };
+
+        self.app_state.borrow().track(subrip.track).is_some()
+            && self.track_filter.is_none_or(|id| id == subrip.track)
+            && passes_speaker_filter
+    }
+
+    /// Pick the track filter, new subrips still go to the active track.
+    fn draw_track_filter(&mut self, eui: &mut egui::Ui) {
+        let tracks = self.app_state.borrow().tracks.clone();
+        // The track may have been removed since the last frame.
+        if self
+            .track_filter
+            .is_some_and(|id| !tracks.iter().any(|i| i.id == id))
+        {
+            self.track_filter = None;
+        }
+        let selected_text = match self
+            .track_filter
+            .and_then(|id| tracks.iter().find(|i| i.id == id))
+        {
+            Some(track) => track.name.clone(),
+            None => "All tracks".to_string(),
+        };
+
+        egui::ComboBox::from_id_source("subrip_list_track_filter")
+            .selected_text(selected_text)
+            .show_ui(eui, |eui| {
+                eui.selectable_value(&mut self.track_filter, None, "All tracks");
+                for track in tracks.iter() {
+                    eui.selectable_value(&mut self.track_filter, Some(track.id), &track.name);
+                }
+            });
+    }
+
+    /// Pick the speaker filter, it's hidden until there are speakers.
+    fn draw_speaker_filter(&mut self, eui: &mut egui::Ui) {
+        let speakers = self.app_state.borrow().speakers.borrow().clone();
+        if speakers.is_empty() {
+            self.speaker_filter = SpeakerFilter::All;
+
+            return;
+        }
+
+        let selected_text = match self.speaker_filter {
+            SpeakerFilter::All => "All speakers".to_string(),
+            SpeakerFilter::Nobody => "No speaker".to_string(),
+            SpeakerFilter::Speaker(id) => speakers
+                .iter()
+                .find(|i| i.id == id)
+                .map(|i| i.name.clone())
+                .unwrap_or_default(),
+        };
+
+        egui::ComboBox::from_id_source("subrip_list_speaker_filter")
+            .selected_text(selected_text)
+            .show_ui(eui, |eui| {
+                eui.selectable_value(&mut self.speaker_filter, SpeakerFilter::All, "All speakers");
+                eui.selectable_value(
+                    &mut self.speaker_filter,
+                    SpeakerFilter::Nobody,
+                    "No speaker",
+                );
+                for speaker in speakers.iter() {
+                    eui.selectable_value(
+                        &mut self.speaker_filter,
+                        SpeakerFilter::Speaker(speaker.id),
+                        egui::RichText::new(&speaker.name).color(speaker.color32()),
+                    );
+                }
+            });
+    }
+
+    fn reload_items(&mut self) {
+        for item in self.item_widgets.iter() {
+            item.borrow_mut().reload();
+        }
+    }
+
+    /// Start transcribing the opening media in the background.
     pub fn translate_by_ai(&mut self, _: &()) {
-        let translator = ai::AiTranslator::default();
+        if self.transcription.is_some() {
+            warn!("A transcription is running already");
+
+            return;
+        }
+
+        let media_path = self.app_state.borrow().file_path_opening.clone();
+        if let Some(path) = media_path {
+            info!("Transcribe {}", path.display());
+            let translator = ai::AiTranslator::new(self.app_state.borrow().ai_settings.clone());
+            self.transcription_error = None;
+            self.transcription = Some(TranscriptionJob::spawn(translator, path));
+        } else {
+            error!("There isn't video selected...");
+        }
+    }
+
+    /// Add the transcribed subrips to the active track.
+    fn finish_transcription(&mut self, result: Result<String>) {
+        let subrips = match result.and_then(|str| utils::json_str_to_subrips(&str)) {
+            Ok(subrips) if subrips.is_empty() => {
+                warn!("The transcription has no subrips");
+                self.transcription_error = Some("Nothing was transcribed".to_string());
+
+                return;
+            }
+            Ok(subrips) => subrips,
+            Err(err) => {
+                error!("Transcription failed: {}", err);
+                self.transcription_error = Some(err.to_string());
+
+                return;
+            }
+        };
+
+        info!("Transcribed {} subrips", subrips.len());
         let app_state = self.app_state.clone();
         let mut borrowed_app_state = app_state.borrow_mut();
-        if let Some(path) = borrowed_app_state.file_path_opening.as_ref() {
-            let subrips = translator.translate(path.as_path());
-            for subrip in subrips.iter() {
-                borrowed_app_state.subrips.push(subrip.clone());
-                self.add(subrip.clone());
-            }
+        // The speaker labels of a diarized transcription come in the actor.
+        if borrowed_app_state.ai_settings.diarization {
+            borrowed_app_state.assign_speakers(&subrips);
         } else {
-            error!("There isn't video selected...");
+            subrips
+                .iter()
+                .for_each(|i| i.borrow_mut().style.actor.clear());
+        }
+        for subrip in subrips.iter() {
+            borrowed_app_state.add_subrip(subrip);
+            self.add(subrip.clone());
+        }
+    }
+
+    /// Poll the running transcription and show its progress.
+    fn draw_transcription(&mut self, ctx: &egui::Context, eui: &mut egui::Ui) {
+        if let Some(result) = self.transcription.as_mut().and_then(|job| job.poll()) {
+            self.transcription = None;
+            self.finish_transcription(result);
+        }
+
+        let mut cancelled = false;
+        if let Some(job) = self.transcription.as_ref() {
+            eui.horizontal(|eui| {
+                eui.spinner();
+                let elapsed = job.elapsed().as_secs();
+                let elapsed = format!("{:02}:{:02}", elapsed / 60, elapsed % 60);
+                match job.status() {
+                    TranscriptionStatus::ExtractingAudio { done, total } if total > 0 => {
+                        eui.label(format!(
+                            "Extracting audio, {}/{} chunks transcribed… {}",
+                            done, total, elapsed
+                        ));
+                    }
+                    TranscriptionStatus::ExtractingAudio { .. } => {
+                        eui.label(format!("Extracting audio… {}", elapsed));
+                    }
+                    TranscriptionStatus::Uploading(fraction) => {
+                        eui.add(
+                            egui::ProgressBar::new(fraction)
+                                .desired_width(120.0)
+                                .show_percentage(),
+                        );
+                        eui.label("Uploading");
+                    }
+                    TranscriptionStatus::Transcribing { done, total } => {
+                        eui.add(
+                            egui::ProgressBar::new(done as f32 / total as f32)
+                                .desired_width(120.0)
+                                .text(format!("{}/{}", done, total)),
+                        );
+                        eui.label(format!("Transcribing… {}", elapsed));
+                    }
+                }
+                cancelled = eui.button("Cancel").clicked();
+            });
+            // Keep the progress moving without any input.
+            ctx.request_repaint_after(std::time::Duration::from_millis(200));
+        } else if let Some(err) = self.transcription_error.clone() {
+            eui.horizontal(|eui| {
+                eui.colored_label(egui::Color32::from_hex("#D20F39").unwrap(), err);
+                if eui.small_button("✕").clicked() {
+                    self.transcription_error = None;
+                }
+            });
+        }
+
+        if cancelled {
+            if let Some(job) = self.transcription.take() {
+                info!("Transcription cancelled");
+                job.cancel();
+            }
+        }
+    }
+
+    /// Read subrips from a subtitle file and append them to the active track, which takes
+    /// the header of the file.
+    pub fn import_subrips(&mut self, (path_buf, format): &(PathBuf, SubripFormat)) {
+        let (subrips, header) =
+            match SubripReaderBuilder::generate_reader_from_format(path_buf, *format)
+                .and_then(|mut reader| Ok((reader.read()?, reader.header())))
+            {
+                Ok(result) => result,
+                Err(err) => {
+                    error!("Importing {} failed: {}", path_buf.display(), err);
+
+                    return;
+                }
+            };
+
+        info!(
+            "Imported {} subrips from {}",
+            subrips.len(),
+            path_buf.display()
+        );
+
+        let app_state = self.app_state.clone();
+        let mut borrowed_app_state = app_state.borrow_mut();
+        let active_track = borrowed_app_state.active_track;
+        if let Some((track, header)) = borrowed_app_state.track_mut(active_track).zip(header) {
+            track.header = header;
+        }
+        borrowed_app_state.assign_speakers(&subrips);
+        for subrip in subrips.iter() {
+            borrowed_app_state.add_subrip(subrip);
+            self.add(subrip.clone());
         }
     }
 
+    /// Delete the subrips shown in the list, i.e. of the filtered track.
     pub fn delete_all_subrips(&mut self, _: &()) {
-        let mut app_state = self.app_state.borrow_mut();
-        app_state.subrips.iter_mut().for_each(|i| {
-            i.borrow_mut().delete();
-        });
+        let subrips = self
+            .app_state
+            .borrow()
+            .subrips
+            .iter()
+            .filter(|i| !i.borrow().is_deleted() && self.is_shown(&i.borrow()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if subrips.is_empty() {
+            return;
+        }
+
+        let command = EditCommand::Delete { subrips };
+        command.redo();
+        self.history.borrow_mut().push(command);
     }
 }
 
@@ -53,10 +339,43 @@ impl Drawable for SubripListWidget {
         pos.x += eui.available_width();
         pos.y += eui.available_height();
         eui.separator();
+        self.draw_transcription(ctx, eui);
+        eui.horizontal(|eui| {
+            eui.selectable_value(&mut self.is_translating, false, "Cues");
+            eui.selectable_value(&mut self.is_translating, true, "Translation");
+        });
+
+        if self.is_translating {
+            let output = self.translation_view.draw(eui);
+            for subrip in output.created {
+                self.add(subrip);
+            }
+            if let Some(track) = output.export {
+                self.sig_export_track_selected.emit(&track);
+            }
+
+            return;
+        }
+
+        eui.horizontal(|eui| {
+            self.draw_track_filter(eui);
+            self.draw_speaker_filter(eui);
+        });
         eui.separator();
+
+        // Only the shown items are handed to the list, their new order is written back afterwards.
+        let shown_indices = (0..self.item_widgets.len())
+            .filter(|k| self.is_shown(&self.item_widgets[*k].borrow().subrip.borrow()))
+            .collect::<Vec<_>>();
+        let mut shown_items = shown_indices
+            .iter()
+            .map(|k| self.item_widgets[*k].clone())
+            .collect::<Vec<_>>();
+
+        let speakers = self.app_state.borrow().speakers.borrow().clone();
         egui::ScrollArea::vertical().show(eui, |eui| {
             egui_dnd::dnd(eui, "Subrips List").show_vec(
-                &mut self.item_widgets,
+                &mut shown_items,
                 |eui, item, handle, state| {
                     if state.dragged {
                         if let Some(pointer_pos) = ctx.pointer_latest_pos() {
@@ -69,10 +388,14 @@ impl Drawable for SubripListWidget {
                         }
                     }
                     handle.ui(eui, |eui| {
-                        item.borrow_mut().draw(ctx, eui);
+                        item.borrow_mut().draw(eui, &speakers);
                     });
                 },
             );
         });
+
+        for (k, item) in shown_indices.into_iter().zip(shown_items) {
+            self.item_widgets[k] = item;
+        }
     }
 }
@@ -0,0 +1,526 @@
+use crate::prelude::*;
+use crate::subrip::{AssHeader, Subrip, SubripFormat};
+use crate::Timestamp;
+
+use eframe::egui::{Align, Align2, Color32, FontFamily, FontId, Pos2, Rect, Vec2};
+
+/// Font sizes and outlines are given for a video of this height and scaled with the monitor.
+const REFERENCE_HEIGHT: f32 = 720.0;
+/// `PlayResX`/`PlayResY` of a script which doesn't declare them.
+const DEFAULT_PLAY_RES: Vec2 = Vec2::new(384.0, 288.0);
+
+/// How the active subrips look on top of the video.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayStyle {
+    pub visible: bool,
+    pub font_family: FontFamily,
+    pub font_size: f32,
+    pub text_color: Color32,
+    pub outline_width: f32,
+    pub outline_color: Color32,
+    pub background: bool,
+    pub background_color: Color32,
+    /// Margins around the video as a fraction of its size.
+    pub safe_area: f32,
+}
+
+impl Default for OverlayStyle {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            font_family: FontFamily::Proportional,
+            font_size: 36.0,
+            text_color: Color32::WHITE,
+            outline_width: 2.0,
+            outline_color: Color32::BLACK,
+            background: false,
+            background_color: Color32::from_black_alpha(160),
+            safe_area: 0.05,
+        }
+    }
+}
+
+/// Where a cue is drawn, `align` tells which side of its text box `pos` is on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Placement {
+    align: Align2,
+    pos: Pos2,
+}
+
+/// The columns of an ASS `Style:` line which place and size a cue.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct AssStyle {
+    /// In the numpad layout of `\an`
+    alignment: Option<u8>,
+    margin_l: i32,
+    margin_r: i32,
+    margin_v: i32,
+    font_size: Option<f32>,
+}
+
+/// Draws the subrips which are active at the playhead over the video frame.
+#[derive(Default)]
+pub struct SubtitleOverlay {
+    pub style: OverlayStyle,
+}
+
+impl SubtitleOverlay {
+    /// Paint the subrips active at `timestamp` into `rect`, the area of the video frame.
+    pub fn paint(
+        &self,
+        painter: &egui::Painter,
+        rect: Rect,
+        subrips: &[Shared<Subrip>],
+        header: &AssHeader,
+        timestamp: Timestamp,
+    ) {
+        if !self.style.visible {
+            return;
+        }
+
+        let scale = rect.height() / REFERENCE_HEIGHT;
+        let safe_rect = rect.shrink2(rect.size() * self.style.safe_area);
+        let play_res = play_res(header);
+
+        // Cues sharing a placement are stacked away from the anchor.
+        let mut stacks: Vec<(Placement, f32)> = vec![];
+
+        for subrip in active_subrips(subrips, timestamp) {
+            let ass_style = match subrip.format {
+                SubripFormat::ASS => ass_style(header, &subrip.style.name),
+                _ => None,
+            };
+            // Font sizes of ASS styles are given in script pixels.
+            let font_size = ass_style
+                .and_then(|i| i.font_size)
+                .map(|size| size * rect.height() / play_res.y)
+                .unwrap_or(self.style.font_size * scale);
+            let font_id = FontId::new(font_size, self.style.font_family.clone());
+            let line_height = painter.fonts(|fonts| fonts.row_height(&font_id));
+            let placement = placement(
+                &subrip,
+                ass_style.as_ref(),
+                rect,
+                safe_rect,
+                play_res,
+                line_height,
+            );
+            let galleys = plain_text(&subrip.content)
+                .lines()
+                .map(|line| {
+                    painter.layout(
+                        line.to_string(),
+                        font_id.clone(),
+                        self.style.text_color,
+                        safe_rect.width(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let size = Vec2::new(
+                galleys.iter().map(|i| i.size().x).fold(0.0, f32::max),
+                galleys.iter().map(|i| i.size().y).sum(),
+            );
+
+            let offset = match stacks.iter_mut().find(|(i, _)| *i == placement) {
+                Some((_, offset)) => offset,
+                None => {
+                    stacks.push((placement, 0.0));
+                    &mut stacks.last_mut().unwrap().1
+                }
+            };
+            let direction = if placement.align.y() == Align::Max {
+                -1.0
+            } else {
+                1.0
+            };
+            let block = placement
+                .align
+                .anchor_size(placement.pos + Vec2::new(0.0, *offset * direction), size);
+            *offset += size.y;
+
+            let mut y = block.top();
+            for galley in galleys {
+                let x = match placement.align.x() {
+                    Align::Min => block.left(),
+                    Align::Center => block.center().x - galley.size().x / 2.0,
+                    Align::Max => block.right() - galley.size().x,
+                };
+                let pos = Pos2::new(x, y);
+                y += galley.size().y;
+
+                if self.style.background {
+                    painter.rect_filled(
+                        Rect::from_min_size(pos, galley.size()).expand(4.0 * scale),
+                        2.0,
+                        self.style.background_color,
+                    );
+                }
+
+                let outline = self.style.outline_width * scale;
+                if outline > 0.0 {
+                    for (dx, dy) in [
+                        (-1.0, -1.0),
+                        (0.0, -1.0),
+                        (1.0, -1.0),
+                        (-1.0, 0.0),
+                        (1.0, 0.0),
+                        (-1.0, 1.0),
+                        (0.0, 1.0),
+                        (1.0, 1.0),
+                    ] {
+                        painter.galley_with_override_text_color(
+                            pos + Vec2::new(dx, dy) * outline,
+                            galley.clone(),
+                            self.style.outline_color,
+                        );
+                    }
+                }
+                painter.galley(pos, galley, self.style.text_color);
+            }
+        }
+    }
+
+    pub fn settings_ui(&mut self, eui: &mut egui::Ui) {
+        let style = &mut self.style;
+
+        egui::Grid::new("overlay_style_grid")
+            .num_columns(2)
+            .show(eui, |eui| {
+                eui.label("Show");
+                eui.checkbox(&mut style.visible, "");
+                eui.end_row();
+
+                eui.label("Font");
+                egui::ComboBox::from_id_source("overlay_font_family")
+                    .selected_text(style.font_family.to_string())
+                    .show_ui(eui, |eui| {
+                        for family in [FontFamily::Proportional, FontFamily::Monospace] {
+                            let text = family.to_string();
+                            eui.selectable_value(&mut style.font_family, family, text);
+                        }
+                    });
+                eui.end_row();
+
+                eui.label("Size");
+                eui.add(egui::Slider::new(&mut style.font_size, 12.0..=96.0));
+                eui.end_row();
+
+                eui.label("Color");
+                eui.color_edit_button_srgba(&mut style.text_color);
+                eui.end_row();
+
+                eui.label("Outline");
+                eui.horizontal(|eui| {
+                    eui.add(egui::Slider::new(&mut style.outline_width, 0.0..=8.0));
+                    eui.color_edit_button_srgba(&mut style.outline_color);
+                });
+                eui.end_row();
+
+                eui.label("Background");
+                eui.horizontal(|eui| {
+                    eui.checkbox(&mut style.background, "");
+                    eui.color_edit_button_srgba(&mut style.background_color);
+                });
+                eui.end_row();
+
+                eui.label("Safe area");
+                eui.add(
+                    egui::Slider::new(&mut style.safe_area, 0.0..=0.2)
+                        .custom_formatter(|n, _| format!("{:.0}%", n * 100.0)),
+                );
+                eui.end_row();
+            });
+    }
+}
+
+/// Subrips covering `timestamp`, lower ASS layers first so that upper ones are painted on top.
+fn active_subrips(subrips: &[Shared<Subrip>], timestamp: Timestamp) -> Vec<Subrip> {
+    let mut active = subrips
+        .iter()
+        .map(|i| i.borrow())
+        .filter(|i| !i.is_deleted() && i.begin_time <= timestamp && timestamp < i.end_time)
+        .map(|i| i.clone())
+        .collect::<Vec<_>>();
+    active.sort_by_key(|i| (i.style.layer, i.begin_time));
+
+    active
+}
+
+/// The content without ASS override blocks and HTML-like tags.
+fn plain_text(content: &str) -> String {
+    let mut text = String::new();
+    let mut closing = None;
+
+    for c in content.chars() {
+        match (closing, c) {
+            (None, '{') => closing = Some('}'),
+            (None, '<') => closing = Some('>'),
+            (None, c) => text.push(c),
+            (Some(end), c) if c == end => closing = None,
+            _ => {}
+        }
+    }
+
+    text.replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", "\u{a0}")
+}
+
+/// `\an` alignment and `\pos` of the ASS override blocks in `content`.
+fn ass_overrides(content: &str) -> (Option<u8>, Option<Pos2>) {
+    let mut alignment = None;
+    let mut pos = None;
+
+    for block in content.split('{').skip(1) {
+        let block = block.split('}').next().unwrap_or_default();
+        for tag in block.split('\\') {
+            if let Some(n) = tag.strip_prefix("an") {
+                alignment = n.trim().parse::<u8>().ok().filter(|n| (1..=9).contains(n));
+            } else if let Some(args) = tag.strip_prefix("pos(") {
+                let args = args
+                    .trim_end_matches(')')
+                    .split(',')
+                    .filter_map(|i| i.trim().parse::<f32>().ok())
+                    .collect::<Vec<_>>();
+                if let [x, y] = args[..] {
+                    pos = Some(Pos2::new(x, y));
+                }
+            }
+        }
+    }
+
+    (alignment, pos)
+}
+
+/// Map the numpad layout of ASS (`1` is bottom left) to an alignment.
+fn numpad_align(n: u8) -> Align2 {
+    let x = match (n - 1) % 3 {
+        0 => Align::Min,
+        1 => Align::Center,
+        _ => Align::Max,
+    };
+    let y = match (n - 1) / 3 {
+        0 => Align::Max,
+        1 => Align::Center,
+        _ => Align::Min,
+    };
+
+    Align2([x, y])
+}
+
+fn play_res(header: &AssHeader) -> Vec2 {
+    let value = |key: &str| {
+        header.script_info.iter().find_map(|line| {
+            let (k, v) = line.split_once(':')?;
+            (k.trim() == key).then(|| v.trim().parse::<f32>().ok())?
+        })
+    };
+
+    Vec2::new(
+        value("PlayResX").unwrap_or(DEFAULT_PLAY_RES.x),
+        value("PlayResY").unwrap_or(DEFAULT_PLAY_RES.y),
+    )
+}
+
+/// The style named `name` in `header`, cues without a style name use `Default`.
+fn ass_style(header: &AssHeader, name: &str) -> Option<AssStyle> {
+    let name = if name.is_empty() { "Default" } else { name };
+    let column = |key: &str| header.style_format.iter().position(|i| i == key);
+    let style = header.styles.iter().find(|i| {
+        column("Name")
+            .and_then(|k| i.get(k))
+            .is_some_and(|i| i == name)
+    })?;
+    let number = |key: &str| {
+        column(key)
+            .and_then(|k| style.get(k))
+            .and_then(|i| i.trim().parse::<f32>().ok())
+    };
+
+    let mut alignment = number("Alignment").map(|n| n as u8);
+    // SSA counts from the bottom row, adding 4 for the top row and 8 for the middle one.
+    if header.style_section.eq_ignore_ascii_case("[V4 Styles]") {
+        alignment = alignment.map(|n| match n {
+            9.. => n - 5,
+            5.. => n + 2,
+            _ => n,
+        });
+    }
+
+    Some(AssStyle {
+        alignment: alignment.filter(|n| (1..=9).contains(n)),
+        margin_l: number("MarginL").unwrap_or_default() as i32,
+        margin_r: number("MarginR").unwrap_or_default() as i32,
+        margin_v: number("MarginV").unwrap_or_default() as i32,
+        font_size: number("Fontsize"),
+    })
+}
+
+fn percent(value: &str) -> Option<f32> {
+    value.trim().strip_suffix('%')?.parse::<f32>().ok()
+}
+
+/// Honour `\an`/`\pos` overrides, ASS margins and the ASS style of the cue, or WebVTT cue
+/// settings. Anything else sits at the bottom center of the safe area.
+fn placement(
+    subrip: &Subrip,
+    ass_style: Option<&AssStyle>,
+    rect: Rect,
+    safe_rect: Rect,
+    play_res: Vec2,
+    line_height: f32,
+) -> Placement {
+    let (alignment, pos) = ass_overrides(&subrip.content);
+    let align = alignment
+        .or(ass_style.and_then(|i| i.alignment))
+        .map(numpad_align)
+        .unwrap_or(Align2::CENTER_BOTTOM);
+    let scale = rect.size() / play_res;
+
+    if let Some(pos) = pos {
+        return Placement {
+            align,
+            pos: rect.min + pos.to_vec2() * scale,
+        };
+    }
+
+    if subrip.format == SubripFormat::VTT {
+        let settings = &subrip.settings;
+        let x_align = match settings.align.as_deref() {
+            Some("start") | Some("left") => Align::Min,
+            Some("end") | Some("right") => Align::Max,
+            _ => Align::Center,
+        };
+        let x = settings
+            .position
+            .as_deref()
+            .and_then(|i| percent(i.split(',').next().unwrap_or_default()))
+            .map(|p| rect.left() + rect.width() * p / 100.0)
+            .unwrap_or(Align2([x_align, Align::Center]).pos_in_rect(&safe_rect).x);
+
+        let line = settings
+            .line
+            .as_deref()
+            .map(|i| i.split(',').next().unwrap_or_default());
+        let (y_align, y) = match line {
+            Some(line) if percent(line).is_some() => (
+                Align::Min,
+                rect.top() + rect.height() * percent(line).unwrap_or_default() / 100.0,
+            ),
+            Some(line) => match line.trim().parse::<i32>() {
+                Ok(n) if n >= 0 => (Align::Min, safe_rect.top() + n as f32 * line_height),
+                Ok(n) => (
+                    Align::Max,
+                    safe_rect.bottom() + (n + 1) as f32 * line_height,
+                ),
+                Err(_) => (Align::Max, safe_rect.bottom()),
+            },
+            None => (Align::Max, safe_rect.bottom()),
+        };
+
+        return Placement {
+            align: Align2([x_align, y_align]),
+            pos: Pos2::new(x, y),
+        };
+    }
+
+    // Margins of the cue which are 0 fall back to those of its style.
+    let style = &subrip.style;
+    let default = ass_style.copied().unwrap_or_default();
+    let margin = |cue: i32, style: i32| if cue != 0 { cue } else { style } as f32;
+    let (margin_l, margin_r, margin_v) = (
+        margin(style.margin_l, default.margin_l),
+        margin(style.margin_r, default.margin_r),
+        margin(style.margin_v, default.margin_v),
+    );
+    let margin_rect = if subrip.format == SubripFormat::ASS
+        && (margin_l, margin_r, margin_v) != (0.0, 0.0, 0.0)
+    {
+        Rect::from_min_max(
+            rect.min + Vec2::new(margin_l, margin_v) * scale,
+            rect.max - Vec2::new(margin_r, margin_v) * scale,
+        )
+    } else {
+        safe_rect
+    };
+
+    Placement {
+        align,
+        pos: align.pos_in_rect(&margin_rect),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            plain_text("{\\an8}<i>Hello</i>\\Nworld{\\i0}"),
+            "Hello\nworld"
+        );
+    }
+
+    #[test]
+    fn test_placement() {
+        let rect = Rect::from_min_size(Pos2::ZERO, Vec2::new(1000.0, 500.0));
+        let safe_rect = rect.shrink(50.0);
+        let play_res = Vec2::new(100.0, 50.0);
+
+        let mut subrip = Subrip::new("Hello", Timestamp::ZERO, chrono::Duration::seconds(1));
+        let default = placement(&subrip, None, rect, safe_rect, play_res, 10.0);
+        assert_eq!(default.align, Align2::CENTER_BOTTOM);
+        assert_eq!(default.pos, Pos2::new(500.0, 450.0));
+
+        subrip.content = "{\\an7\\pos(10,5)}Hello".to_string();
+        let ass = placement(&subrip, None, rect, safe_rect, play_res, 10.0);
+        assert_eq!(ass.align, Align2::LEFT_TOP);
+        assert_eq!(ass.pos, Pos2::new(100.0, 50.0));
+
+        subrip.content = "Hello".to_string();
+        subrip.format = SubripFormat::VTT;
+        subrip.settings.line = Some("0".to_string());
+        subrip.settings.position = Some("25%".to_string());
+        subrip.settings.align = Some("start".to_string());
+        let vtt = placement(&subrip, None, rect, safe_rect, play_res, 10.0);
+        assert_eq!(vtt.align, Align2::LEFT_TOP);
+        assert_eq!(vtt.pos, Pos2::new(250.0, 50.0));
+    }
+
+    #[test]
+    fn test_ass_style_placement() {
+        let rect = Rect::from_min_size(Pos2::ZERO, Vec2::new(1000.0, 500.0));
+        let play_res = Vec2::new(100.0, 50.0);
+        let columns = |line: &str| line.split(',').map(String::from).collect::<Vec<_>>();
+        let mut header = AssHeader {
+            style_format: columns("Name,Fontsize,Alignment,MarginL,MarginR,MarginV"),
+            styles: vec![columns("Sign,32,8,10,20,5")],
+            ..Default::default()
+        };
+
+        let mut subrip = Subrip::new("Shop", Timestamp::ZERO, chrono::Duration::seconds(1));
+        subrip.format = SubripFormat::ASS;
+        subrip.style.name = "Sign".to_string();
+        let style = ass_style(&header, "Sign").unwrap();
+        assert_eq!(style.font_size, Some(32.0));
+        assert!(ass_style(&header, "Default").is_none());
+
+        // The style places the cue at the top center within its margins.
+        let sign = placement(&subrip, Some(&style), rect, rect, play_res, 10.0);
+        assert_eq!(sign.align, Align2::CENTER_TOP);
+        assert_eq!(sign.pos, Pos2::new(450.0, 50.0));
+
+        // Overrides and margins of the cue come first.
+        subrip.content = "{\\an1}Shop".to_string();
+        subrip.style.margin_v = 10;
+        let cue = placement(&subrip, Some(&style), rect, rect, play_res, 10.0);
+        assert_eq!(cue.align, Align2::LEFT_BOTTOM);
+        assert_eq!(cue.pos, Pos2::new(100.0, 400.0));
+
+        // SSA numbers the top row 5 to 7.
+        header.style_section = "[V4 Styles]".to_string();
+        header.styles[0][2] = "6".to_string();
+        assert_eq!(ass_style(&header, "Sign").unwrap().alignment, Some(8));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::core::thumbnailer::Thumbnailer;
+use crate::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+/// Thumbnails are decoded at this height and scaled to the lane.
+const THUMBNAIL_HEIGHT: u32 = 72;
+/// Candidate intervals between thumbnails in MILLISECONDS.
+const INTERVALS: [i64; 11] = [
+    1000, 2000, 5000, 10_000, 15_000, 30_000, 60_000, 120_000, 300_000, 600_000, 1_800_000,
+];
+/// The least recently painted textures beyond this are dropped, they are reloaded from the
+/// disk cache.
+const MAX_TEXTURES: usize = 512;
+
+/// The shortest interval which leaves room for a thumbnail `thumbnail_ms` wide.
+fn interval_for(thumbnail_ms: f32) -> i64 {
+    INTERVALS
+        .iter()
+        .copied()
+        .find(|i| *i as f32 >= thumbnail_ms)
+        .unwrap_or(INTERVALS[INTERVALS.len() - 1])
+}
+
+/// Drop the entries painted longest ago until `max` are left, the `u64` is the frame in which
+/// an entry was painted last.
+fn evict_least_recent<T>(entries: &mut HashMap<i64, (T, u64)>, max: usize) {
+    if entries.len() <= max {
+        return;
+    }
+
+    let mut last_painted = entries
+        .iter()
+        .map(|(timestamp_ms, (_, frame))| (*frame, *timestamp_ms))
+        .collect::<Vec<_>>();
+    last_painted.sort_unstable();
+    for (_, timestamp_ms) in last_painted.iter().take(entries.len() - max) {
+        entries.remove(timestamp_ms);
+    }
+}
+
+/// The cached thumbnail is the width and the height as little-endian `u32`, then RGB bytes.
+fn write_thumbnail(path: &Path, image: &egui::ColorImage) -> Result<()> {
+    let mut bytes = vec![];
+    bytes.extend((image.width() as u32).to_le_bytes());
+    bytes.extend((image.height() as u32).to_le_bytes());
+    for pixel in image.pixels.iter() {
+        bytes.extend([pixel.r(), pixel.g(), pixel.b()]);
+    }
+
+    fs::write(path, bytes)?;
+
+    Ok(())
+}
+
+fn read_thumbnail(path: &Path) -> Result<egui::ColorImage> {
+    let bytes = fs::read(path)?;
+    let dimension = |k: usize| -> Result<usize> {
+        let le_bytes = bytes
+            .get(k * 4..k * 4 + 4)
+            .ok_or(anyhow!("Truncated thumbnail {}", path.display()))?;
+
+        Ok(u32::from_le_bytes(le_bytes.try_into()?) as usize)
+    };
+    let (width, height) = (dimension(0)?, dimension(1)?);
+
+    let rgb = &bytes[8..];
+    if rgb.len() != width * height * 3 {
+        return Err(anyhow!("Truncated thumbnail {}", path.display()));
+    }
+
+    Ok(egui::ColorImage::from_rgb([width, height], rgb))
+}
+
+/// Serve thumbnails from the disk cache, decode the missing ones. Requests which are no
+/// longer `pending` by the time they're read are skipped.
+fn run_worker(
+    media_path: PathBuf,
+    requests: mpsc::Receiver<i64>,
+    pending: Arc<Mutex<HashSet<i64>>>,
+    sender: mpsc::Sender<(i64, egui::ColorImage)>,
+) -> Result<()> {
+    let cache_dir = dirs::cache_dir()
+        .unwrap_or(PathBuf::from("./"))
+        .join("mksubrip")
+        .join("thumbnails")
+        .join(utils::media_hash(&media_path)?);
+    fs::create_dir_all(&cache_dir)?;
+
+    let mut thumbnailer = None;
+    for timestamp_ms in requests {
+        if !pending.lock().unwrap().contains(&timestamp_ms) {
+            continue;
+        }
+
+        let cache_path = cache_dir.join(format!("{}.rgb", timestamp_ms));
+        let image = match read_thumbnail(&cache_path) {
+            Ok(image) => image,
+            Err(_) => {
+                if thumbnailer.is_none() {
+                    thumbnailer = Some(Thumbnailer::new(&media_path, THUMBNAIL_HEIGHT)?);
+                }
+                let image = match thumbnailer.as_mut().unwrap().thumbnail_at(timestamp_ms) {
+                    Ok(image) => image,
+                    Err(err) => {
+                        warn!(
+                            "Decoding the thumbnail at {}ms failed: {}",
+                            timestamp_ms, err
+                        );
+                        continue;
+                    }
+                };
+                if let Err(err) = write_thumbnail(&cache_path, &image) {
+                    warn!(
+                        "Caching the thumbnail {} failed: {}",
+                        cache_path.display(),
+                        err
+                    );
+                }
+
+                image
+            }
+        };
+
+        if sender.send((timestamp_ms, image)).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// The filmstrip lane of [`crate::ui::Timeline`]. Thumbnails of the visible range are
+/// requested from a worker thread and cached on the disk by the media hash.
+#[derive(Default)]
+pub struct Filmstrip {
+    media_path: Option<PathBuf>,
+    requests: Option<mpsc::Sender<i64>>,
+    receiver: Option<mpsc::Receiver<(i64, egui::ColorImage)>>,
+    /// Requested thumbnails which haven't arrived yet, shared with the worker
+    pending: Arc<Mutex<HashSet<i64>>>,
+    /// Textures and the frame in which they were painted last
+    textures: HashMap<i64, (egui::TextureHandle, u64)>,
+    frame: u64,
+    /// Width over height of the thumbnails
+    aspect_ratio: Option<f32>,
+}
+
+impl Filmstrip {
+    pub fn load(&mut self, path: &Path) {
+        if self.media_path.as_deref() == Some(path) {
+            return;
+        }
+
+        let (request_sender, request_receiver) = mpsc::channel();
+        let (sender, receiver) = mpsc::channel();
+        let pending = Arc::new(Mutex::new(HashSet::new()));
+        let media_path = path.to_path_buf();
+        let worker_pending = pending.clone();
+        thread::spawn(move || {
+            if let Err(err) =
+                run_worker(media_path.clone(), request_receiver, worker_pending, sender)
+            {
+                error!("Thumbnails of {} failed: {}", media_path.display(), err);
+            }
+        });
+
+        // Dropping the former sender stops the former worker.
+        self.media_path = Some(path.to_path_buf());
+        self.requests = Some(request_sender);
+        self.receiver = Some(receiver);
+        self.pending = pending;
+        self.textures.clear();
+        self.aspect_ratio = None;
+    }
+
+    fn request(&mut self, timestamp_ms: i64) {
+        if let Some(requests) = self.requests.as_ref() {
+            if self.pending.lock().unwrap().insert(timestamp_ms) {
+                let _ = requests.send(timestamp_ms);
+            }
+        }
+    }
+
+    /// Upload the thumbnails decoded since the last frame.
+    fn poll(&mut self, ctx: &egui::Context) {
+        if let Some(receiver) = self.receiver.as_ref() {
+            for (timestamp_ms, image) in receiver.try_iter() {
+                self.aspect_ratio
+                    .get_or_insert(image.width() as f32 / image.height().max(1) as f32);
+                let texture = ctx.load_texture(
+                    format!("thumbnail_{}", timestamp_ms),
+                    image,
+                    egui::TextureOptions::LINEAR,
+                );
+                self.pending.lock().unwrap().remove(&timestamp_ms);
+                self.textures.insert(timestamp_ms, (texture, self.frame));
+            }
+        }
+
+        evict_least_recent(&mut self.textures, MAX_TEXTURES);
+    }
+
+    /// Paint thumbnails into `rect`, their interval follows the granularity of the timeline.
+    pub fn paint(
+        &mut self,
+        ctx: &egui::Context,
+        painter: &egui::Painter,
+        rect: egui::Rect,
+        duration_range: &[i64; 2],
+        granularity: f32,
+    ) {
+        self.frame += 1;
+        self.poll(ctx);
+        if self.media_path.is_none() {
+            return;
+        }
+
+        let ms_per_pixel = granularity * 1000.0;
+        let thumbnail_width = rect.height() * self.aspect_ratio.unwrap_or(16.0 / 9.0);
+        let interval = interval_for(thumbnail_width * ms_per_pixel);
+
+        let painter = painter.with_clip_rect(rect);
+        let mut visible = HashSet::new();
+        let mut timestamp_ms = duration_range[0] / interval * interval;
+        while timestamp_ms <= duration_range[1] {
+            visible.insert(timestamp_ms);
+            let x = rect.left() + (timestamp_ms - duration_range[0]) as f32 / ms_per_pixel;
+            let thumbnail_rect = egui::Rect::from_min_size(
+                Pos2::new(x, rect.top()),
+                Vec2::new(thumbnail_width, rect.height()),
+            );
+
+            if let Some((texture, last_painted)) = self.textures.get_mut(&timestamp_ms) {
+                *last_painted = self.frame;
+                painter.image(
+                    texture.id(),
+                    thumbnail_rect,
+                    egui::Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                    egui::Color32::WHITE,
+                );
+            } else {
+                self.request(timestamp_ms);
+                painter.rect_filled(
+                    thumbnail_rect.shrink(1.0),
+                    0.0,
+                    egui::Color32::from_hex("#DDDDDD").unwrap(),
+                );
+            }
+
+            timestamp_ms += interval;
+        }
+
+        // Requests scrolled or zoomed out of sight aren't decoded anymore.
+        self.pending
+            .lock()
+            .unwrap()
+            .retain(|timestamp_ms| visible.contains(timestamp_ms));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_for() {
+        assert_eq!(interval_for(10.0), 1000);
+        assert_eq!(interval_for(1000.0), 1000);
+        assert_eq!(interval_for(7000.0), 10_000);
+        assert_eq!(interval_for(1e9), 1_800_000);
+    }
+
+    #[test]
+    fn test_evict_least_recent() {
+        let mut entries = HashMap::from([(0, ((), 3)), (1000, ((), 1)), (2000, ((), 2))]);
+
+        evict_least_recent(&mut entries, 3);
+        assert_eq!(entries.len(), 3);
+        evict_least_recent(&mut entries, 1);
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec![&0]);
+    }
+
+    #[test]
+    fn test_thumbnail_cache() {
+        let dir = std::env::temp_dir().join("mksubrip_test_thumbnail_cache");
+        fs::create_dir_all(&dir).unwrap();
+
+        let media_path = dir.join("media.bin");
+        fs::write(&media_path, b"not really a video").unwrap();
+        let hash = utils::media_hash(&media_path).unwrap();
+        assert_eq!(hash.len(), 16);
+        assert_eq!(utils::media_hash(&media_path).unwrap(), hash);
+
+        let image = egui::ColorImage::from_rgb([2, 1], &[1, 2, 3, 4, 5, 6]);
+        let thumbnail_path = dir.join("0.rgb");
+        write_thumbnail(&thumbnail_path, &image).unwrap();
+        assert_eq!(read_thumbnail(&thumbnail_path).unwrap(), image);
+
+        fs::write(&thumbnail_path, [2, 0, 0, 0, 1, 0, 0, 0, 1]).unwrap();
+        assert!(read_thumbnail(&thumbnail_path).is_err());
+    }
+}
@@ -1,37 +1,72 @@
+pub mod filmstrip;
+pub mod shot_changes;
+pub mod spectrogram;
 pub mod subrip_block;
+pub mod waveform;
 
 use crate::app::AppState;
 use crate::core::media_player::{self, Player};
+use crate::history::EditHistory;
 use crate::prelude::*;
+use crate::project::TimelineView;
 use crate::ui::Drawable;
 use crate::ui::EditSubripWindow;
 use crate::ui::SubripBlock;
 use crate::Subrip;
 
+use filmstrip::Filmstrip;
+use shot_changes::ShotChanges;
+use spectrogram::Spectrogram;
+use std::collections::HashMap;
+use waveform::Waveform;
+
+/// The lane of the first track starts this far below the top of the timeline.
+const TRACK_LANES_TOP: f32 = 64.0;
+/// Each further track adds a lane this high.
+const TRACK_LANE_HEIGHT: f32 = 56.0;
+const FILMSTRIP_HEIGHT: f32 = 46.0;
+
 #[derive(Default)]
 pub struct Timeline {
     pub sig_video_seeked: Signal<f32>,
+    /// Emitted for each part of a split subrip
+    pub sig_subrip_created: Signal<Shared<Subrip>>,
 
     pub ctx: Option<egui::Context>,
     app_state: Shared<AppState>,
     state: Shared<TimelineState>,
 
-    media_duration_s: i64,
+    media_duration_ms: i64,
+    /// The height with a single track
     default_height: f32,
     /// Refer to that one pixel means several seconds
     granularity: Shared<f32>,
     stroke: egui::Stroke,
     subrip_blocks: Vec<SubripBlock>,
     player: Option<Shared<Player>>,
-    /// The range from begin timestamp to end timestamp in SECONDS
+    /// The range from begin timestamp to end timestamp in MILLISECONDS
     /// example:
     /// ```rust
-    /// // Means from 1000s to 10000s.
+    /// // Means from 1s to 10s.
     /// let duration_range = [1000, 10000];
     /// ```
     duration_range: [i64; 2],
 
     edit_subrip_win: Shared<EditSubripWindow>,
+    history: Shared<EditHistory>,
+    lane: Lane,
+    waveform: Waveform,
+    spectrogram: Spectrogram,
+    filmstrip: Filmstrip,
+    shot_changes: ShotChanges,
+}
+
+/// What is drawn under the subrip blocks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    #[default]
+    Waveform,
+    Spectrogram,
 }
 
 #[derive(Default)]
@@ -44,13 +79,18 @@ struct TimelineState {
 
 impl Timeline {
     pub fn new(app_state: Shared<AppState>) -> Self {
+        let history = app_state.borrow().history.clone();
+        let mut edit_subrip_win = EditSubripWindow::new();
+        edit_subrip_win.set_history(history.clone());
+
         Self {
             app_state,
-            default_height: 120.0,
-            media_duration_s: 0,
+            default_height: 170.0,
+            media_duration_ms: 0,
             granularity: Shared::new(0.1),
             stroke: egui::Stroke::new(2.0, egui::Color32::from_hex("#555555").unwrap()),
-            edit_subrip_win: Shared::new(EditSubripWindow::new()),
+            edit_subrip_win: Shared::new(edit_subrip_win),
+            history,
             ..Self::default()
         }
     }
@@ -87,8 +127,9 @@ impl Timeline {
         let tick_step = self.calc_tick_step();
         let duration_range = &self.duration_range;
         let interval = duration_range[1] - duration_range[0];
-        let begin_tick = (duration_range[0] as f32 * sec_pixs / tick_step).round() as u32;
-        let end_tick = begin_tick + (interval as f32 * sec_pixs / tick_step).round() as u32;
+        let begin_tick = (duration_range[0] as f32 / 1000.0 * sec_pixs / tick_step).round() as u32;
+        let end_tick =
+            begin_tick + (interval as f32 / 1000.0 * sec_pixs / tick_step).round() as u32;
 
         (begin_tick, end_tick)
     }
@@ -103,15 +144,12 @@ impl Timeline {
         if let Some(player) = self.player.as_ref() {
             let borrowed_player = player.borrow();
             let video_streamer = borrowed_player.video_streamer.lock();
-            let elapsed_s = video_streamer.elapsed_ms().get() / 1000;
+            let elapsed_ms = video_streamer.elapsed_ms().get();
 
-            if utils::range_contains_timestamp(&self.duration_range, elapsed_s) {
-                let tick_step = self.calc_tick_step();
+            if utils::range_contains_timestamp(&self.duration_range, elapsed_ms) {
                 let sec_pixs = self.calc_sec_pixels();
-                let cur_tick = (elapsed_s as f32 * sec_pixs / tick_step).round() as u32;
-                let (begin_tick, _) = self.calc_ticks_range();
-                let offset_tick = cur_tick - begin_tick;
-                let offset_x = resp.rect.min.x + offset_tick as f32 * tick_step;
+                let offset_x = resp.rect.min.x
+                    + (elapsed_ms - self.duration_range[0]) as f32 / 1000.0 * sec_pixs;
                 let p0 = Pos2 {
                     x: offset_x,
                     y: resp.rect.min.y,
@@ -169,10 +207,10 @@ impl Timeline {
 
                 if resp.double_clicked() {
                     let offset_x = pointer_pos.x - resp.rect.min.x;
-                    let offset_secs =
-                        (offset_x * self.get_granularity()).min(self.duration_range[1] as f32);
-                    let t = (self.duration_range[0] as f32 + offset_secs)
-                        / self.media_duration_s as f32;
+                    let offset_ms = (offset_x * self.get_granularity() * 1000.0)
+                        .min(self.duration_range[1] as f32);
+                    let t =
+                        (self.duration_range[0] as f32 + offset_ms) / self.media_duration_ms as f32;
                     self.sig_video_seeked.emit(&{ t });
                     info!("Seek to {}", t);
                 }
@@ -237,6 +275,123 @@ impl Timeline {
         }
     }
 
+    /// Draw the waveform or the spectrogram below the ticks, behind the cursor and subrip blocks,
+    /// then the filmstrip at the bottom and the shot changes across both.
+    fn draw_lane(
+        &mut self,
+        ctx: &egui::Context,
+        eui: &mut egui::Ui,
+        painter: &egui::Painter,
+        resp: &egui::Response,
+    ) {
+        let lane_rect = utils::new_rect(
+            resp.rect.left(),
+            resp.rect.top() + 44.0,
+            resp.rect.right(),
+            resp.rect.top() + 120.0,
+        );
+        let granularity = self.get_granularity();
+
+        match self.lane {
+            Lane::Waveform => {
+                self.waveform
+                    .paint(painter, lane_rect, &self.duration_range, granularity)
+            }
+            Lane::Spectrogram => {
+                self.spectrogram
+                    .paint(ctx, painter, lane_rect, &self.duration_range, granularity)
+            }
+        }
+
+        let filmstrip_rect = utils::new_rect(
+            resp.rect.left(),
+            resp.rect.bottom() - FILMSTRIP_HEIGHT,
+            resp.rect.right(),
+            resp.rect.bottom(),
+        );
+        self.filmstrip.paint(
+            ctx,
+            painter,
+            filmstrip_rect,
+            &self.duration_range,
+            granularity,
+        );
+
+        let shot_changes_rect = utils::new_rect(
+            resp.rect.left(),
+            lane_rect.top(),
+            resp.rect.right(),
+            resp.rect.bottom(),
+        );
+        self.shot_changes.paint(
+            painter,
+            shot_changes_rect,
+            &self.duration_range,
+            granularity,
+        );
+
+        let toggle_rect = utils::new_rect(
+            lane_rect.right() - 400.0,
+            lane_rect.top(),
+            lane_rect.right(),
+            lane_rect.top() + 20.0,
+        );
+        let mut toggle_ui = eui.child_ui(
+            toggle_rect,
+            egui::Layout::right_to_left(egui::Align::Center),
+        );
+        toggle_ui.selectable_value(&mut self.lane, Lane::Spectrogram, "Spectrogram");
+        toggle_ui.selectable_value(&mut self.lane, Lane::Waveform, "Waveform");
+
+        let snapping = self.shot_changes.snapping();
+        let mut snapping = snapping.borrow_mut();
+        toggle_ui.add_enabled(
+            snapping.enabled,
+            egui::DragValue::new(&mut snapping.distance_px)
+                .clamp_range(1.0..=64.0)
+                .suffix(" px"),
+        );
+        toggle_ui
+            .checkbox(&mut snapping.enabled, "Snap to shots")
+            .on_hover_text("Hold ALT while dragging an edge to place it freely");
+    }
+
+    /// Draw the name of each track at the left of its lane, the active track is highlighted.
+    fn draw_track_labels(&self, painter: &egui::Painter, resp: &egui::Response) {
+        let app_state = self.app_state.borrow();
+
+        for (k, track) in app_state.tracks.iter().enumerate() {
+            let color = if track.id == app_state.active_track {
+                egui::Color32::from_hex("#1E66F5").unwrap()
+            } else {
+                egui::Color32::from_hex("#999999").unwrap()
+            };
+            let label = if track.language.is_empty() {
+                track.name.clone()
+            } else {
+                format!("{} [{}]", track.name, track.language)
+            };
+
+            painter.text(
+                Pos2::new(
+                    resp.rect.left() + 4.0,
+                    resp.rect.top() + TRACK_LANES_TOP + k as f32 * TRACK_LANE_HEIGHT + 48.0,
+                ),
+                egui::Align2::LEFT_BOTTOM,
+                label,
+                egui::FontId::proportional(11.0),
+                color,
+            );
+        }
+    }
+
+    /// Grow with the number of tracks, one lane per track.
+    fn height(&self) -> f32 {
+        let track_count = self.app_state.borrow().tracks.len().max(1);
+
+        self.default_height + (track_count - 1) as f32 * TRACK_LANE_HEIGHT
+    }
+
     /// Poll and handle input events.
     fn update_input_event(&mut self, ctx: &egui::Context, resp: &egui::Response) {
         if ctx.rect_contains_pointer(resp.layer_id, resp.rect) {
@@ -257,7 +412,8 @@ impl Timeline {
     fn update_duration_range(&mut self, width: f32) {
         let gran = self.get_granularity();
         let begin_timestamp = self.duration_range[0];
-        let end_timestamp = (begin_timestamp + (gran * width) as i64).min(self.media_duration_s);
+        let end_timestamp =
+            (begin_timestamp + (gran * width * 1000.0) as i64).min(self.media_duration_ms);
         self.duration_range[1] = end_timestamp;
     }
 
@@ -266,14 +422,14 @@ impl Timeline {
         // Granularity
         let width = self.app_state.borrow().screen_width;
         // The minimum of the granularity is 1.0
-        let mut gran = self.media_duration_s as f32 / width;
+        let mut gran = self.media_duration_ms as f32 / 1000.0 / width;
         gran = utils::clamp(3.0, 0.016, gran);
         *self.granularity.borrow_mut() = gran;
 
         debug!("Granularity = {}", gran);
 
         // Duration Range
-        self.duration_range = [0, (gran * width) as i64];
+        self.duration_range = [0, (gran * width * 1000.0) as i64];
     }
 
     fn increase_granularity(&mut self) {
@@ -293,14 +449,14 @@ impl Timeline {
         let sec_pixs = self.calc_sec_pixels();
         let tick_step = self.calc_tick_step();
         let tick_secs = tick_step / sec_pixs;
-        let delta_seconds = (sgn as f32 * tick_secs) as i64;
+        let delta_ms = (sgn as f32 * tick_secs * 1000.0) as i64;
         let interval = self.duration_range[1] - self.duration_range[0];
 
-        self.duration_range[0] = 0.max(self.duration_range[0] + delta_seconds);
+        self.duration_range[0] = 0.max(self.duration_range[0] + delta_ms);
         self.duration_range[1] = self.duration_range[0] + interval;
 
-        if self.duration_range[1] > self.media_duration_s {
-            self.duration_range[1] = self.media_duration_s;
+        if self.duration_range[1] > self.media_duration_ms {
+            self.duration_range[1] = self.media_duration_ms;
             self.duration_range[0] = self.duration_range[1] - interval;
         }
     }
@@ -312,33 +468,81 @@ impl Timeline {
         *self.granularity.borrow()
     }
 
+    pub fn view(&self) -> TimelineView {
+        TimelineView {
+            granularity: self.get_granularity(),
+            duration_range: self.duration_range,
+        }
+    }
+
+    pub fn set_view(&mut self, view: &TimelineView) {
+        self.set_granularity(utils::clamp(3.0, 0.016, view.granularity));
+        self.duration_range = view.duration_range;
+    }
+
+    pub fn clear_blocks(&mut self) {
+        self.subrip_blocks.clear();
+    }
+
     pub fn add_block_from_subrip(&mut self, subrip: &Shared<Subrip>) {
         let mut block = SubripBlock::new(subrip.clone());
         block
             .sig_edit_subrip_win_showed
             .connect_method(self.edit_subrip_win.clone(), EditSubripWindow::edit_subrip);
         block.set_granularity(self.granularity.clone());
+        block.set_history(self.history.clone());
+        block.set_app_state(self.app_state.clone());
+        block.set_snapping(self.shot_changes.snapping());
 
         self.subrip_blocks.push(block);
     }
 
-    pub fn set_media_duration_s(&mut self, duration_s: &i64) {
-        info!("ui::TimeLine::media_duration_s = {}", duration_s);
+    /// Replace `subrip` by its two halves, see [`Subrip::split`].
+    fn split_subrip(&mut self, subrip: &Shared<Subrip>) {
+        let Some((first, second)) = subrip.borrow().split() else {
+            warn!("A subrip of a single word can't be split");
+
+            return;
+        };
+
+        let track = subrip.borrow().track;
+        let parts = vec![Shared::new(first), Shared::new(second)];
+        for part in parts.iter() {
+            self.app_state.borrow_mut().add_subrip_to_track(part, track);
+            self.add_block_from_subrip(part);
+            self.sig_subrip_created.emit(part);
+        }
+
+        let command = self.app_state.borrow().split_command(subrip, parts);
+        command.redo();
+        self.history.borrow_mut().push(command);
+    }
 
-        self.media_duration_s = *duration_s;
+    pub fn set_media_duration_ms(&mut self, duration_ms: &i64) {
+        info!("ui::TimeLine::media_duration_ms = {}", duration_ms);
+
+        self.media_duration_ms = *duration_ms;
         self.init();
     }
 
     pub fn set_player(&mut self, player: &Shared<Player>) {
         self.player = Some(player.clone());
         self.init();
+
+        let media_path = self.app_state.borrow().file_path_opening.clone();
+        if let Some(media_path) = media_path {
+            self.waveform.load(&media_path);
+            self.spectrogram.load(&media_path);
+            self.filmstrip.load(&media_path);
+            self.shot_changes.load(&media_path);
+        }
     }
 
-    /// Get current timestamp pointed by the cursor of the timeline in SECONDS.
-    /// In other words, get the elapsed duration in SECONDS.
+    /// Get current timestamp pointed by the cursor of the timeline in MILLISECONDS.
+    /// In other words, get the elapsed duration in MILLISECONDS.
     pub fn get_cursor_timestamp(&self) -> i64 {
         if let Some(player) = self.player.as_ref() {
-            player.borrow().elapsed_ms() / 1000
+            player.borrow().elapsed_ms()
         } else {
             0
         }
@@ -360,19 +564,47 @@ impl Drawable for Timeline {
     fn draw(&mut self, ctx: &egui::Context, eui: &mut egui::Ui) {
         let width = ctx.available_rect().width();
         let (resp, painter) = eui.allocate_painter(
-            Vec2::new(width, self.default_height),
+            Vec2::new(width, self.height()),
             egui::Sense::click_and_drag(),
         );
 
         self.edit_subrip_win.borrow_mut().draw(ctx, eui);
+        let split_request = self.edit_subrip_win.borrow_mut().take_split_request();
+        if let Some(subrip) = split_request {
+            self.split_subrip(&subrip);
+        }
         self.update_input_event(ctx, &resp);
         self.update_duration_range(width);
         self.state.borrow_mut().width = width;
+        self.draw_lane(ctx, eui, &painter, &resp);
         self.draw_cursor(ctx, &painter, &resp);
         self.draw_hovered_cursor(ctx, &painter, &resp);
         self.draw_ticks(ctx, &painter, &resp);
-
+        self.draw_track_labels(&painter, &resp);
+
+        let track_ids = self
+            .app_state
+            .borrow()
+            .tracks
+            .iter()
+            .map(|i| i.id)
+            .collect::<Vec<_>>();
+        let speaker_colors = self
+            .app_state
+            .borrow()
+            .speakers
+            .borrow()
+            .iter()
+            .map(|i| (i.id, i.color32()))
+            .collect::<HashMap<_, _>>();
         for i in self.subrip_blocks.iter_mut() {
+            // Blocks of removed tracks aren't drawn.
+            let Some(lane) = track_ids.iter().position(|id| *id == i.track()) else {
+                continue;
+            };
+            i.set_lane_offset(lane as f32 * TRACK_LANE_HEIGHT);
+            i.set_speaker_color(i.speaker().and_then(|id| speaker_colors.get(&id).copied()));
+
             // if i.is_containsed_in_range(&self.duration_range) {
             //     i.draw(ctx, eui);
             // }
@@ -0,0 +1,282 @@
+use crate::core::video_decoder;
+use crate::prelude::*;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Frames are compared at this size, it's plenty for telling shots apart.
+const FRAME_WIDTH: u32 = 64;
+const FRAME_HEIGHT: u32 = 36;
+/// Frames differing by more than this, from `0.0` to `1.0`, are on both sides of a cut.
+const CUT_THRESHOLD: f32 = 0.12;
+/// A cut closer than this many MILLISECONDS to the former one is a flash rather than a new shot.
+const MIN_SHOT_MS: i64 = 500;
+
+/// The mean absolute difference of two grayscale frames, from `0.0` to `1.0`.
+fn frame_difference(a: &[u8], b: &[u8]) -> f32 {
+    let sum = a
+        .iter()
+        .zip(b.iter())
+        .map(|(a, b)| a.abs_diff(*b) as u64)
+        .sum::<u64>();
+
+    sum as f32 / a.len().max(1) as f32 / 255.0
+}
+
+/// Finds cuts by comparing each frame with the former one.
+#[derive(Default)]
+struct CutDetector {
+    previous: Option<Vec<u8>>,
+    last_cut_ms: Option<i64>,
+}
+
+impl CutDetector {
+    /// Returns the timestamp of the frame if it starts a new shot.
+    fn push(&mut self, timestamp_ms: i64, pixels: &[u8]) -> Option<i64> {
+        let difference = self
+            .previous
+            .as_ref()
+            .map(|previous| frame_difference(previous, pixels));
+        self.previous = Some(pixels.to_vec());
+
+        let is_cut = difference? > CUT_THRESHOLD
+            && self
+                .last_cut_ms
+                .is_none_or(|last_cut_ms| timestamp_ms - last_cut_ms >= MIN_SHOT_MS);
+        if is_cut {
+            self.last_cut_ms = Some(timestamp_ms);
+        }
+
+        is_cut.then_some(timestamp_ms)
+    }
+}
+
+/// The cut nearest to `timestamp_ms` within `distance_ms`, otherwise `timestamp_ms` itself.
+/// `cuts` must be sorted.
+pub fn snap(timestamp_ms: i64, cuts: &[i64], distance_ms: i64) -> i64 {
+    let index = cuts.partition_point(|i| *i < timestamp_ms);
+    let nearest = [index.checked_sub(1), Some(index)]
+        .into_iter()
+        .flatten()
+        .filter_map(|i| cuts.get(i))
+        .min_by_key(|cut| (*cut - timestamp_ms).abs());
+
+    match nearest {
+        Some(cut) if (cut - timestamp_ms).abs() <= distance_ms => *cut,
+        _ => timestamp_ms,
+    }
+}
+
+fn cache_path(media_path: &Path) -> Result<PathBuf> {
+    let cache_dir = dirs::cache_dir()
+        .unwrap_or(PathBuf::from("./"))
+        .join("mksubrip")
+        .join("shot_changes");
+    fs::create_dir_all(&cache_dir)?;
+
+    Ok(cache_dir.join(format!("{}.json", utils::media_hash(media_path)?)))
+}
+
+/// Send the cached cuts, or detect them and cache them once the whole video is scanned.
+fn run_worker(
+    media_path: PathBuf,
+    cancel: Arc<AtomicBool>,
+    sender: mpsc::Sender<i64>,
+) -> Result<()> {
+    let cache_path = cache_path(&media_path)?;
+    if let Ok(json_str) = fs::read_to_string(&cache_path) {
+        let cuts: Vec<i64> = serde_json::from_str(&json_str)?;
+        for cut in cuts {
+            let _ = sender.send(cut);
+        }
+
+        return Ok(());
+    }
+
+    let mut detector = CutDetector::default();
+    let mut cuts = vec![];
+    video_decoder::decode_gray(
+        &media_path,
+        FRAME_WIDTH,
+        FRAME_HEIGHT,
+        |timestamp_ms, pixels| {
+            if let Some(cut) = detector.push(timestamp_ms, pixels) {
+                cuts.push(cut);
+                if sender.send(cut).is_err() {
+                    return false;
+                }
+            }
+
+            !cancel.load(Ordering::Relaxed)
+        },
+    )?;
+
+    if !cancel.load(Ordering::Relaxed) {
+        fs::write(&cache_path, serde_json::to_string(&cuts)?)?;
+        info!("{} shot changes in {}", cuts.len(), media_path.display());
+    }
+
+    Ok(())
+}
+
+/// How [`crate::ui::SubripBlock`]s snap to shot changes while dragging.
+pub struct Snapping {
+    /// Timestamps of the cuts in MILLISECONDS, sorted
+    pub cuts: Vec<i64>,
+    pub enabled: bool,
+    /// Edges within this many pixels of a cut snap to it
+    pub distance_px: f32,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Self {
+            cuts: vec![],
+            enabled: true,
+            distance_px: 8.0,
+        }
+    }
+}
+
+impl Snapping {
+    /// Snap `timestamp_ms` at the given granularity of the timeline.
+    pub fn snap(&self, timestamp_ms: i64, granularity: f32) -> i64 {
+        if !self.enabled {
+            return timestamp_ms;
+        }
+
+        let distance_ms = (self.distance_px * granularity * 1000.0) as i64;
+        snap(timestamp_ms, &self.cuts, distance_ms)
+    }
+
+    /// How far to shift `begin_ms..end_ms` to snap whichever edge is nearer to a cut,
+    /// zero without a cut nearby.
+    pub fn snap_shift(&self, begin_ms: i64, end_ms: i64, granularity: f32) -> i64 {
+        [begin_ms, end_ms]
+            .into_iter()
+            .map(|edge_ms| self.snap(edge_ms, granularity) - edge_ms)
+            .filter(|shift| *shift != 0)
+            .min_by_key(|shift| shift.abs())
+            .unwrap_or_default()
+    }
+}
+
+/// Shot changes of the media, detected on a background thread and cached on the disk by the
+/// media hash. They are drawn as markers on [`crate::ui::Timeline`].
+#[derive(Default)]
+pub struct ShotChanges {
+    media_path: Option<PathBuf>,
+    receiver: Option<mpsc::Receiver<i64>>,
+    cancel: Arc<AtomicBool>,
+    snapping: Shared<Snapping>,
+}
+
+impl ShotChanges {
+    /// Start detecting the shot changes of `path`, the cuts of the same media are reused.
+    pub fn load(&mut self, path: &Path) {
+        if self.media_path.as_deref() == Some(path) {
+            return;
+        }
+
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::new(AtomicBool::new(false));
+        self.media_path = Some(path.to_path_buf());
+        self.snapping.borrow_mut().cuts.clear();
+
+        let (sender, receiver) = mpsc::channel();
+        let cancel = self.cancel.clone();
+        let media_path = path.to_path_buf();
+        thread::spawn(move || {
+            if let Err(err) = run_worker(media_path.clone(), cancel, sender) {
+                error!("Shot changes of {} failed: {}", media_path.display(), err);
+            }
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// Shared with the subrip blocks, the cuts fill up while detecting goes on.
+    pub fn snapping(&self) -> Shared<Snapping> {
+        self.snapping.clone()
+    }
+
+    /// Collect the cuts detected since the last frame.
+    fn poll(&mut self) {
+        if let Some(receiver) = self.receiver.as_ref() {
+            let mut snapping = self.snapping.borrow_mut();
+            for cut in receiver.try_iter() {
+                let index = snapping.cuts.partition_point(|i| *i < cut);
+                snapping.cuts.insert(index, cut);
+            }
+        }
+    }
+
+    /// Paint a marker for each cut in `rect`.
+    pub fn paint(
+        &mut self,
+        painter: &egui::Painter,
+        rect: egui::Rect,
+        duration_range: &[i64; 2],
+        granularity: f32,
+    ) {
+        self.poll();
+
+        let ms_per_pixel = granularity * 1000.0;
+        let stroke = egui::Stroke::new(1.0, egui::Color32::from_hex("#FAB387").unwrap());
+        let snapping = self.snapping.borrow();
+        let first = snapping.cuts.partition_point(|i| *i < duration_range[0]);
+        for cut in snapping.cuts[first..]
+            .iter()
+            .take_while(|i| **i <= duration_range[1])
+        {
+            let x = rect.left() + (cut - duration_range[0]) as f32 / ms_per_pixel;
+            painter.line_segment(
+                [Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())],
+                stroke,
+            );
+            painter.circle_filled(Pos2::new(x, rect.top()), 2.5, stroke.color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cut_detector() {
+        let dark = vec![10; 16];
+        let bright = vec![200; 16];
+        let mut detector = CutDetector::default();
+
+        assert_eq!(detector.push(0, &dark), None);
+        assert_eq!(detector.push(40, &dark), None);
+        assert_eq!(detector.push(80, &bright), Some(80));
+        // A flash right after a cut isn't a new shot.
+        assert_eq!(detector.push(120, &dark), None);
+        assert_eq!(detector.push(1000, &bright), Some(1000));
+    }
+
+    #[test]
+    fn test_snap() {
+        let cuts = [1000, 2000, 5000];
+        assert_eq!(snap(1040, &cuts, 50), 1000);
+        assert_eq!(snap(1960, &cuts, 50), 2000);
+        assert_eq!(snap(1500, &cuts, 50), 1500);
+        assert_eq!(snap(9000, &cuts, 50), 9000);
+        assert_eq!(snap(9000, &[], 50), 9000);
+
+        let mut snapping = Snapping {
+            cuts: cuts.to_vec(),
+            ..Default::default()
+        };
+        // 8 pixels at 10 ms per pixel
+        assert_eq!(snapping.snap(4930, 0.01), 5000);
+        assert_eq!(snapping.snap_shift(2980, 4960, 0.01), 40);
+        assert_eq!(snapping.snap_shift(2000, 3000, 0.01), 0);
+        snapping.enabled = false;
+        assert_eq!(snapping.snap(4930, 0.01), 4930);
+    }
+}
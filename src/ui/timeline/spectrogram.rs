@@ -0,0 +1,331 @@
+use crate::core::audio_decoder;
+use crate::prelude::*;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Speech sits below 8 kHz, so 16 kHz is enough.
+const SAMPLE_RATE: u32 = 16000;
+const FFT_SIZE: usize = 512;
+/// One column of the spectrogram per this many MILLISECONDS.
+const HOP_MS: i64 = 20;
+const HOP_SIZE: usize = (SAMPLE_RATE as i64 * HOP_MS / 1000) as usize;
+/// Frequency bins of a column, neighbouring FFT bins are merged.
+const BINS: usize = 128;
+/// The quietest level drawn, in dB relative to a full scale sine.
+const FLOOR_DB: f32 = -90.0;
+
+/// Width in pixels of a cached texture.
+const TILE_WIDTH: usize = 256;
+/// The textures of at most this many zoom levels are kept.
+const MAX_ZOOM_LEVELS: usize = 4;
+
+/// Levels of the frequency bins in one column, `0` is [`FLOOR_DB`] and `255` full scale.
+type Column = [u8; BINS];
+
+/// In-place radix-2 FFT, the length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let tr = re[b] * cos - im[b] * sin;
+                let ti = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Runs a short-time Fourier transform over the decoded samples.
+struct ColumnBuilder {
+    pending: Vec<f32>,
+    window: Vec<f32>,
+}
+
+impl Default for ColumnBuilder {
+    fn default() -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+
+        // Pad the beginning so that the column `k` is centered at `k * HOP_MS`.
+        Self {
+            pending: vec![0.0; FFT_SIZE / 2],
+            window,
+        }
+    }
+}
+
+impl ColumnBuilder {
+    fn push(&mut self, samples: &[f32]) -> Vec<Column> {
+        self.pending.extend_from_slice(samples);
+
+        let mut columns = vec![];
+        let mut offset = 0;
+        while self.pending.len() - offset >= FFT_SIZE {
+            columns.push(self.column(&self.pending[offset..offset + FFT_SIZE]));
+            offset += HOP_SIZE;
+        }
+        self.pending.drain(..offset);
+
+        columns
+    }
+
+    fn column(&self, samples: &[f32]) -> Column {
+        let mut re = samples
+            .iter()
+            .zip(self.window.iter())
+            .map(|(s, w)| s * w)
+            .collect::<Vec<_>>();
+        let mut im = vec![0.0; FFT_SIZE];
+        fft(&mut re, &mut im);
+
+        // A full scale sine peaks at `FFT_SIZE / 4` with the Hann window.
+        let full_scale = FFT_SIZE as f32 / 4.0;
+        let merged = FFT_SIZE / 2 / BINS;
+        let mut column = [0; BINS];
+        for (bin, level) in column.iter_mut().enumerate() {
+            let magnitude = (bin * merged..(bin + 1) * merged)
+                .map(|k| (re[k] * re[k] + im[k] * im[k]).sqrt())
+                .fold(0.0f32, f32::max);
+            let db = 20.0 * (magnitude / full_scale + 1e-9).log10();
+            *level = ((db - FLOOR_DB) / -FLOOR_DB * 255.0).clamp(0.0, 255.0) as u8;
+        }
+
+        column
+    }
+}
+
+/// Map a level to a black-blue-purple-orange-yellow gradient.
+fn colormap(level: u8) -> egui::Color32 {
+    const STOPS: [(f32, [u8; 3]); 5] = [
+        (0.0, [0, 0, 0]),
+        (64.0, [30, 30, 90]),
+        (128.0, [140, 40, 160]),
+        (192.0, [240, 120, 50]),
+        (255.0, [255, 245, 160]),
+    ];
+
+    let level = level as f32;
+    let k = STOPS
+        .windows(2)
+        .position(|i| level <= i[1].0)
+        .unwrap_or(STOPS.len() - 2);
+    let ((l0, c0), (l1, c1)) = (STOPS[k], STOPS[k + 1]);
+    let t = (level - l0) / (l1 - l0);
+    let mix = |i: usize| (c0[i] as f32 + (c1[i] as f32 - c0[i] as f32) * t).round() as u8;
+
+    egui::Color32::from_rgb(mix(0), mix(1), mix(2))
+}
+
+struct Tile {
+    texture: egui::TextureHandle,
+    /// The number of decoded columns when the texture was rendered.
+    columns_len: usize,
+}
+
+/// The spectrogram lane of [`crate::ui::Timeline`]. The audio is analyzed on a background
+/// thread once the lane is shown, the textures are cached per zoom level.
+#[derive(Default)]
+pub struct Spectrogram {
+    media_path: Option<PathBuf>,
+    columns: Vec<Column>,
+    receiver: Option<mpsc::Receiver<Vec<Column>>>,
+    cancel: Arc<AtomicBool>,
+    /// Tiles by their index, by MILLISECONDS per pixel.
+    tiles: HashMap<i64, HashMap<i64, Tile>>,
+}
+
+impl Spectrogram {
+    /// Forget the current media, the analysis of `path` starts when the lane is painted.
+    pub fn load(&mut self, path: &Path) {
+        if self.media_path.as_deref() == Some(path) {
+            return;
+        }
+
+        self.cancel.store(true, Ordering::Relaxed);
+        self.media_path = Some(path.to_path_buf());
+        self.columns.clear();
+        self.receiver = None;
+        self.tiles.clear();
+    }
+
+    fn start(&mut self, path: PathBuf) {
+        let (sender, receiver) = mpsc::channel();
+        self.cancel = Arc::new(AtomicBool::new(false));
+        let cancel = self.cancel.clone();
+        thread::spawn(move || {
+            let mut builder = ColumnBuilder::default();
+            let result = audio_decoder::decode_mono(&path, SAMPLE_RATE, |samples| {
+                let columns = builder.push(samples);
+                !cancel.load(Ordering::Relaxed)
+                    && (columns.is_empty() || sender.send(columns).is_ok())
+            });
+
+            if let Err(err) = result {
+                error!("Decoding the audio of {} failed: {}", path.display(), err);
+            }
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// Collect the columns analyzed since the last frame.
+    fn poll(&mut self) {
+        if let Some(receiver) = self.receiver.as_ref() {
+            for columns in receiver.try_iter() {
+                self.columns.extend(columns);
+            }
+        }
+    }
+
+    fn render_tile(&self, ms_per_pixel: i64, index: i64) -> egui::ColorImage {
+        let mut image = egui::ColorImage::new([TILE_WIDTH, BINS], egui::Color32::TRANSPARENT);
+
+        for x in 0..TILE_WIDTH {
+            let begin_ms = (index * TILE_WIDTH as i64 + x as i64) * ms_per_pixel;
+            let first = (begin_ms / HOP_MS) as usize;
+            let last = ((begin_ms + ms_per_pixel + HOP_MS - 1) / HOP_MS) as usize;
+            let columns = match self.columns.get(first..last.max(first + 1)) {
+                Some(columns) => columns,
+                None => match self.columns.get(first..) {
+                    Some(columns) if !columns.is_empty() => columns,
+                    _ => break,
+                },
+            };
+
+            for bin in 0..BINS {
+                let level = columns.iter().map(|i| i[bin]).max().unwrap_or_default();
+                // Low frequencies at the bottom
+                image[(x, BINS - 1 - bin)] = colormap(level);
+            }
+        }
+
+        image
+    }
+
+    /// Paint the lane into `rect` with the same mapping of time to pixels as the timeline.
+    pub fn paint(
+        &mut self,
+        ctx: &egui::Context,
+        painter: &egui::Painter,
+        rect: egui::Rect,
+        duration_range: &[i64; 2],
+        granularity: f32,
+    ) {
+        if self.receiver.is_none() {
+            if let Some(path) = self.media_path.clone() {
+                self.start(path);
+            }
+        }
+        self.poll();
+
+        let ms_per_pixel = ((granularity * 1000.0).round() as i64).max(1);
+        let tile_ms = ms_per_pixel * TILE_WIDTH as i64;
+        let pixels_per_ms = 1.0 / (granularity * 1000.0);
+
+        if !self.tiles.contains_key(&ms_per_pixel) && self.tiles.len() >= MAX_ZOOM_LEVELS {
+            self.tiles.clear();
+        }
+
+        let painter = painter.with_clip_rect(rect);
+        for index in duration_range[0] / tile_ms..=duration_range[1] / tile_ms {
+            let tile_end_column = ((index + 1) * tile_ms / HOP_MS) as usize;
+            let columns_len = self.columns.len();
+            // Re-render a tile which was incomplete when more of it has been analyzed.
+            let is_stale = match self.tiles.get(&ms_per_pixel).and_then(|i| i.get(&index)) {
+                Some(tile) => tile.columns_len < tile_end_column && columns_len > tile.columns_len,
+                None => true,
+            };
+
+            if is_stale {
+                let image = self.render_tile(ms_per_pixel, index);
+                let texture = ctx.load_texture(
+                    format!("spectrogram_{}_{}", ms_per_pixel, index),
+                    image,
+                    egui::TextureOptions::LINEAR,
+                );
+                self.tiles.entry(ms_per_pixel).or_default().insert(
+                    index,
+                    Tile {
+                        texture,
+                        columns_len,
+                    },
+                );
+            }
+
+            if let Some(tile) = self.tiles.get(&ms_per_pixel).and_then(|i| i.get(&index)) {
+                let x = rect.left() + (index * tile_ms - duration_range[0]) as f32 * pixels_per_ms;
+                painter.image(
+                    tile.texture.id(),
+                    egui::Rect::from_min_max(
+                        Pos2::new(x, rect.top()),
+                        Pos2::new(x + tile_ms as f32 * pixels_per_ms, rect.bottom()),
+                    ),
+                    egui::Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                    egui::Color32::WHITE,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spectrogram_column() {
+        // A full scale sine right at the 40th bin.
+        let frequency = 40.0 * SAMPLE_RATE as f32 / BINS as f32 / 2.0;
+        let samples = (0..FFT_SIZE * 2)
+            .map(|i| (2.0 * std::f32::consts::PI * frequency * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect::<Vec<_>>();
+
+        let mut builder = ColumnBuilder::default();
+        let columns = builder.push(&samples);
+        assert_eq!(
+            columns.len(),
+            (FFT_SIZE / 2 + samples.len() - FFT_SIZE) / HOP_SIZE + 1
+        );
+
+        let column = columns.last().unwrap();
+        let loudest = (0..BINS).max_by_key(|i| column[*i]).unwrap();
+        assert_eq!(loudest, 40);
+        assert!(column[loudest] > 250);
+        assert!(column[100] < 128);
+    }
+
+    #[test]
+    fn test_colormap() {
+        assert_eq!(colormap(0), egui::Color32::from_rgb(0, 0, 0));
+        assert_eq!(colormap(128), egui::Color32::from_rgb(140, 40, 160));
+        assert_eq!(colormap(255), egui::Color32::from_rgb(255, 245, 160));
+    }
+}
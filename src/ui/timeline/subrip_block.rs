@@ -1,13 +1,18 @@
-use chrono::Timelike;
-
+use crate::app::AppState;
+use crate::history::EditHistory;
 use crate::prelude::*;
+use crate::ui::timeline::shot_changes::Snapping;
 use crate::ui::Drawable;
-use crate::Subrip;
+use crate::{SpeakerId, Subrip, Timestamp, TrackId};
 
 const BORDER_NORMAL_WIDTH: f32 = 1.0;
 const BORDER_HOVER_RANGE: f32 = 8.0;
 const BORDER_HOVERED_WIDTH: f32 = 2.0;
 const BLOCK_HEIGHT: f32 = 50.0;
+const WORD_TICK_HEIGHT: f32 = 8.0;
+const SPEAKER_BAR_HEIGHT: f32 = 4.0;
+/// The shortest a subrip gets by dragging one of its edges, in MILLISECONDS
+const MIN_DURATION_MS: i64 = 100;
 
 // FIXME:
 // 1. The display for data of the subrip is exceptional.
@@ -19,6 +24,12 @@ pub struct SubripBlock {
 
     granularity: Shared<f32>,
     subrip: Shared<Subrip>,
+    history: Shared<EditHistory>,
+    /// For the subrips linked to the subrip, which follow its timing
+    app_state: Shared<AppState>,
+    snapping: Shared<Snapping>,
+    /// The colour of the speaker of the subrip, if it has one
+    speaker_color: Option<egui::Color32>,
 }
 
 pub struct SubripBlockState {
@@ -31,6 +42,8 @@ pub struct SubripBlockState {
     left_drag_start: Pos2,
     right_dragging: bool,
     right_drag_start: Pos2,
+    /// `(begin_time, end_time)` of the subrip when the current drag started
+    drag_origin: (Timestamp, Timestamp),
 }
 
 impl SubripBlock {
@@ -40,6 +53,10 @@ impl SubripBlock {
             state: SubripBlockState::new(),
             subrip: data,
             granularity: Shared::new(1.0),
+            history: Shared::default(),
+            app_state: Shared::default(),
+            snapping: Shared::default(),
+            speaker_color: None,
         }
     }
 
@@ -47,13 +64,86 @@ impl SubripBlock {
         self.granularity = granularity;
     }
 
-    /// Get the begin timestamp and end timestamp of [`Subrip`] in SECONDS
+    pub fn set_history(&mut self, history: Shared<EditHistory>) {
+        self.history = history;
+    }
+
+    pub fn set_app_state(&mut self, app_state: Shared<AppState>) {
+        self.app_state = app_state;
+    }
+
+    pub fn set_snapping(&mut self, snapping: Shared<Snapping>) {
+        self.snapping = snapping;
+    }
+
+    /// The edge dragged to `timestamp_ms`, snapped to a nearby shot change unless ALT is held.
+    fn snap_edge(&self, ctx: &egui::Context, timestamp_ms: i64) -> i64 {
+        if ctx.input(|i| i.modifiers.alt) {
+            timestamp_ms
+        } else {
+            self.snapping
+                .borrow()
+                .snap(timestamp_ms, self.get_granularity())
+        }
+    }
+
+    /// `(begin_time, end_time)` once the pointer moved `drag_delta` pixels since the drag
+    /// started. A dragged body snaps by whichever of its edges is nearer to a shot change, a
+    /// dragged edge stops [`MIN_DURATION_MS`] short of the other one even after snapping.
+    fn dragged_times(&self, ctx: &egui::Context, drag_delta: f32) -> (Timestamp, Timestamp) {
+        let delta_ms = (drag_delta / self.calc_sec_pixels() * 1000.0).round() as i64;
+        let begin_ms = self.state.drag_origin.0.as_millis();
+        let end_ms = self.state.drag_origin.1.as_millis();
+
+        let (begin_ms, end_ms) = if self.state.body_dragging {
+            let shift = if ctx.input(|i| i.modifiers.alt) {
+                0
+            } else {
+                self.snapping.borrow().snap_shift(
+                    begin_ms + delta_ms,
+                    end_ms + delta_ms,
+                    self.get_granularity(),
+                )
+            };
+
+            (begin_ms + delta_ms + shift, end_ms + delta_ms + shift)
+        } else if self.state.left_dragging {
+            let begin_ms = self.snap_edge(ctx, begin_ms + delta_ms);
+            (begin_ms.min(end_ms - MIN_DURATION_MS), end_ms)
+        } else if self.state.right_dragging {
+            let end_ms = self.snap_edge(ctx, end_ms + delta_ms);
+            (begin_ms, end_ms.max(begin_ms + MIN_DURATION_MS))
+        } else {
+            (begin_ms, end_ms)
+        };
+
+        (
+            Timestamp::from_millis(begin_ms),
+            Timestamp::from_millis(end_ms),
+        )
+    }
+
+    /// Record a whole drag as a single edit, the subrips linked to the subrip follow it.
+    /// The subrip must not be borrowed.
+    fn push_retime(&self, new: (Timestamp, Timestamp)) {
+        if new != self.state.drag_origin {
+            let command =
+                self.app_state
+                    .borrow()
+                    .retime_command(&self.subrip, self.state.drag_origin, new);
+            command.redo();
+            self.history.borrow_mut().push(command);
+        }
+    }
+
+    /// Get the begin timestamp and end timestamp of [`Subrip`] in MILLISECONDS
     pub fn get_duration_range(&self) -> [i64; 2] {
         let borrowed_subrip = self.subrip.borrow();
-        let begin_timestamp_s = borrowed_subrip.get_begin_time().num_seconds_from_midnight() as i64;
-        let end_timestamp_s = begin_timestamp_s + borrowed_subrip.get_duration().num_seconds();
+        let begin_timestamp_ms = borrowed_subrip.get_begin_time().as_millis();
+        let end_timestamp_ms =
+            begin_timestamp_ms + borrowed_subrip.get_duration().num_milliseconds();
 
-        [begin_timestamp_s, end_timestamp_s]
+        [begin_timestamp_ms, end_timestamp_ms]
     }
 
     /// Check if the duration range is contained in the given range.
@@ -107,10 +197,27 @@ impl SubripBlock {
         }
     }
 
+    pub fn track(&self) -> TrackId {
+        self.subrip.borrow().track
+    }
+
+    /// Move the block to the lane `offset_y` pixels below the lane of the first track.
+    pub fn set_lane_offset(&mut self, offset_y: f32) {
+        self.state.pos.y = offset_y;
+    }
+
     pub fn is_deleted(&self) -> bool {
         self.subrip.borrow().is_deleted()
     }
 
+    pub fn speaker(&self) -> Option<SpeakerId> {
+        self.subrip.borrow().speaker
+    }
+
+    pub fn set_speaker_color(&mut self, color: Option<egui::Color32>) {
+        self.speaker_color = color;
+    }
+
     pub fn draw_on_timeline(
         &mut self,
         ctx: &egui::Context,
@@ -119,15 +226,15 @@ impl SubripBlock {
         duration_range: &[i64; 2],
     ) {
         let mut subrip = self.subrip.borrow_mut();
-        let begin_timestamp = subrip.begin_time.num_seconds_from_midnight() as i64;
-        let end_timestamp = subrip.end_time.num_seconds_from_midnight() as i64;
+        let begin_timestamp = subrip.begin_time.as_millis();
+        let end_timestamp = subrip.end_time.as_millis();
         if begin_timestamp > duration_range[1] || end_timestamp < duration_range[0] {
             return;
         }
 
         let sec_pixs = self.calc_sec_pixels();
-        let delta_pixs = (begin_timestamp - duration_range[0]) as f32 * sec_pixs;
-        let duration_pixs = (end_timestamp - begin_timestamp) as f32 * sec_pixs;
+        let delta_pixs = (begin_timestamp - duration_range[0]) as f32 / 1000.0 * sec_pixs;
+        let duration_pixs = (end_timestamp - begin_timestamp) as f32 / 1000.0 * sec_pixs;
         let exposed_pixs = if delta_pixs < 0.0 {
             duration_pixs + delta_pixs
         } else {
@@ -180,6 +287,21 @@ impl SubripBlock {
             );
         }
 
+        if let Some(color) = self.speaker_color {
+            painter.rect_filled(
+                egui::Rect::from_points(&[
+                    [paint_rect.left() + BORDER_NORMAL_WIDTH, paint_rect.top()].into(),
+                    [
+                        paint_rect.left() + width - BORDER_NORMAL_WIDTH,
+                        paint_rect.top() + SPEAKER_BAR_HEIGHT,
+                    ]
+                    .into(),
+                ]),
+                egui::Rounding::default(),
+                color,
+            );
+        }
+
         painter.rect_filled(
             egui::Rect::from_points(&[
                 [paint_rect.left(), paint_rect.top()].into(),
@@ -234,6 +356,25 @@ impl SubripBlock {
             egui::Color32::PLACEHOLDER,
         );
 
+        // Tick where each timed word begins, along the bottom of the block.
+        if let Some(lines) = subrip.timed_lines() {
+            let stroke = egui::Stroke::new(1.0, egui::Color32::from_hex("#888").unwrap());
+            for (_, word) in lines.iter().flatten().skip(1) {
+                let word_ms = begin_timestamp + word.begin.as_millis();
+                let x =
+                    timeline_rect.left() + (word_ms - duration_range[0]) as f32 / 1000.0 * sec_pixs;
+                if x > paint_rect.left() && x < paint_rect.right() {
+                    painter.line_segment(
+                        [
+                            Pos2::new(x, paint_rect.bottom() - WORD_TICK_HEIGHT),
+                            Pos2::new(x, paint_rect.bottom()),
+                        ],
+                        stroke,
+                    );
+                }
+            }
+        }
+
         if self.is_hovered_right(&resp) {
             painter.rect_filled(
                 egui::Rect::from_points(&[
@@ -253,6 +394,10 @@ impl SubripBlock {
             );
         }
 
+        if resp.drag_started() {
+            self.state.drag_origin = (subrip.begin_time, subrip.end_time);
+        }
+
         if self.is_hovered_left(&resp) && resp.drag_started() {
             if let Some(new_drag_start_pos) = resp.interact_pointer_pos() {
                 self.state.left_dragging = true;
@@ -276,36 +421,21 @@ impl SubripBlock {
             }
         }
 
-        if self.state.body_dragging {
-            if let Some(new_drag_new_pos) = resp.interact_pointer_pos() {
-                let drag_delta = new_drag_new_pos.x - self.state.body_drag_start.x;
-                // let time_delta = drag_delta * (*self.granularity.borrow());
-                let time_delta = drag_delta / self.calc_sec_pixels();
-                subrip.add_begin_delta(time_delta);
-                subrip.add_end_delta(time_delta);
-                // self.state.pos.x += drag_delta;
-                self.state.body_drag_start.x = new_drag_new_pos.x;
-            }
-        } else if self.state.left_dragging {
-            if let Some(new_drag_new_pos) = resp.interact_pointer_pos() {
-                let drag_delta = new_drag_new_pos.x - self.state.left_drag_start.x;
-                // let time_delta = drag_delta * (*self.granularity.borrow());
-                let time_delta = drag_delta / self.calc_sec_pixels();
-                // self.state.pos.x += time_delta;
-                subrip.add_begin_delta(time_delta);
-                self.state.left_drag_start.x = new_drag_new_pos.x;
-            }
-        } else if self.state.right_dragging {
+        // Blocks follow the pointer from where the drag started, so snapping doesn't drift.
+        if let Some(drag_start) = self.state.drag_start() {
             if let Some(new_drag_new_pos) = resp.interact_pointer_pos() {
-                let drag_delta = new_drag_new_pos.x - self.state.right_drag_start.x;
-                // let time_delta = drag_delta * (*self.granularity.borrow());
-                let time_delta = drag_delta / self.calc_sec_pixels();
-                subrip.add_end_delta(time_delta);
-                self.state.right_drag_start.x = new_drag_new_pos.x;
+                let (begin_time, end_time) =
+                    self.dragged_times(ctx, new_drag_new_pos.x - drag_start.x);
+                subrip.begin_time = begin_time;
+                subrip.end_time = end_time;
             }
         }
 
+        let mut retimed = None;
         if resp.drag_stopped() {
+            if self.state.body_dragging || self.state.left_dragging || self.state.right_dragging {
+                retimed = Some((subrip.begin_time, subrip.end_time));
+            }
             self.state.body_drag_start = Pos2 { x: 0.0, y: 0.0 };
             self.state.left_drag_start = Pos2 { x: 0.0, y: 0.0 };
             self.state.right_drag_start = Pos2 { x: 0.0, y: 0.0 };
@@ -327,11 +457,7 @@ impl SubripBlock {
                                 let end_time = subrip.get_end_time();
                                 let content = subrip.get_content();
 
-                                ui.label(format!(
-                                    "{} --> {}",
-                                    begin_time.format("%M:%S"),
-                                    end_time.format("%M:%S"),
-                                ));
+                                ui.label(format!("{} --> {}", begin_time, end_time));
                                 ui.label(content);
                             });
                         },
@@ -341,6 +467,9 @@ impl SubripBlock {
         }
 
         drop(subrip);
+        if let Some(new) = retimed {
+            self.push_retime(new);
+        }
 
         if resp.secondary_clicked() {
             self.sig_edit_subrip_win_showed.emit(&self.subrip);
@@ -353,8 +482,8 @@ impl Drawable for SubripBlock {
         let cursor_rect = eui.cursor();
         let mut subrip = self.subrip.borrow_mut();
         let ctnt = subrip.get_content();
-        let duration = subrip.get_duration().num_seconds();
-        let width = duration as f32 / *self.granularity.borrow();
+        let duration = subrip.get_duration().num_milliseconds();
+        let width = duration as f32 / 1000.0 / *self.granularity.borrow();
         let height = BLOCK_HEIGHT;
         let rect = utils::new_rect(
             cursor_rect.left() + self.state.pos.x,
@@ -462,6 +591,10 @@ impl Drawable for SubripBlock {
             );
         }
 
+        if resp.drag_started() {
+            self.state.drag_origin = (subrip.begin_time, subrip.end_time);
+        }
+
         if self.is_hovered_left(&resp) && resp.drag_started() {
             if let Some(new_drag_start_pos) = resp.interact_pointer_pos() {
                 self.state.left_dragging = true;
@@ -510,32 +643,22 @@ impl Drawable for SubripBlock {
         //     }
         // }
 
-        if self.state.body_dragging {
-            if let Some(new_drag_new_pos) = resp.interact_pointer_pos() {
-                let drag_delta = new_drag_new_pos.x - self.state.body_drag_start.x;
-                let time_delta = drag_delta * (*self.granularity.borrow());
-                subrip.add_begin_delta(time_delta);
-                subrip.add_end_delta(time_delta);
-                self.state.pos.x += drag_delta;
-                self.state.body_drag_start.x = new_drag_new_pos.x;
-            }
-        } else if self.state.left_dragging {
-            if let Some(new_drag_new_pos) = resp.interact_pointer_pos() {
-                let drag_delta = new_drag_new_pos.x - self.state.left_drag_start.x;
-                let time_delta = drag_delta * (*self.granularity.borrow());
-                self.state.pos.x += time_delta;
-                subrip.add_begin_delta(time_delta);
-                self.state.left_drag_start.x = new_drag_new_pos.x;
-            }
-        } else if self.state.right_dragging {
+        if let Some(drag_start) = self.state.drag_start() {
             if let Some(new_drag_new_pos) = resp.interact_pointer_pos() {
-                let drag_delta = new_drag_new_pos.x - self.state.right_drag_start.x;
-                subrip.add_end_delta(drag_delta * (*self.granularity.borrow()));
-                self.state.right_drag_start.x = new_drag_new_pos.x;
+                let (begin_time, end_time) =
+                    self.dragged_times(ctx, new_drag_new_pos.x - drag_start.x);
+                let shift_ms = (begin_time - subrip.begin_time).num_milliseconds();
+                self.state.pos.x += shift_ms as f32 / 1000.0 * self.calc_sec_pixels();
+                subrip.begin_time = begin_time;
+                subrip.end_time = end_time;
             }
         }
 
+        let mut retimed = None;
         if resp.drag_stopped() {
+            if self.state.body_dragging || self.state.left_dragging || self.state.right_dragging {
+                retimed = Some((subrip.begin_time, subrip.end_time));
+            }
             self.state.body_drag_start = Pos2 { x: 0.0, y: 0.0 };
             self.state.left_drag_start = Pos2 { x: 0.0, y: 0.0 };
             self.state.right_drag_start = Pos2 { x: 0.0, y: 0.0 };
@@ -547,6 +670,11 @@ impl Drawable for SubripBlock {
         if resp.hovered() {
             if let Some(_pointer_pos) = ctx.pointer_latest_pos() {}
         }
+
+        drop(subrip);
+        if let Some(new) = retimed {
+            self.push_retime(new);
+        }
     }
 }
 
@@ -568,10 +696,24 @@ impl SubripBlockState {
             left_drag_start: Pos2 { x: 0.0, y: 0.0 },
             right_dragging: false,
             right_drag_start: Pos2 { x: 0.0, y: 0.0 },
+            drag_origin: (Timestamp::ZERO, Timestamp::ZERO),
         }
     }
 
     pub fn set_rect(&mut self, rect: egui::Rect) {
         self.rect = rect;
     }
+
+    /// Where the pointer was when the current drag started, `None` without a drag.
+    fn drag_start(&self) -> Option<Pos2> {
+        if self.body_dragging {
+            Some(self.body_drag_start)
+        } else if self.left_dragging {
+            Some(self.left_drag_start)
+        } else if self.right_dragging {
+            Some(self.right_drag_start)
+        } else {
+            None
+        }
+    }
 }
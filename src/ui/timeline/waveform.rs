@@ -0,0 +1,198 @@
+use crate::core::audio_decoder;
+use crate::prelude::*;
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// The audio is decoded at this rate, it's plenty for drawing.
+const SAMPLE_RATE: u32 = 8000;
+/// Each bucket of the summary covers this many MILLISECONDS.
+const BUCKET_MS: i64 = 10;
+const SAMPLES_PER_BUCKET: usize = (SAMPLE_RATE as i64 * BUCKET_MS / 1000) as usize;
+
+/// Peak and RMS of the samples in one bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bucket {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+impl Bucket {
+    fn from_samples(samples: &[f32]) -> Self {
+        let peak = samples.iter().fold(0.0f32, |peak, i| peak.max(i.abs()));
+        let sum_sq = samples.iter().map(|i| i * i).sum::<f32>();
+
+        Self {
+            peak,
+            rms: (sum_sq / samples.len().max(1) as f32).sqrt(),
+        }
+    }
+}
+
+/// Splits decoded samples into buckets, the remainder waits for the next samples.
+#[derive(Default)]
+struct BucketBuilder {
+    pending: Vec<f32>,
+}
+
+impl BucketBuilder {
+    fn push(&mut self, samples: &[f32]) -> Vec<Bucket> {
+        self.pending.extend_from_slice(samples);
+
+        let buckets = self
+            .pending
+            .chunks_exact(SAMPLES_PER_BUCKET)
+            .map(Bucket::from_samples)
+            .collect::<Vec<_>>();
+        self.pending.drain(..buckets.len() * SAMPLES_PER_BUCKET);
+
+        buckets
+    }
+
+    fn finish(self) -> Option<Bucket> {
+        (!self.pending.is_empty()).then(|| Bucket::from_samples(&self.pending))
+    }
+}
+
+/// Peak/RMS summary of the whole audio track.
+#[derive(Default)]
+pub struct WaveformSummary {
+    buckets: Vec<Bucket>,
+}
+
+impl WaveformSummary {
+    /// Merge the buckets covering `[begin_ms, end_ms)`, `None` if they aren't decoded yet.
+    pub fn range(&self, begin_ms: i64, end_ms: i64) -> Option<Bucket> {
+        let begin = (begin_ms.max(0) / BUCKET_MS) as usize;
+        let end = ((end_ms.max(0) + BUCKET_MS - 1) / BUCKET_MS) as usize;
+        let buckets = self
+            .buckets
+            .get(begin..end.max(begin + 1).min(self.buckets.len()))?;
+        if buckets.is_empty() {
+            return None;
+        }
+
+        let peak = buckets.iter().fold(0.0f32, |peak, i| peak.max(i.peak));
+        let mean_sq = buckets.iter().map(|i| i.rms * i.rms).sum::<f32>() / buckets.len() as f32;
+
+        Some(Bucket {
+            peak,
+            rms: mean_sq.sqrt(),
+        })
+    }
+}
+
+/// The waveform lane of [`crate::ui::Timeline`]. The audio is summarized on a background thread
+/// and the lane fills up while decoding goes on.
+#[derive(Default)]
+pub struct Waveform {
+    media_path: Option<PathBuf>,
+    summary: WaveformSummary,
+    receiver: Option<mpsc::Receiver<Vec<Bucket>>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Waveform {
+    /// Start summarizing the audio of `path`, the summary of the same media is reused.
+    pub fn load(&mut self, path: &Path) {
+        if self.media_path.as_deref() == Some(path) {
+            return;
+        }
+
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::new(AtomicBool::new(false));
+        self.media_path = Some(path.to_path_buf());
+        self.summary = WaveformSummary::default();
+
+        let (sender, receiver) = mpsc::channel();
+        let cancel = self.cancel.clone();
+        let path = path.to_path_buf();
+        thread::spawn(move || {
+            let mut builder = BucketBuilder::default();
+            let result = audio_decoder::decode_mono(&path, SAMPLE_RATE, |samples| {
+                let buckets = builder.push(samples);
+                !cancel.load(Ordering::Relaxed)
+                    && (buckets.is_empty() || sender.send(buckets).is_ok())
+            });
+
+            match result {
+                Ok(_) => {
+                    if let Some(bucket) = builder.finish() {
+                        let _ = sender.send(vec![bucket]);
+                    }
+                }
+                Err(err) => error!("Decoding the audio of {} failed: {}", path.display(), err),
+            }
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// Collect the buckets decoded since the last frame.
+    fn poll(&mut self) {
+        if let Some(receiver) = self.receiver.as_ref() {
+            for buckets in receiver.try_iter() {
+                self.summary.buckets.extend(buckets);
+            }
+        }
+    }
+
+    /// Paint the lane into `rect`, one column per pixel.
+    pub fn paint(
+        &mut self,
+        painter: &egui::Painter,
+        rect: egui::Rect,
+        duration_range: &[i64; 2],
+        granularity: f32,
+    ) {
+        self.poll();
+
+        let ms_per_pixel = granularity * 1000.0;
+        let center_y = rect.center().y;
+        let half_height = rect.height() / 2.0;
+        let peak_stroke = egui::Stroke::new(1.0, egui::Color32::from_hex("#B4BEFE").unwrap());
+        let rms_stroke = egui::Stroke::new(1.0, egui::Color32::from_hex("#7287FD").unwrap());
+
+        for x in 0..rect.width() as i64 {
+            let begin_ms = duration_range[0] + (x as f32 * ms_per_pixel) as i64;
+            let end_ms = duration_range[0] + ((x + 1) as f32 * ms_per_pixel) as i64;
+            if begin_ms >= duration_range[1] {
+                break;
+            }
+
+            if let Some(bucket) = self.summary.range(begin_ms, end_ms) {
+                let x = rect.left() + x as f32;
+                for (value, stroke) in [(bucket.peak, peak_stroke), (bucket.rms, rms_stroke)] {
+                    let y = value.min(1.0) * half_height;
+                    painter.line_segment(
+                        [Pos2::new(x, center_y - y), Pos2::new(x, center_y + y)],
+                        stroke,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waveform_summary() {
+        let mut builder = BucketBuilder::default();
+        let mut buckets = builder.push(&vec![0.5; SAMPLES_PER_BUCKET + 10]);
+        assert_eq!(buckets.len(), 1);
+        buckets.extend(builder.push(&vec![-1.0; SAMPLES_PER_BUCKET - 10]));
+        buckets.extend(builder.finish());
+        assert_eq!(buckets.len(), 2);
+
+        let summary = WaveformSummary { buckets };
+        assert_eq!(summary.range(0, BUCKET_MS).unwrap().rms, 0.5);
+        assert_eq!(summary.range(0, BUCKET_MS * 2).unwrap().peak, 1.0);
+        // A pixel narrower than a bucket still shows it.
+        assert!(summary.range(BUCKET_MS + 2, BUCKET_MS + 4).is_some());
+        assert!(summary.range(BUCKET_MS * 2, BUCKET_MS * 3).is_none());
+    }
+}
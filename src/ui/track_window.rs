@@ -0,0 +1,122 @@
+use crate::app::AppState;
+use crate::prelude::*;
+use crate::subrip::SubripFormat;
+use crate::ui::Drawable;
+use crate::TrackId;
+
+/// Add, edit and remove the subtitle tracks, and pick the one new subrips go to.
+pub struct TrackWindow {
+    app_state: Shared<AppState>,
+    visible: bool,
+}
+
+impl TrackWindow {
+    pub fn new(app_state: Shared<AppState>) -> Self {
+        Self {
+            app_state,
+            visible: false,
+        }
+    }
+
+    pub fn toggle_visible(&mut self, _: &()) {
+        self.visible = !self.visible;
+    }
+
+    fn remove_track(&mut self, id: TrackId) {
+        if let Err(err) = self.app_state.borrow_mut().remove_track(id) {
+            error!("Removing the track {} failed: {}", id, err);
+        }
+    }
+}
+
+impl Drawable for TrackWindow {
+    fn draw(&mut self, ctx: &egui::Context, _eui: &mut egui::Ui) {
+        if !self.visible {
+            return;
+        }
+
+        let mut visible = self.visible;
+        let mut removed = None;
+
+        egui::Window::new("Tracks")
+            .collapsible(false)
+            .open(&mut visible)
+            .show(ctx, |eui| {
+                let mut app_state = self.app_state.borrow_mut();
+                let mut active_track = app_state.active_track;
+                let mut default_track = None;
+                let removable = app_state
+                    .tracks
+                    .iter()
+                    .map(|track| {
+                        app_state.tracks.len() > 1
+                            && app_state
+                                .subrips_of_track(track.id)
+                                .iter()
+                                .all(|i| i.borrow().is_deleted())
+                    })
+                    .collect::<Vec<_>>();
+
+                egui::Grid::new("track_grid")
+                    .num_columns(7)
+                    .striped(true)
+                    .show(eui, |eui| {
+                        for header in ["Active", "Name", "Language", "Format", "Default", "Forced"]
+                        {
+                            eui.strong(header);
+                        }
+                        eui.end_row();
+
+                        for (track, removable) in app_state.tracks.iter_mut().zip(removable) {
+                            eui.radio_value(&mut active_track, track.id, "");
+                            eui.add(
+                                egui::TextEdit::singleline(&mut track.name).desired_width(120.0),
+                            );
+                            eui.add(
+                                egui::TextEdit::singleline(&mut track.language)
+                                    .hint_text("en")
+                                    .desired_width(50.0),
+                            );
+                            egui::ComboBox::from_id_source(("track_format", track.id))
+                                .selected_text(track.format.name())
+                                .show_ui(eui, |eui| {
+                                    for i in SubripFormat::ALL {
+                                        eui.selectable_value(&mut track.format, i, i.name());
+                                    }
+                                });
+                            if eui.checkbox(&mut track.is_default, "").changed() && track.is_default
+                            {
+                                default_track = Some(track.id);
+                            }
+                            eui.checkbox(&mut track.is_forced, "");
+                            if eui
+                                .add_enabled(removable, egui::Button::new("🗑"))
+                                .on_disabled_hover_text("Only an empty track can be removed")
+                                .clicked()
+                            {
+                                removed = Some(track.id);
+                            }
+                            eui.end_row();
+                        }
+                    });
+
+                // At most one track is the default one.
+                if let Some(id) = default_track {
+                    for track in app_state.tracks.iter_mut() {
+                        track.is_default = track.id == id;
+                    }
+                }
+                app_state.active_track = active_track;
+
+                if eui.button("Add Track").clicked() {
+                    let name = format!("Track {}", app_state.tracks.len() + 1);
+                    app_state.add_track(name);
+                }
+            });
+
+        self.visible = visible;
+        if let Some(id) = removed {
+            self.remove_track(id);
+        }
+    }
+}
@@ -0,0 +1,581 @@
+use crate::ai::{self, MachineTranslationJob};
+use crate::app::AppState;
+use crate::history::{EditCommand, EditHistory};
+use crate::prelude::*;
+use crate::subrip::{SubripId, Translation};
+use crate::{Subrip, TrackId};
+
+use std::collections::{HashMap, HashSet};
+
+/// How far the translation of a source subrip is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranslationStatus {
+    Untranslated,
+    /// The source text changed after it was translated
+    Stale,
+    Translated,
+}
+
+impl TranslationStatus {
+    pub fn of(source: &Subrip, target: Option<&Subrip>) -> Self {
+        match target {
+            Some(target) if !target.content.trim().is_empty() => {
+                match target.translation.as_ref() {
+                    Some(translation) if translation.source_text == source.content => {
+                        TranslationStatus::Translated
+                    }
+                    _ => TranslationStatus::Stale,
+                }
+            }
+            _ => TranslationStatus::Untranslated,
+        }
+    }
+
+    fn label(&self) -> (&'static str, egui::Color32) {
+        match self {
+            TranslationStatus::Untranslated => {
+                ("untranslated", egui::Color32::from_hex("#D20F39").unwrap())
+            }
+            TranslationStatus::Stale => ("stale", egui::Color32::from_hex("#FE640B").unwrap()),
+            TranslationStatus::Translated => ("✔", egui::Color32::from_hex("#40A02B").unwrap()),
+        }
+    }
+}
+
+/// A source subrip and its translation, if there is one yet.
+pub struct TranslationRow {
+    pub source: Shared<Subrip>,
+    pub target: Option<Shared<Subrip>>,
+    pub status: TranslationStatus,
+}
+
+/// Pair each subrip of `source_track` with its translation in `target_track`, ordered by time.
+pub fn pair_rows(
+    subrips: &[Shared<Subrip>],
+    source_track: TrackId,
+    target_track: TrackId,
+) -> Vec<TranslationRow> {
+    let live = |track: TrackId| {
+        subrips
+            .iter()
+            .filter(move |i| i.borrow().track == track && !i.borrow().is_deleted())
+    };
+
+    // The translations of the target track by the id of their source.
+    let mut targets = HashMap::new();
+    for target in live(target_track) {
+        let source = target.borrow().translation.as_ref().map(|i| i.source);
+        if let Some(source) = source {
+            targets.entry(source).or_insert_with(|| target.clone());
+        }
+    }
+
+    let mut rows = live(source_track)
+        .map(|source| {
+            let target = targets.get(&source.borrow().id).cloned();
+            let status = {
+                let target = target.as_ref().map(|i| i.borrow());
+                TranslationStatus::of(&source.borrow(), target.as_deref())
+            };
+
+            TranslationRow {
+                source: source.clone(),
+                target,
+                status,
+            }
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by_key(|row| row.source.borrow().begin_time);
+
+    rows
+}
+
+/// What the user asked for while drawing [`TranslationView`].
+#[derive(Default)]
+pub struct TranslationViewOutput {
+    /// Translations created by typing into an untranslated row
+    pub created: Vec<Shared<Subrip>>,
+    pub export: Option<TrackId>,
+}
+
+/// A machine translation of the source track running in the background.
+struct PendingMachineTranslation {
+    job: MachineTranslationJob,
+    sources: Vec<Shared<Subrip>>,
+    language: String,
+}
+
+/// The source track and the target track side by side in [`crate::ui::SubripListWidget`].
+/// Translations keep the timing of their source, see [`AppState::retime_command`].
+pub struct TranslationView {
+    app_state: Shared<AppState>,
+    history: Shared<EditHistory>,
+
+    source_track: TrackId,
+    target_track: TrackId,
+    /// Ids of the source subrips picked for machine translation
+    selected: HashSet<SubripId>,
+    /// The language machine translations go to, e.g. `de`
+    machine_language: String,
+    machine_translation: Option<PendingMachineTranslation>,
+    /// Why the last machine translation failed, shown until dismissed
+    machine_translation_error: Option<String>,
+}
+
+impl TranslationView {
+    pub fn new(app_state: Shared<AppState>, history: Shared<EditHistory>) -> Self {
+        Self {
+            app_state,
+            history,
+            source_track: 0,
+            target_track: 0,
+            selected: HashSet::new(),
+            machine_language: String::new(),
+            machine_translation: None,
+            machine_translation_error: None,
+        }
+    }
+
+    /// Machine translate the selected source subrips in the background, into a new track.
+    fn start_machine_translation(&mut self, rows: &[TranslationRow]) {
+        let language = self.machine_language.trim().to_string();
+        let app_state = self.app_state.borrow();
+        let source_language = app_state
+            .track(self.source_track)
+            .map(|i| i.language.clone())
+            .unwrap_or_default();
+        let settings = &app_state.ai_settings.translation;
+
+        let sources = rows
+            .iter()
+            .filter(|row| self.selected.contains(&row.source.borrow().id))
+            .map(|row| row.source.clone())
+            .collect::<Vec<_>>();
+        let texts = sources
+            .iter()
+            .map(|i| i.borrow().content.clone())
+            .collect::<Vec<_>>();
+        info!(
+            "Machine translate {} subrips into {} through {}",
+            texts.len(),
+            language,
+            settings.backend.name()
+        );
+
+        let translator = ai::translator(settings, &source_language, &language);
+        self.machine_translation_error = None;
+        self.machine_translation = Some(PendingMachineTranslation {
+            job: MachineTranslationJob::spawn(translator, texts, settings),
+            sources,
+            language,
+        });
+    }
+
+    /// Put the translations into a new track, which becomes the target track. Undo removes
+    /// the translations in one step, the track is left for removal.
+    fn finish_machine_translation(
+        &mut self,
+        pending: PendingMachineTranslation,
+        result: Result<Vec<String>>,
+    ) -> Vec<Shared<Subrip>> {
+        let translations = match result {
+            Ok(translations) => translations,
+            Err(err) => {
+                error!("Machine translation failed: {}", err);
+                self.machine_translation_error = Some(err.to_string());
+
+                return vec![];
+            }
+        };
+
+        let mut app_state = self.app_state.borrow_mut();
+        let track_id = app_state.add_track(pending.language.clone());
+        if let Some(index) = app_state.track_index(track_id) {
+            app_state.tracks[index].language = pending.language;
+        }
+
+        let created = pending
+            .sources
+            .iter()
+            .zip(translations)
+            .map(|(source, text)| {
+                let source = source.borrow();
+                let mut subrip = Subrip::new(text, source.begin_time, source.get_duration());
+                subrip.translation = Some(Translation {
+                    source: source.id,
+                    source_text: source.content.clone(),
+                });
+                let subrip = Shared::new(subrip);
+                app_state.add_subrip_to_track(&subrip, track_id);
+
+                subrip
+            })
+            .collect::<Vec<_>>();
+        info!("Machine translated {} subrips", created.len());
+        self.target_track = track_id;
+        if !created.is_empty() {
+            let commands = created
+                .iter()
+                .map(|subrip| EditCommand::Create {
+                    subrip: subrip.clone(),
+                })
+                .collect();
+            self.history.borrow_mut().push(EditCommand::Batch(commands));
+        }
+
+        created
+    }
+
+    /// Poll the running machine translation, or offer to start one.
+    fn draw_machine_translation(
+        &mut self,
+        eui: &mut egui::Ui,
+        rows: &[TranslationRow],
+    ) -> Vec<Shared<Subrip>> {
+        let mut created = vec![];
+        if let Some(pending) = self.machine_translation.as_mut() {
+            if let Some(result) = pending.job.poll() {
+                if let Some(pending) = self.machine_translation.take() {
+                    created = self.finish_machine_translation(pending, result);
+                }
+            }
+        }
+
+        eui.horizontal(|eui| {
+            if let Some(pending) = self.machine_translation.as_ref() {
+                eui.spinner();
+                eui.add(
+                    egui::ProgressBar::new(pending.job.progress())
+                        .desired_width(120.0)
+                        .show_percentage(),
+                );
+                eui.label(format!("Translating into {}", pending.language));
+                if eui.button("Cancel").clicked() {
+                    info!("Machine translation cancelled");
+                    pending.job.cancel();
+                    self.machine_translation = None;
+                }
+                // Keep the progress moving without any input.
+                eui.ctx()
+                    .request_repaint_after(std::time::Duration::from_millis(200));
+
+                return;
+            }
+
+            // Subrips removed or moved out of the source track aren't picked anymore.
+            let ids = rows
+                .iter()
+                .map(|row| row.source.borrow().id)
+                .collect::<HashSet<_>>();
+            self.selected.retain(|id| ids.contains(id));
+            if eui.small_button("All").clicked() {
+                self.selected = rows.iter().map(|row| row.source.borrow().id).collect();
+            }
+            if eui.small_button("None").clicked() {
+                self.selected.clear();
+            }
+            eui.label(format!("{} selected", self.selected.len()));
+
+            eui.add(
+                egui::TextEdit::singleline(&mut self.machine_language)
+                    .hint_text("de")
+                    .desired_width(50.0),
+            );
+            let enabled = !self.selected.is_empty() && !self.machine_language.trim().is_empty();
+            if eui
+                .add_enabled(enabled, egui::Button::new("Machine Translate"))
+                .on_hover_text("Translate the selected subrips into a new track")
+                .clicked()
+            {
+                self.start_machine_translation(rows);
+            }
+        });
+
+        if let Some(err) = self.machine_translation_error.clone() {
+            eui.horizontal(|eui| {
+                eui.colored_label(egui::Color32::from_hex("#D20F39").unwrap(), err);
+                if eui.small_button("✕").clicked() {
+                    self.machine_translation_error = None;
+                }
+            });
+        }
+
+        created
+    }
+
+    /// Store the text typed for `row` into the text field `session`, creating the translation
+    /// on the first keystroke.
+    fn set_translation(
+        &self,
+        row: &TranslationRow,
+        text: String,
+        session: egui::Id,
+    ) -> Option<Shared<Subrip>> {
+        let source = row.source.borrow().clone();
+        let translation = Translation {
+            source: source.id,
+            source_text: source.content.clone(),
+        };
+
+        match row.target.as_ref() {
+            Some(target) => {
+                let (old, old_translation) = {
+                    let target = target.borrow();
+                    (target.get_content(), target.translation.clone())
+                };
+                let mut command = EditCommand::EditText {
+                    subrip: target.clone(),
+                    old,
+                    new: text,
+                };
+                // Typing into a stale translation makes it follow the current source text.
+                if old_translation.as_ref() != Some(&translation) {
+                    command = EditCommand::Batch(vec![
+                        EditCommand::SetTranslation {
+                            subrip: target.clone(),
+                            old: old_translation,
+                            new: Some(translation),
+                        },
+                        command,
+                    ]);
+                }
+                command.redo();
+                self.history.borrow_mut().push_typing(command, session);
+
+                None
+            }
+            None => {
+                let mut subrip = Subrip::new(text, source.begin_time, source.get_duration());
+                subrip.translation = Some(translation);
+                let subrip = Shared::new(subrip);
+
+                self.app_state
+                    .borrow_mut()
+                    .add_subrip_to_track(&subrip, self.target_track);
+                self.history.borrow_mut().push(EditCommand::Create {
+                    subrip: subrip.clone(),
+                });
+
+                Some(subrip)
+            }
+        }
+    }
+
+    /// Accept the translation of a stale row as it is, the command isn't applied yet.
+    fn mark_reviewed(row: &TranslationRow) -> Option<EditCommand> {
+        let target = row.target.as_ref()?;
+        let source = row.source.borrow();
+
+        Some(EditCommand::SetTranslation {
+            subrip: target.clone(),
+            old: target.borrow().translation.clone(),
+            new: Some(Translation {
+                source: source.id,
+                source_text: source.content.clone(),
+            }),
+        })
+    }
+
+    fn draw_header(&mut self, eui: &mut egui::Ui, rows: &[TranslationRow]) -> Option<TrackId> {
+        let tracks = self.app_state.borrow().tracks.clone();
+        let mut export = None;
+
+        eui.horizontal(|eui| {
+            for (id_source, track_id) in [
+                ("translation_source_track", &mut self.source_track),
+                ("translation_target_track", &mut self.target_track),
+            ] {
+                let selected_text = tracks
+                    .iter()
+                    .find(|i| i.id == *track_id)
+                    .map(|i| i.name.clone())
+                    .unwrap_or_default();
+                egui::ComboBox::from_id_source(id_source)
+                    .selected_text(selected_text)
+                    .show_ui(eui, |eui| {
+                        for track in tracks.iter() {
+                            eui.selectable_value(track_id, track.id, &track.name);
+                        }
+                    });
+                if id_source == "translation_source_track" {
+                    eui.label("→");
+                }
+            }
+        });
+
+        let count =
+            |status: TranslationStatus| rows.iter().filter(|row| row.status == status).count();
+        eui.horizontal(|eui| {
+            eui.label(format!(
+                "{}/{} translated, {} stale",
+                count(TranslationStatus::Translated),
+                rows.len(),
+                count(TranslationStatus::Stale)
+            ));
+            if eui.button("Export Source").clicked() {
+                export = Some(self.source_track);
+            }
+            if eui.button("Export Translation").clicked() {
+                export = Some(self.target_track);
+            }
+        });
+
+        export
+    }
+
+    pub fn draw(&mut self, eui: &mut egui::Ui) -> TranslationViewOutput {
+        let mut output = TranslationViewOutput::default();
+
+        // Tracks may have been removed since the last frame.
+        {
+            let app_state = self.app_state.borrow();
+            for track_id in [&mut self.source_track, &mut self.target_track] {
+                if app_state.track(*track_id).is_none() {
+                    *track_id = app_state.tracks[0].id;
+                }
+            }
+        }
+
+        let rows = pair_rows(
+            &self.app_state.borrow().subrips,
+            self.source_track,
+            self.target_track,
+        );
+        output.export = self.draw_header(eui, &rows);
+        output.created = self.draw_machine_translation(eui, &rows);
+        eui.separator();
+
+        if self.source_track == self.target_track {
+            eui.label("Pick a target track other than the source track.");
+
+            return output;
+        }
+
+        // Only the visible rows are drawn, those taller than two lines of text make the
+        // scrolling a bit off.
+        let spacing = eui.spacing().item_spacing.y;
+        let row_height = eui.spacing().interact_size.y
+            + 2.0 * eui.text_style_height(&egui::TextStyle::Body)
+            + 2.0 * eui.spacing().button_padding.y
+            + 3.0 * spacing;
+        egui::ScrollArea::vertical().show_rows(eui, row_height, rows.len(), |eui, range| {
+            for row in rows[range].iter() {
+                let source = row.source.borrow().clone();
+                let target = row.target.as_ref().map(|i| i.borrow().clone());
+                let status = row.status;
+                let (status_text, status_color) = status.label();
+
+                eui.horizontal(|eui| {
+                    let mut selected = self.selected.contains(&source.id);
+                    if eui.checkbox(&mut selected, "").changed() {
+                        if selected {
+                            self.selected.insert(source.id);
+                        } else {
+                            self.selected.remove(&source.id);
+                        }
+                    }
+                    eui.label(format!("{} --> {}", source.begin_time, source.end_time));
+                    eui.colored_label(status_color, status_text);
+                    if status == TranslationStatus::Stale
+                        && eui
+                            .small_button("Reviewed")
+                            .on_hover_text(format!(
+                                "Translated from: {}",
+                                target
+                                    .as_ref()
+                                    .and_then(|i| i.translation.as_ref())
+                                    .map(|i| i.source_text.as_str())
+                                    .unwrap_or_default()
+                            ))
+                            .clicked()
+                    {
+                        if let Some(command) = Self::mark_reviewed(row) {
+                            command.redo();
+                            self.history.borrow_mut().push(command);
+                        }
+                    }
+                });
+
+                eui.columns(2, |columns| {
+                    let mut source_text = source.content.clone();
+                    egui::TextEdit::multiline(&mut source_text)
+                        .interactive(false)
+                        .desired_rows(2)
+                        .desired_width(f32::INFINITY)
+                        .show(&mut columns[0]);
+
+                    let mut target_text = target.map(|i| i.content).unwrap_or_default();
+                    let response = egui::TextEdit::multiline(&mut target_text)
+                        .id_source(("translation", source.id))
+                        .desired_rows(2)
+                        .desired_width(f32::INFINITY)
+                        .show(&mut columns[1])
+                        .response;
+                    if response.changed() {
+                        output
+                            .created
+                            .extend(self.set_translation(row, target_text, response.id));
+                    }
+                    if response.lost_focus() {
+                        self.history.borrow_mut().end_session();
+                    }
+                });
+                eui.separator();
+            }
+        });
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timestamp;
+
+    #[test]
+    fn test_translation_rows() {
+        let mut app_state = AppState::default();
+        let target_track = app_state.add_track("German");
+        for (content, begin_ms) in [("world", 2000), ("hello", 0)] {
+            app_state.add_subrip(&Shared::new(Subrip::new(
+                content,
+                Timestamp::from_millis(begin_ms),
+                chrono::Duration::seconds(1),
+            )));
+        }
+
+        let mut translated = Subrip::new(
+            "Welt",
+            Timestamp::from_millis(2000),
+            chrono::Duration::seconds(1),
+        );
+        translated.translation = Some(Translation {
+            source: app_state.subrips[0].borrow().id,
+            source_text: "world".to_string(),
+        });
+        app_state.add_subrip_to_track(&Shared::new(translated), target_track);
+
+        let rows = pair_rows(&app_state.subrips, 0, target_track);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].source.borrow().content, "hello");
+        assert!(rows[0].target.is_none());
+        assert_eq!(rows[1].target.as_ref().unwrap().borrow().content, "Welt");
+
+        assert_eq!(rows[0].status, TranslationStatus::Untranslated);
+        assert_eq!(rows[1].status, TranslationStatus::Translated);
+
+        let status = |row: &TranslationRow| {
+            let target = row.target.as_ref().map(|i| i.borrow());
+            TranslationStatus::of(&row.source.borrow(), target.as_deref())
+        };
+
+        rows[1].source.borrow_mut().set_content("world!");
+        assert_eq!(status(&rows[1]), TranslationStatus::Stale);
+        let command = TranslationView::mark_reviewed(&rows[1]).unwrap();
+        command.redo();
+        assert_eq!(status(&rows[1]), TranslationStatus::Translated);
+        command.undo();
+        assert_eq!(status(&rows[1]), TranslationStatus::Stale);
+    }
+}
@@ -131,53 +131,202 @@ mod lerp {
     }
 }
 
-pub fn naive_time_from_str(str: &str) -> Result<chrono::NaiveTime> {
-    let mins: u32 = str[0..2].parse()?;
-    let secs: u32 = str[3..5].parse()?;
-    let nano: u32 = str[6..9].parse()?;
-    let naive_time =
-        chrono::NaiveTime::from_num_seconds_from_midnight_opt(mins * 60 + secs, nano * 10e5 as u32)
-            .ok_or(anyhow!("Parsing NaiveTime Failed..."))?;
-
-    Ok(naive_time)
+/// The bytes hashed at both ends of a media file.
+const HASH_SAMPLE_SIZE: u64 = 1 << 20;
+
+/// FNV-1a of the size and both ends of the file, so caches survive renaming the media.
+pub fn media_hash(path: &std::path::Path) -> Result<String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut head = vec![];
+    (&mut file).take(HASH_SAMPLE_SIZE).read_to_end(&mut head)?;
+    let mut tail = vec![];
+    file.seek(SeekFrom::Start(size.saturating_sub(HASH_SAMPLE_SIZE)))?;
+    file.read_to_end(&mut tail)?;
+
+    let hash = size
+        .to_le_bytes()
+        .iter()
+        .chain(head.iter())
+        .chain(tail.iter())
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+
+    Ok(format!("{:016x}", hash))
 }
 
-use crate::Subrip;
+use crate::subrip::WordTiming;
+use crate::{Subrip, Timestamp};
 
-pub fn json_str_to_subrips(json_str: &str) -> Result<Vec<Shared<Subrip>>> {
-    let value: serde_json::Value = serde_json::from_str(json_str)?;
-    if let Some(data) = value["data"].as_array() {
-        let mut result = vec![];
-        for subrip_json in data.iter() {
-            let index = subrip_json["index"].as_str().unwrap();
-            let start = subrip_json["start"].as_str().unwrap();
-            let end = subrip_json["end"].as_str().unwrap();
-            let text = subrip_json["text"].as_str().unwrap();
-            let subrip = Subrip::from_vec_str([index, start, end, text])?;
-
-            result.push(Shared::new(subrip));
-        }
+use serde::Deserialize;
 
-        Ok(result)
-    } else {
-        error!("json_str is invalid...");
+/// What the transcription server responds, `{"data": [{"index", "start", "end", "text"}]}`,
+/// the cues may carry `words` and a `speaker` label too.
+#[derive(Deserialize)]
+struct TranscriptionJson {
+    data: Vec<CueJson>,
+}
+
+#[derive(Deserialize)]
+struct CueJson {
+    #[serde(default)]
+    index: Option<IndexJson>,
+    start: TimestampJson,
+    end: TimestampJson,
+    text: String,
+    /// Timed words, their times like those of the cue
+    #[serde(default)]
+    words: Vec<WordJson>,
+    /// Label of a diarized transcription, e.g. `SPEAKER_00`
+    #[serde(default)]
+    speaker: Option<String>,
+}
 
-        Err(anyhow!("json_str is invalid..."))
+#[derive(Deserialize)]
+struct WordJson {
+    #[serde(alias = "word")]
+    text: String,
+    start: TimestampJson,
+    end: TimestampJson,
+}
+
+/// Servers disagree on whether the index is a number or a string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IndexJson {
+    Number(u32),
+    Text(String),
+}
+
+/// Seconds as a number, or a timestamp like `MM:SS.mmm`, `HH:MM:SS.mmm` or `SS.mmm`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TimestampJson {
+    Seconds(f64),
+    Text(String),
+}
+
+impl TimestampJson {
+    fn to_timestamp(&self) -> Result<Timestamp> {
+        match self {
+            TimestampJson::Seconds(secs) => Timestamp::checked_from_secs_f64(*secs)
+                .ok_or_else(|| anyhow!("Timestamp out of range: {}", secs)),
+            TimestampJson::Text(text) => text.parse(),
+        }
     }
 }
 
+pub fn json_str_to_subrips(json_str: &str) -> Result<Vec<Shared<Subrip>>> {
+    let transcription: TranscriptionJson = serde_json::from_str(json_str)
+        .map_err(|err| anyhow!("The transcription is invalid: {}", err))?;
+
+    transcription
+        .data
+        .iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            let invalid =
+                |err: anyhow::Error| anyhow!("Cue {} of the transcription: {}", i + 1, err);
+
+            let index = match cue.index.as_ref() {
+                Some(IndexJson::Number(index)) => *index,
+                Some(IndexJson::Text(index)) => index
+                    .trim()
+                    .parse()
+                    .map_err(|err| invalid(anyhow!("{}", err)))?,
+                None => i as u32 + 1,
+            };
+            let begin_time = cue.start.to_timestamp().map_err(invalid)?;
+            let end_time = cue.end.to_timestamp().map_err(invalid)?;
+            if end_time < begin_time {
+                return Err(invalid(anyhow!("It ends before it begins")));
+            }
+
+            let mut subrip = Subrip::new(cue.text.trim(), begin_time, end_time - begin_time);
+            subrip.set_index(index);
+            if let Some(speaker) = cue.speaker.as_ref() {
+                subrip.style.actor = speaker.trim().to_string();
+            }
+            subrip.words = cue
+                .words
+                .iter()
+                .filter(|word| !word.text.trim().is_empty())
+                .map(|word| {
+                    Ok(WordTiming {
+                        text: word.text.trim().to_string(),
+                        begin: Timestamp::ZERO + (word.start.to_timestamp()? - begin_time),
+                        end: Timestamp::ZERO + (word.end.to_timestamp()? - begin_time),
+                    })
+                })
+                .collect::<Result<_>>()
+                .map_err(invalid)?;
+
+            Ok(Shared::new(subrip))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_naive_time_from_str() {
-        let str = "00:05.001";
-        let naive_time = naive_time_from_str(str).unwrap();
+    fn test_json_str_to_subrips() {
+        let json_str = r#"{"data": [
+            {"index": "1", "start": "00:05.001", "end": "00:10.001", "text": "Hello, World!"}
+        ]}"#;
+        let subrips = json_str_to_subrips(json_str).unwrap();
 
         assert_eq!(
-            naive_time,
-            chrono::NaiveTime::from_num_seconds_from_midnight_opt(5, 10e5 as u32).unwrap()
+            subrips[0].borrow().get_begin_time(),
+            crate::Timestamp::from_millis(5001)
         );
+
+        let json_str = r#"{"data": [
+            {"index": 1, "start": "01:02:03.5", "end": 3725.25, "text": " Numbers "},
+            {"start": 3726, "end": "01:02:07", "text": "No index"}
+        ]}"#;
+        let subrips = json_str_to_subrips(json_str).unwrap();
+        let subrip = subrips[0].borrow();
+        assert_eq!(subrip.get_index(), 1);
+        assert_eq!(subrip.begin_time, Timestamp::from_hms_milli(1, 2, 3, 500));
+        assert_eq!(subrip.end_time, Timestamp::from_hms_milli(1, 2, 5, 250));
+        assert_eq!(subrip.content, "Numbers");
+        assert_eq!(subrips[1].borrow().get_index(), 2);
+        assert_eq!(
+            subrips[1].borrow().end_time,
+            Timestamp::from_hms_milli(1, 2, 7, 0)
+        );
+
+        let json_str = r#"{"data": [{"start": 10, "end": 12, "text": "Hi there", "speaker": "A", "words": [
+            {"word": " Hi", "start": 10.2, "end": 10.5},
+            {"text": "there", "start": "00:10.600", "end": 11.9}
+        ]}]}"#;
+        let subrips = json_str_to_subrips(json_str).unwrap();
+        let words = &subrips[0].borrow().words;
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].text, "Hi");
+        assert_eq!(words[0].begin, Timestamp::from_millis(200));
+        assert_eq!(words[1].end, Timestamp::from_millis(1900));
+        assert_eq!(subrips[0].borrow().style.actor, "A");
+
+        for json_str in [
+            r#"{"data": [{"index": "one", "start": 0, "end": 1, "text": ""}]}"#,
+            r#"{"data": [{"start": "00:0x", "end": 1, "text": ""}]}"#,
+            r#"{"data": [{"start": 2, "end": 1, "text": ""}]}"#,
+            r#"{"data": [{"start": "00:99:00.000", "end": 1, "text": ""}]}"#,
+            r#"{"data": [{"start": 0, "end": 1e300, "text": ""}]}"#,
+            r#"{"data": [{"start": 0, "end": 1, "text": "", "words": [
+                {"word": "Hi", "start": -1e300, "end": 0.5}
+            ]}]}"#,
+            r#"{"data": [{"start": 0, "end": 1}]}"#,
+            r#"{"error": "Out of memory"}"#,
+        ] {
+            assert!(json_str_to_subrips(json_str).is_err());
+        }
     }
 }